@@ -5,27 +5,96 @@
 
 use crate::pools::pool_fetchers::{
     AerodromeFetcher, AlienBaseV2Fetcher, AlienBaseV3Fetcher, BalancerV2Fetcher, BaseSwapV2Fetcher,
-    BaseSwapV3Fetcher, CurveTriCryptoFetcher, CurveTwoCryptoFetcher, DackieSwapV2Fetcher,
+    BaseSwapV3Fetcher, CamelotFetcher, CurveStableSwapFetcher, CurveTriCryptoFetcher,
+    CurveTwoCryptoFetcher, CustomFactoryFetcher,
+    DackieSwapV2Fetcher,
     DackieSwapV3Fetcher, MaverickV1Fetcher, MaverickV2Fetcher, PancakeSwapV2Fetcher,
     PancakeSwapV3Fetcher, SlipstreamFetcher, SushiSwapV2Fetcher, SushiSwapV3Fetcher,
-    SwapBasedV2Fetcher, SwapBasedV3Fetcher, UniswapV2Fetcher, UniswapV3Fetcher,
+    SwapBasedV2Fetcher, SwapBasedV3Fetcher, UniswapV2Fetcher, UniswapV3Fetcher, UniswapV4Fetcher,
+    VelodromeFetcher,
 };
 
 use crate::errors::*;
+use crate::log_cache::LogCache;
+use crate::sync_log::{FanOutSink, JsonLineSink, LogEvent, LogSink};
 use crate::pools::*;
-use crate::{Chain, PoolSync, PoolType};
-use std::collections::HashMap;
+use crate::{Chain, PoolFilter, PoolSync, PoolType, RetryConfig};
+use alloy::primitives::Address;
+use alloy::transports::http::reqwest::Client;
+use dashmap::DashMap;
+use log::warn;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Builder for constructing a PoolSync instance
 #[derive(Default)]
 pub struct PoolSyncBuilder {
     /// Mapping from the pool type to the implementation of its fetcher
-    fetchers: HashMap<PoolType, Arc<dyn PoolFetcher>>,
+    fetchers: BTreeMap<PoolType, Arc<dyn PoolFetcher>>,
     /// The chain to be synced on
     chain: Option<Chain>,
     /// Rate limit on the rpc endpoint
     rate_limit: Option<usize>,
+    /// Factory addresses for each pool type when syncing a custom chain
+    custom_factories: HashMap<PoolType, Address>,
+    /// Skip the liquidity sync and only fetch pool metadata
+    metadata_only: bool,
+    /// Drop pool types that are not supported on the chain instead of erroring
+    skip_unsupported: bool,
+    /// Compress the cache files
+    compress: bool,
+    /// Sinks receiving structured sync events
+    log_sinks: Vec<Arc<dyn LogSink>>,
+    /// Cap on the number of ticks kept for each V3 pool
+    max_ticks_per_pool: Option<usize>,
+    /// Directory of the on disk log cache
+    log_cache: Option<PathBuf>,
+    /// Number of recent blocks a pool must be active in to be kept
+    liquidity_window: Option<u64>,
+    /// Number of post swap prices kept per V3 pool
+    price_history: Option<usize>,
+    /// Number of pool data batch calls in flight at once
+    metadata_concurrency: Option<usize>,
+    /// Token symbols that take precedence over the on chain metadata
+    token_list: HashMap<Address, String>,
+    /// Known token decimals to cross check the on chain values against
+    token_decimals: HashMap<Address, u8>,
+    /// Relative distance from the median beyond which pools are ignored by `robust_price`
+    max_price_deviation: Option<f64>,
+    /// Output receiving each synced pool as a json line
+    ndjson_sink: Option<Box<dyn Write + Send>>,
+    /// Tick distance from the price that the initial V3 tick sync reconstructs
+    initial_tick_window: Option<i32>,
+    /// Connections open at once to each rpc endpoint
+    http_pool_size: Option<usize>,
+    /// Tcp keepalive of rpc connections
+    http_keepalive: Option<Duration>,
+    /// Time an idle rpc connection is kept open
+    http_idle_timeout: Option<Duration>,
+    /// Block range of each pool discovery log query
+    address_batch_size: Option<u64>,
+    /// Pools fetched per info contract call
+    info_batch_size: Option<usize>,
+    /// Block range of each liquidity log query
+    liquidity_step: Option<u64>,
+    /// Websocket endpoint used to follow new blocks
+    ws_endpoint: Option<String>,
+    /// Retries of a failed rpc request
+    retry_limit: Option<usize>,
+    /// Wait before the first retry in milliseconds
+    initial_backoff_ms: Option<u64>,
+    /// Blocks rewound when a reorg is detected
+    reorg_depth: Option<u64>,
+    /// Filters applied to newly discovered pools
+    filters: Vec<Box<dyn PoolFilter>>,
+    /// Unix timestamp that fresh caches start syncing from
+    start_timestamp: Option<u64>,
+    /// Block that fresh caches of a pool type start syncing from
+    start_blocks: HashMap<PoolType, u64>,
 }
 
 impl PoolSyncBuilder {
@@ -65,6 +134,10 @@ impl PoolSyncBuilder {
                 self.fetchers
                     .insert(PoolType::Slipstream, Arc::new(SlipstreamFetcher));
             }
+            PoolType::Velodrome => {
+                self.fetchers
+                    .insert(PoolType::Velodrome, Arc::new(VelodromeFetcher));
+            }
             PoolType::BaseSwapV2 => {
                 self.fetchers
                     .insert(PoolType::BaseSwapV2, Arc::new(BaseSwapV2Fetcher));
@@ -97,6 +170,10 @@ impl PoolSyncBuilder {
                 self.fetchers
                     .insert(PoolType::CurveTriCrypto, Arc::new(CurveTriCryptoFetcher));
             }
+            PoolType::CurveStableSwap => {
+                self.fetchers
+                    .insert(PoolType::CurveStableSwap, Arc::new(CurveStableSwapFetcher));
+            }
             PoolType::BalancerV2 => {
                 self.fetchers
                     .insert(PoolType::BalancerV2, Arc::new(BalancerV2Fetcher));
@@ -117,6 +194,14 @@ impl PoolSyncBuilder {
                 self.fetchers
                     .insert(PoolType::DackieSwapV3, Arc::new(DackieSwapV3Fetcher));
             }
+            PoolType::Camelot => {
+                self.fetchers
+                    .insert(PoolType::Camelot, Arc::new(CamelotFetcher));
+            }
+            PoolType::UniswapV4 => {
+                self.fetchers
+                    .insert(PoolType::UniswapV4, Arc::new(UniswapV4Fetcher));
+            }
         }
         self
     }
@@ -129,6 +214,25 @@ impl PoolSyncBuilder {
         self
     }
 
+    /// Sync on `chain` with every pool type that is supported on it
+    /// The builder instance for method chaining
+    pub fn add_all_supported(self, chain: Chain) -> Self {
+        let supported = chain.supported_pools();
+        self.chain(chain).add_pools(&supported)
+    }
+
+    /// Add every concentrated liquidity pool type that is supported on the chain
+    /// The chain must be set beforehand so unsupported pool types can be filtered out
+    pub fn add_all_concentrated(mut self) -> Self {
+        let concentrated = PoolType::all().iter().copied().filter(PoolType::is_concentrated);
+        for pool_type in concentrated {
+            if self.chain.as_ref().is_none_or(|chain| chain.supported(&pool_type)) {
+                self = self.add_pool(pool_type);
+            }
+        }
+        self
+    }
+
     /// Sets the chain to sync on
     /// The builder instance for method chaining
     pub fn chain(mut self, chain: Chain) -> Self {
@@ -136,6 +240,24 @@ impl PoolSyncBuilder {
         self
     }
 
+    /// Sync on a chain that is not natively supported. The factory address for each pool
+    /// type must be supplied, and only pool types with a factory can be synced. The name can be
+    /// a literal or read at runtime, for example from a config file
+    /// The builder instance for method chaining
+    pub fn custom_chain(
+        mut self,
+        chain_id: u64,
+        name: impl Into<Cow<'static, str>>,
+        factories: HashMap<PoolType, Address>,
+    ) -> Self {
+        self.chain = Some(Chain::Custom {
+            chain_id,
+            name: name.into(),
+        });
+        self.custom_factories = factories;
+        self
+    }
+
     /// Set the rate limit of the rpc
     /// The builder instance for method chaining
     pub fn rate_limit(mut self, rate_limit: usize) -> Self {
@@ -143,15 +265,308 @@ impl PoolSyncBuilder {
         self
     }
 
+    /// Only discover pools and fetch their token metadata, skipping the liquidity sync.
+    /// Useful for building a token/pair registry cheaply
+    /// The builder instance for method chaining
+    pub fn metadata_only(mut self) -> Self {
+        self.metadata_only = true;
+        self
+    }
+
+    /// Write the cache files zlib compressed. V3 caches holding every tick shrink several
+    /// times over, plain caches from earlier syncs still load either way
+    /// The builder instance for method chaining
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Drop pool types that are not supported on the chain with a warning instead of failing
+    /// the build. Lets a single config with every pool type run against any chain
+    /// The builder instance for method chaining
+    pub fn skip_unsupported(mut self) -> Self {
+        self.skip_unsupported = true;
+        self
+    }
+
+    /// Send sync progress events to the sink instead of logging them as text.
+    /// Use a `JsonLineSink` to get one JSON object per line. Every added sink receives each event
+    /// The builder instance for method chaining
+    pub fn log_sink(mut self, sink: Arc<dyn LogSink>) -> Self {
+        self.log_sinks.push(sink);
+        self
+    }
+
+    /// Call `callback` with every sync progress event instead of logging them as text. Shorthand
+    /// for a `log_sink` made from the closure, so it runs alongside the other sinks
+    /// The builder instance for method chaining
+    pub fn on_progress(self, callback: impl Fn(&LogEvent) + Send + Sync + 'static) -> Self {
+        self.log_sink(Arc::new(callback))
+    }
+
+    /// Keep at most `max_ticks` ticks per V3 pool, evicting the ticks furthest from the current
+    /// tick. Bounds memory for deep pools at the cost of accuracy for large quotes
+    /// The builder instance for method chaining
+    pub fn max_ticks_per_pool(mut self, max_ticks: usize) -> Self {
+        self.max_ticks_per_pool = Some(max_ticks);
+        self
+    }
+
+    /// Cache `eth_getLogs` responses in `path` so repeated syncs over the same ranges are
+    /// served from disk. Ranges near the chain head are never cached
+    /// The builder instance for method chaining
+    pub fn log_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.log_cache = Some(path.into());
+        self
+    }
+
+    /// Only track pools that had a liquidity event in the last `blocks` blocks. Inactive pools
+    /// are evicted from memory and the cache. Only factories that list their pools on chain
+    /// will rediscover them, log scanning never revisits old blocks. Turns the sync
+    /// into a tracker of the active pool set
+    /// The builder instance for method chaining
+    pub fn liquidity_window(mut self, blocks: u64) -> Self {
+        self.liquidity_window = Some(blocks);
+        self
+    }
+
+    /// Keep the prices after the last `depth` swaps of every V3 pool, available through
+    /// `UniswapV3Pool::recent_prices`
+    /// The builder instance for method chaining
+    pub fn price_history(mut self, depth: usize) -> Self {
+        self.price_history = Some(depth);
+        self
+    }
+
+    /// Set how many pool data and token metadata batch calls run in parallel. Defaults to the
+    /// rate limit, which also bounds the log fetching concurrency
+    /// The builder instance for method chaining
+    pub fn metadata_concurrency(mut self, concurrency: usize) -> Self {
+        self.metadata_concurrency = Some(concurrency);
+        self
+    }
+
+    /// Supply symbols for tokens whose `symbol()` and `name()` calls return nothing useful.
+    /// Entries are used as is and skip the on chain lookup
+    /// The builder instance for method chaining
+    pub fn token_list(mut self, token_list: HashMap<Address, String>) -> Self {
+        self.token_list = token_list;
+        self
+    }
+
+    /// Cross check the decimals of newly synced pools against a second source. Mismatches are
+    /// logged and flagged through `PoolInfo::decimals_verified`
+    /// The builder instance for method chaining
+    pub fn token_decimals(mut self, token_decimals: HashMap<Address, u8>) -> Self {
+        self.token_decimals = token_decimals;
+        self
+    }
+
+    /// Set how far, as a fraction, a pool price may be from the liquidity weighted median before
+    /// `robust_price` discards it as an outlier. Defaults to 0.05
+    /// The builder instance for method chaining
+    pub fn max_price_deviation(mut self, deviation: f64) -> Self {
+        self.max_price_deviation = Some(deviation);
+        self
+    }
+
+    /// Write every pool as a json line to `writer` as soon as it is synced, so the output can be
+    /// followed during a long sync. Open files with `OpenOptions::append` to keep earlier runs
+    /// The builder instance for method chaining
+    pub fn ndjson_sink(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.ndjson_sink = Some(Box::new(writer));
+        self
+    }
+
+    /// Only reconstruct V3 positions that overlap `window` ticks on either side of the current
+    /// tick during the initial sync. This skips most of the mint and burn history, quotes near
+    /// the price stay exact but liquidity outside the window is missing
+    /// The builder instance for method chaining
+    pub fn initial_tick_window(mut self, window: i32) -> Self {
+        self.initial_tick_window = Some(window);
+        self
+    }
+
+    /// Cap the connections open to each rpc endpoint. Requests past the cap wait for one to
+    /// finish, which keeps highly concurrent syncs from running out of file descriptors
+    /// The builder instance for method chaining
+    pub fn http_pool_size(mut self, pool_size: usize) -> Self {
+        self.http_pool_size = Some(pool_size);
+        self
+    }
+
+    /// Set the tcp keepalive probe interval of rpc connections
+    /// The builder instance for method chaining
+    pub fn http_keepalive(mut self, keepalive: Duration) -> Self {
+        self.http_keepalive = Some(keepalive);
+        self
+    }
+
+    /// Close rpc connections that have been idle for `timeout`
+    /// The builder instance for method chaining
+    pub fn http_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.http_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the block range of each `eth_getLogs` query when discovering new pools. Providers
+    /// differ widely in the range they accept, lower this if discovery queries are rejected
+    /// The builder instance for method chaining
+    pub fn address_batch_size(mut self, blocks: u64) -> Self {
+        self.address_batch_size = Some(blocks);
+        self
+    }
+
+    /// Set how many pools are fetched per `eth_call` when populating pool info. Larger batches
+    /// mean fewer calls but each call does more work and may hit gas or size limits
+    /// The builder instance for method chaining
+    pub fn info_batch_size(mut self, batch_size: usize) -> Self {
+        self.info_batch_size = Some(batch_size);
+        self
+    }
+
+    /// Set the block range of each `eth_getLogs` query during the liquidity sync, for every
+    /// pool type. Tune this to the log range limit of the archive endpoint
+    /// The builder instance for method chaining
+    pub fn liquidity_step(mut self, step: u64) -> Self {
+        self.liquidity_step = Some(step);
+        self
+    }
+
+    /// Set the websocket endpoint that `live_sync` subscribes to for new blocks
+    /// The builder instance for method chaining
+    pub fn ws_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.ws_endpoint = Some(url.into());
+        self
+    }
+
+    /// Set how many times a failed rpc request is retried before the sync gives up on it
+    /// The builder instance for method chaining
+    pub fn retry_limit(mut self, retry_limit: usize) -> Self {
+        self.retry_limit = Some(retry_limit);
+        self
+    }
+
+    /// Set the wait before the first retry of a failed rpc request, it doubles after every
+    /// retry. Raise it for endpoints that need longer to recover
+    /// The builder instance for method chaining
+    pub fn initial_backoff_ms(mut self, backoff_ms: u64) -> Self {
+        self.initial_backoff_ms = Some(backoff_ms);
+        self
+    }
+
+    /// Set how many blocks a pool type is rewound by when the block it was last synced to has
+    /// been reorged out. V2 style pools replay the rewound blocks, the other pool types read
+    /// the state of the pools active in them at the head. Should cover the deepest reorg
+    /// expected on the chain, defaults to 64
+    /// The builder instance for method chaining
+    pub fn reorg_depth(mut self, depth: u64) -> Self {
+        self.reorg_depth = Some(depth);
+        self
+    }
+
+    /// Only sync pools created at or after the unix timestamp `unix_ts`. The timestamp is turned
+    /// into a start block when a sync begins, and only applies to pool types that have not
+    /// been synced before. Cached pool types keep syncing from their last block
+    /// The builder instance for method chaining
+    pub fn start_timestamp(mut self, unix_ts: u64) -> Self {
+        self.start_timestamp = Some(unix_ts);
+        self
+    }
+
+    /// Start syncing `pool_type` from `block` when it has not been synced before. Overrides the
+    /// bundled deployment block of the protocol, a cache that already holds progress keeps
+    /// syncing from its last block
+    /// The builder instance for method chaining
+    pub fn start_block(mut self, pool_type: PoolType, block: u64) -> Self {
+        self.start_blocks.insert(pool_type, block);
+        self
+    }
+
+    /// Only keep the newly discovered pools that `filter` accepts. Can be called multiple
+    /// times, a pool has to pass every filter. Rejected pools are not cached and are not
+    /// looked at again by later syncs
+    /// The builder instance for method chaining
+    pub fn with_filter(mut self, filter: Box<dyn PoolFilter>) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
     /// Consumes the builder and produces a constructed PoolSync
     pub fn build(self) -> Result<PoolSync, PoolSyncError> {
-        // Ensure the chain is set
+        // Ensure the chain is set and that something can be synced on it. Custom chains only
+        // support the pool types they were given factories for
         let chain = self.chain.ok_or(PoolSyncError::ChainNotSet)?;
+        let chain_supported = if chain.is_custom() {
+            !self.custom_factories.is_empty()
+        } else {
+            chain.is_supported()
+        };
+        if !chain_supported {
+            return Err(PoolSyncError::UnsupportedChain(chain));
+        }
+
+        // Batch sizes split the sync into chunks, a size of zero would never make progress
+        if self.address_batch_size == Some(0) {
+            return Err(PoolSyncError::InvalidConfig(
+                "address batch size must be non-zero".to_string(),
+            ));
+        }
+        if self.info_batch_size == Some(0) {
+            return Err(PoolSyncError::InvalidConfig(
+                "info batch size must be non-zero".to_string(),
+            ));
+        }
+        if self.http_pool_size == Some(0) {
+            return Err(PoolSyncError::InvalidConfig(
+                "http pool size must be non-zero".to_string(),
+            ));
+        }
+
+        // every provider shares the client and so its connection pool. Idle connections never
+        // outnumber the open ones
+        let mut http_client = Client::builder();
+        if let Some(pool_size) = self.http_pool_size {
+            http_client = http_client.pool_max_idle_per_host(pool_size);
+        }
+        if let Some(keepalive) = self.http_keepalive {
+            http_client = http_client.tcp_keepalive(keepalive);
+        }
+        if let Some(timeout) = self.http_idle_timeout {
+            http_client = http_client.pool_idle_timeout(timeout);
+        }
+        let http_client = http_client
+            .build()
+            .map_err(|e| PoolSyncError::InvalidConfig(format!("http client: {}", e)))?;
+
+        let mut fetchers = self.fetchers;
+        let custom_factories = self.custom_factories;
+        let supported = |pool_type: &PoolType| {
+            if chain.is_custom() {
+                custom_factories.contains_key(pool_type)
+            } else {
+                chain.supported(pool_type)
+            }
+        };
 
         // Ensure all the pools are supported
-        for pool_type in self.fetchers.keys() {
-            if !chain.supported(pool_type) {
-                return Err(PoolSyncError::UnsupportedPoolType);
+        if self.skip_unsupported {
+            fetchers.retain(|pool_type, _| {
+                let keep = supported(pool_type);
+                if !keep {
+                    warn!("{} is not supported on {}, skipping it", pool_type, chain);
+                }
+                keep
+            });
+        } else if !fetchers.keys().all(supported) {
+            return Err(PoolSyncError::UnsupportedPoolType);
+        }
+
+        // Custom chains sync from the user supplied factories
+        if chain.is_custom() {
+            for (pool_type, fetcher) in fetchers.iter_mut() {
+                let factory = custom_factories[pool_type];
+                *fetcher = Arc::new(CustomFactoryFetcher::new(fetcher.clone(), factory));
             }
         }
 
@@ -160,10 +575,50 @@ impl PoolSyncBuilder {
         let rate_limit = self.rate_limit.unwrap_or(10000) as u64;
 
         // Construct PoolSync
+        let log_cache = match self.log_cache {
+            Some(path) => Some(Arc::new(
+                LogCache::new(path).map_err(|e| PoolSyncError::CacheError(e.to_string()))?,
+            )),
+            None => None,
+        };
+
+        let default_retry = RetryConfig::default();
+        let retry = RetryConfig {
+            retry_limit: self.retry_limit.unwrap_or(default_retry.retry_limit),
+            initial_backoff_ms: self.initial_backoff_ms.unwrap_or(default_retry.initial_backoff_ms),
+        };
+
         Ok(PoolSync {
-            fetchers: self.fetchers,
+            fetchers,
             rate_limit,
             chain,
+            metadata_only: self.metadata_only,
+            compress: self.compress,
+            log_sink: FanOutSink::combine(self.log_sinks),
+            max_ticks_per_pool: self.max_ticks_per_pool,
+            log_cache,
+            liquidity_window: self.liquidity_window,
+            price_history: self.price_history,
+            metadata_concurrency: self.metadata_concurrency.unwrap_or(rate_limit as usize).max(1),
+            token_list: Arc::new(self.token_list),
+            token_cache: Arc::new(DashMap::new()),
+            token_decimals: self.token_decimals,
+            max_price_deviation: self.max_price_deviation.unwrap_or(0.05),
+            ndjson_sink: self.ndjson_sink.map(|writer| Arc::new(JsonLineSink::new(writer))),
+            initial_tick_window: self.initial_tick_window,
+            http_client,
+            http_pool_size: self.http_pool_size,
+            http_permits: DashMap::new(),
+            page_index: Default::default(),
+            address_batch_size: self.address_batch_size,
+            info_batch_size: self.info_batch_size,
+            liquidity_step: self.liquidity_step,
+            ws_endpoint: self.ws_endpoint,
+            retry,
+            reorg_depth: self.reorg_depth.unwrap_or(64),
+            filters: self.filters,
+            start_timestamp: self.start_timestamp,
+            start_blocks: self.start_blocks,
         })
     }
 }
@@ -4,12 +4,30 @@
 //! including structures and functions for reading from and writing to cache files.
 //!
 use crate::chain::Chain;
-use crate::pools::{Pool, PoolType};
-use anyhow::{Context, Result};
+use crate::errors::PoolSyncError;
+use crate::pools::{Pool, PoolInfo, PoolType, POOL_FORMAT_VERSION};
+use alloy::primitives::{Address, B256};
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter};
+use std::io::{BufWriter, Write};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::time::SystemTime;
+
+/// Current format version of the cache files
+pub const CACHE_VERSION: u32 = 2;
+
+/// Leads a compressed cache file, the rest of the file is the zlib compressed JSON. Plain caches
+/// start with the JSON itself so both load
+const COMPRESSED_MARKER: &[u8] = b"PSZ1";
+/// Compression level, favors size since the caches are written once per synced range
+const COMPRESSION_LEVEL: u8 = 9;
+
+/// Upgrades for older caches. The migration at index `i` moves a cache from version `i` to
+/// `i + 1`, new ones are appended to the end along with a bump of `CACHE_VERSION`
+const MIGRATIONS: [fn(&mut PoolCache); CACHE_VERSION as usize] =
+    [seed_last_activity, seed_router_fee];
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PoolCache {
@@ -17,47 +35,375 @@ pub struct PoolCache {
     pub pool_type: PoolType,
     pub pools: Vec<Pool>,
     pub is_initial_sync: bool,
+    /// Last block that pool discovery has fully scanned. Can run ahead of `last_synced_block`
+    /// when a sync is interrupted after discovery
+    #[serde(default)]
+    pub discovery_block: u64,
+    /// Discovered pools that have not been populated yet
+    #[serde(default)]
+    pub pending_addrs: Vec<Address>,
+    /// Number of pools of the factory registry that discovery has listed, for pool types whose
+    /// factory keeps one
+    #[serde(default)]
+    pub registry_count: u64,
+    /// Last block each pool had a liquidity event or was discovered in
+    #[serde(default)]
+    pub last_activity: HashMap<Address, u64>,
+    /// Format version the cache was written with, caches from before versioning read as 0
+    #[serde(default)]
+    pub version: u32,
+    /// Hash of `last_synced_block`, used to notice when a reorg replaced it
+    #[serde(default)]
+    pub last_block_hash: Option<B256>,
+    /// `POOL_FORMAT_VERSION` the pools were serialized with. Caches from before it was
+    /// recorded hold the first pool format
+    #[serde(default = "first_pool_format")]
+    pub pool_format_version: u32,
+}
+
+/// Part of a cache read before the pools so their format is checked before they are decoded
+#[derive(Deserialize)]
+struct PoolCacheHeader {
+    #[serde(default = "first_pool_format")]
+    pool_format_version: u32,
 }
 
-pub fn read_cache_file(pool_type: &PoolType, chain: Chain) -> Result<PoolCache> {
-    let pool_cache_file = format!("cache/{}_{}_cache.json", chain, pool_type);
+fn first_pool_format() -> u32 {
+    1
+}
+
+impl PoolCache {
+    /// Empty cache for a pool type that has never been synced. Ethereum starts syncing from
+    /// block 10M, before any of the supported protocols were deployed
+    pub fn new(pool_type: PoolType, chain: Chain) -> Self {
+        let last_synced_block = if chain == Chain::Ethereum { 9_999_999 } else { 0 };
+        PoolCache {
+            last_synced_block,
+            pool_type,
+            pools: Vec::new(),
+            is_initial_sync: true,
+            discovery_block: 0,
+            pending_addrs: Vec::new(),
+            registry_count: 0,
+            last_activity: HashMap::new(),
+            version: CACHE_VERSION,
+            last_block_hash: None,
+            pool_format_version: POOL_FORMAT_VERSION,
+        }
+    }
+
+    /// Undo the last `depth` synced blocks after a reorg, returns the first block of the
+    /// reorged range. V2 style pools hold the absolute reserves of their last `Sync` event, so
+    /// the range is replayed to repair them. The other pool types apply deltas that a replay
+    /// would count twice, so they keep their block and the pools the range touched have their
+    /// state read again instead, see `reorged_pools`. Discovery moves back for every pool type
+    /// so pools created on the new branch are found
+    pub fn rewind(&mut self, depth: u64, chain: Chain) -> u64 {
+        let first_block = PoolCache::new(self.pool_type, chain).last_synced_block;
+        let rewound = self.last_synced_block.saturating_sub(depth).max(first_block);
+        self.last_block_hash = None;
+        if self.pool_type.is_v2() {
+            self.last_synced_block = rewound;
+        }
+        if self.discovery_start() > rewound + 1 {
+            self.discovery_block = rewound;
+            // pending addresses may come from the orphaned blocks, the rescan finds the
+            // canonical ones again. Registry listed pools are not found by a rescan, so they
+            // stay queued
+            if self.registry_count == 0 {
+                self.pending_addrs.clear();
+            }
+        }
+        rewound + 1
+    }
+
+    /// First block the next discovery window scans. Caches without a discovery cursor, such
+    /// as fresh ones, continue after their last synced block
+    pub fn discovery_start(&self) -> u64 {
+        if self.discovery_block == 0 {
+            self.last_synced_block + 1
+        } else {
+            self.discovery_block + 1
+        }
+    }
+
+    /// Pools with recorded activity from `from_block` on, the pools a reorg of the range
+    /// touched
+    pub fn reorged_pools(&self, from_block: u64) -> HashSet<Address> {
+        self.last_activity
+            .iter()
+            .filter(|(_, block)| **block >= from_block)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Start a cache that has never synced anything at `block` instead of the default start
+    /// block, so only pools created from `block` on are discovered. A cache that was already
+    /// moved keeps the later of the two starts. Caches that already hold progress are left
+    /// alone, returns whether the cache was moved
+    pub fn start_at(&mut self, block: u64, chain: Chain) -> bool {
+        if !self.is_fresh(chain) {
+            return false;
+        }
+        self.last_synced_block = self.last_synced_block.max(block.saturating_sub(1));
+        true
+    }
+
+    /// Whether nothing has been discovered or synced into the cache yet
+    pub fn is_fresh(&self, chain: Chain) -> bool {
+        let fresh = PoolCache::new(self.pool_type, chain);
+        self.is_initial_sync
+            && self.discovery_block == fresh.discovery_block
+            && self.pools.is_empty()
+            && self.pending_addrs.is_empty()
+    }
+
+    /// Apply the migrations the cache is missing in order and stamp it with the current
+    /// version. Returns whether anything was migrated
+    pub fn run_migrations(&mut self) -> bool {
+        let start = self.version as usize;
+        if start >= MIGRATIONS.len() {
+            return false;
+        }
+        for migration in &MIGRATIONS[start..] {
+            migration(self);
+        }
+        self.version = CACHE_VERSION;
+        true
+    }
+
+    /// Record activity for the pools, keeping the latest block seen for each
+    pub fn record_activity(&mut self, activity: impl IntoIterator<Item = (Address, u64)>) {
+        for (address, block) in activity {
+            let last = self.last_activity.entry(address).or_default();
+            *last = (*last).max(block);
+        }
+    }
+
+    /// Drop every pool whose last activity is before `cutoff`, returns how many were dropped.
+    /// Pools without any recorded activity are kept, their activity is unknown rather than old
+    pub fn prune_inactive(&mut self, cutoff: u64) -> usize {
+        let before = self.pools.len();
+        let last_activity = &self.last_activity;
+        self.pools.retain(|pool| {
+            last_activity
+                .get(&pool.address())
+                .is_none_or(|block| *block >= cutoff)
+        });
+        self.last_activity.retain(|_, block| *block >= cutoff);
+        before - self.pools.len()
+    }
+
+    /// Queue discovered addresses for population, skipping the ones that are already cached or
+    /// pending so a rescanned range does not build the same pool twice. Returns how many were
+    /// queued
+    pub fn add_pending(&mut self, addresses: impl IntoIterator<Item = Address>) -> usize {
+        let mut known: HashSet<Address> = self
+            .pools
+            .iter()
+            .map(|pool| pool.address())
+            .chain(self.pending_addrs.iter().copied())
+            .collect();
+        let before = self.pending_addrs.len();
+        self.pending_addrs
+            .extend(addresses.into_iter().filter(|address| known.insert(*address)));
+        self.pending_addrs.len() - before
+    }
+
+    /// Merge freshly synced pools into the cache keyed by address. A pool that is already
+    /// cached is replaced in place by its fresh instance, the rest are appended in order
+    pub fn merge_pools(&mut self, new_pools: Vec<Pool>) {
+        let mut order = Vec::with_capacity(new_pools.len());
+        let mut fresh: HashMap<Address, Pool> = HashMap::with_capacity(new_pools.len());
+        for pool in new_pools {
+            let address = pool.address();
+            if fresh.insert(address, pool).is_none() {
+                order.push(address);
+            }
+        }
+        for pool in self.pools.iter_mut() {
+            if let Some(fresh_pool) = fresh.remove(&pool.address()) {
+                *pool = fresh_pool;
+            }
+        }
+        self.pools.extend(order.into_iter().filter_map(|address| fresh.remove(&address)));
+    }
+
+    /// Drop the pools with the given addresses, returns how many were in the cache
+    pub fn remove_pools(&mut self, addresses: &HashSet<Address>) -> usize {
+        let before = self.pools.len();
+        self.pools.retain(|pool| !addresses.contains(&pool.address()));
+        self.pending_addrs.retain(|address| !addresses.contains(address));
+        self.last_activity.retain(|address, _| !addresses.contains(address));
+        before - self.pools.len()
+    }
+}
+
+// Caches from before activity tracking have no activity for their pools, so the first
+// liquidity window prune would drop every pool. Treat them as active at the last synced block
+fn seed_last_activity(cache: &mut PoolCache) {
+    let block = cache.last_synced_block;
+    let addresses: Vec<Address> = cache.pools.iter().map(|pool| pool.address()).collect();
+    for address in addresses {
+        cache.last_activity.entry(address).or_insert(block);
+    }
+}
+
+// V2 pools cached before the fee of their pool type was recorded quote with the uniswap 0.3%
+fn seed_router_fee(cache: &mut PoolCache) {
+    let fee_bps = cache.pool_type.v2_fee_bps();
+    for pool in cache.pools.iter_mut() {
+        if let Some(pool) = pool.get_v2_mut() {
+            pool.router_fee_bps = pool.router_fee_bps.or(fee_bps);
+        }
+    }
+}
+
+/// Path of the cache file for a pool type. Metadata only syncs get their own cache since
+/// their pools are missing liquidity information
+fn cache_file_path(pool_type: &PoolType, chain: &Chain, metadata_only: bool) -> String {
+    if metadata_only {
+        format!("cache/{}_{}_metadata_cache.json", chain, pool_type)
+    } else {
+        format!("cache/{}_{}_cache.json", chain, pool_type)
+    }
+}
+
+/// Pool types that have a cache file for the chain, found from the cache file names
+pub fn cached_pool_types(chain: Chain, metadata_only: bool) -> Result<Vec<PoolType>> {
+    let dir = Path::new("cache");
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = format!("{}_", chain);
+    let suffix = if metadata_only {
+        "_metadata_cache.json"
+    } else {
+        "_cache.json"
+    };
+    let mut pool_types = Vec::new();
+    for entry in std::fs::read_dir(dir).context("Failed to read the cache directory")? {
+        let file_name = entry?.file_name();
+        let Some(pool_type) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|name| name.strip_suffix(suffix))
+        else {
+            continue;
+        };
+        // names that are not a pool type, like the metadata caches when looking for the full
+        // caches, fail to parse and are skipped
+        if let Ok(pool_type) = serde_json::from_value(pool_type.into()) {
+            pool_types.push(pool_type);
+        }
+    }
+    Ok(pool_types)
+}
+
+/// Serialize the cache to JSON, zlib compressed behind `COMPRESSED_MARKER` when `compress` is set
+pub(crate) fn encode_cache(pool_cache: &PoolCache, compress: bool) -> Result<Vec<u8>> {
+    let json = serde_json::to_vec(pool_cache)?;
+    if !compress {
+        return Ok(json);
+    }
+    let mut bytes = COMPRESSED_MARKER.to_vec();
+    bytes.extend(miniz_oxide::deflate::compress_to_vec_zlib(&json, COMPRESSION_LEVEL));
+    Ok(bytes)
+}
+
+/// Deserialize a cache written by `encode_cache`, compressed or not. Caches holding pools of
+/// another `POOL_FORMAT_VERSION` are rejected with `IncompatibleFormat`
+pub(crate) fn decode_cache(bytes: &[u8]) -> Result<PoolCache> {
+    let json = match bytes.strip_prefix(COMPRESSED_MARKER) {
+        Some(compressed) => miniz_oxide::inflate::decompress_to_vec_zlib(compressed)
+            .map_err(|e| anyhow!("Failed to decompress cache: {}", e))?,
+        None => bytes.to_vec(),
+    };
+    let header: PoolCacheHeader = serde_json::from_slice(&json)?;
+    if header.pool_format_version != POOL_FORMAT_VERSION {
+        return Err(PoolSyncError::IncompatibleFormat {
+            found: header.pool_format_version,
+            expected: POOL_FORMAT_VERSION,
+        }
+        .into());
+    }
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Convert a cache error for the public API, keeping an incompatible pool format distinct
+/// from the other cache errors
+pub(crate) fn cache_error(e: anyhow::Error) -> PoolSyncError {
+    match e.downcast_ref::<PoolSyncError>() {
+        Some(&PoolSyncError::IncompatibleFormat { found, expected }) => {
+            PoolSyncError::IncompatibleFormat { found, expected }
+        }
+        _ => PoolSyncError::CacheError(e.to_string()),
+    }
+}
+
+/// Modification time and size of a cache file, None when there is no cache yet. A cache read
+/// earlier with the same stamp has not been written since
+pub(crate) type CacheStamp = Option<(SystemTime, u64)>;
+
+/// Stamp of the cache file of the pool type
+pub(crate) fn cache_stamp(pool_type: &PoolType, chain: Chain, metadata_only: bool) -> CacheStamp {
+    let metadata = std::fs::metadata(cache_file_path(pool_type, &chain, metadata_only)).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+pub fn read_cache_file(
+    pool_type: &PoolType,
+    chain: Chain,
+    metadata_only: bool,
+) -> Result<PoolCache> {
+    let pool_cache_file = cache_file_path(pool_type, &chain, metadata_only);
     if Path::new(&pool_cache_file).exists() {
-        let file = File::open(&pool_cache_file)
+        let bytes = std::fs::read(&pool_cache_file)
             .with_context(|| format!("Failed to open cache file: {}", pool_cache_file))?;
-        let reader = BufReader::new(file);
-        let pool_cache: PoolCache = serde_json::from_reader(reader).with_context(|| {
+        let mut pool_cache = decode_cache(&bytes).with_context(|| {
             format!("Failed to deserialize cache from file: {}", pool_cache_file)
         })?;
+        // the migrated cache is written back by the next sync
+        pool_cache.run_migrations();
         Ok(pool_cache)
     } else {
-        if Chain::Base == chain {
-            Ok(PoolCache {
-                last_synced_block: 0,
-                pool_type: *pool_type,
-                pools: Vec::new(),
-                is_initial_sync: true,
-            })
-        } else {
-            Ok(PoolCache {
-                last_synced_block: 9_999_999,
-                pool_type: *pool_type,
-                pools: Vec::new(),
-                is_initial_sync: true,
-            })
-        }
+        Ok(PoolCache::new(*pool_type, chain))
     }
 }
 
-pub fn write_cache_file(pool_cache: &PoolCache, chain: Chain) -> Result<()> {
-    let pool_cache_file = format!("cache/{}_{}_cache.json", chain, pool_cache.pool_type);
+/// Write the cache to disk, compressed when `compress` is set. The cache is written to a
+/// temporary file, flushed, and then renamed over the old cache so a crash mid write never
+/// leaves a truncated cache behind
+pub fn write_cache_file(
+    pool_cache: &PoolCache,
+    chain: Chain,
+    metadata_only: bool,
+    compress: bool,
+) -> Result<()> {
+    let pool_cache_file = cache_file_path(&pool_cache.pool_type, &chain, metadata_only);
+    let tmp_cache_file = format!("{}.tmp", pool_cache_file);
     let file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(&pool_cache_file)
-        .with_context(|| format!("Failed to create or open cache file: {}", pool_cache_file))?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer(writer, &pool_cache)
-        .with_context(|| format!("Failed to serialize cache to file: {}", pool_cache_file))?;
+        .open(&tmp_cache_file)
+        .with_context(|| format!("Failed to create or open cache file: {}", tmp_cache_file))?;
+    let mut writer = BufWriter::new(file);
+    let bytes = encode_cache(pool_cache, compress)
+        .with_context(|| format!("Failed to serialize cache to file: {}", tmp_cache_file))?;
+    writer
+        .write_all(&bytes)
+        .with_context(|| format!("Failed to write cache file: {}", tmp_cache_file))?;
+    flush_cache_writer(writer)
+        .with_context(|| format!("Failed to flush cache file: {}", tmp_cache_file))?;
+    std::fs::rename(&tmp_cache_file, &pool_cache_file)
+        .with_context(|| format!("Failed to move cache into place: {}", pool_cache_file))?;
     Ok(())
 }
+
+/// Flush all buffered data and sync it to disk
+fn flush_cache_writer(mut writer: BufWriter<File>) -> std::io::Result<()> {
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
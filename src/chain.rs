@@ -3,18 +3,34 @@
 //! This module defines the supported blockchain networks (Chains) and manages
 //! the mapping of supported pool types for each chain.
 
+use crate::errors::PoolSyncError;
 use crate::PoolType;
+use alloy::primitives::{address, Address};
 use once_cell::sync::Lazy;
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 /// Enum representing supported blockchain networks
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Chain {
     /// Ethereum mainnet
     Ethereum,
     /// Base chain
     Base,
+    /// Arbitrum One
+    Arbitrum,
+    /// Optimism mainnet
+    Optimism,
+    /// BNB Smart Chain
+    BSC,
+    /// A chain the crate does not natively know, configured with user supplied factories
+    Custom {
+        /// The numeric chain id
+        chain_id: u64,
+        /// Name of the chain, used for cache file naming
+        name: Cow<'static, str>,
+    },
     // Additional chains can be added here
 }
 
@@ -39,7 +55,9 @@ static CHAIN_POOLS: Lazy<HashMap<Chain, HashSet<PoolType>>> = Lazy::new(|| {
             PoolType::MaverickV2,
             PoolType::CurveTwoCrypto,
             PoolType::CurveTriCrypto,
+            PoolType::CurveStableSwap,
             PoolType::BalancerV2,
+            PoolType::UniswapV4,
         ]
         .iter()
         .cloned()
@@ -77,12 +95,124 @@ static CHAIN_POOLS: Lazy<HashMap<Chain, HashSet<PoolType>>> = Lazy::new(|| {
         .collect(),
     );
 
+    // Protocols supported by Arbitrum
+    m.insert(
+        Chain::Arbitrum,
+        [
+            PoolType::UniswapV3,
+            PoolType::SushiSwapV2,
+            PoolType::SushiSwapV3,
+            PoolType::PancakeSwapV3,
+            PoolType::CurveTwoCrypto,
+            PoolType::CurveTriCrypto,
+            PoolType::BalancerV2,
+            PoolType::Camelot,
+        ]
+        .iter()
+        .cloned()
+        .collect(),
+    );
+
+    // Protocols supported by Optimism
+    m.insert(
+        Chain::Optimism,
+        [PoolType::UniswapV3, PoolType::Velodrome]
+            .iter()
+            .cloned()
+            .collect(),
+    );
+
+    // Protocols supported by BSC
+    m.insert(
+        Chain::BSC,
+        [
+            PoolType::UniswapV3,
+            PoolType::SushiSwapV2,
+            PoolType::SushiSwapV3,
+            PoolType::PancakeSwapV2,
+            PoolType::PancakeSwapV3,
+            PoolType::CurveTwoCrypto,
+        ]
+        .iter()
+        .cloned()
+        .collect(),
+    );
+
     // Additional chains can be configured here
 
     m
 });
 
 impl Chain {
+    /// Returns true if this is a user configured chain
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Chain::Custom { .. })
+    }
+
+    /// The numeric chain id
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Chain::Ethereum => 1,
+            Chain::Base => 8453,
+            Chain::Arbitrum => 42161,
+            Chain::Optimism => 10,
+            Chain::BSC => 56,
+            Chain::Custom { chain_id, .. } => *chain_id,
+        }
+    }
+
+    /// Canonical (wrapped native, USD stablecoin) pairs on this chain. These are the pairs used
+    /// to bootstrap USD pricing. Custom chains have no known reference tokens
+    pub fn reference_pairs(&self) -> Vec<(Address, Address)> {
+        match self {
+            Chain::Ethereum => vec![
+                (
+                    address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                    address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                ),
+                (
+                    address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                    address!("dAC17F958D2ee523a2206206994597C13D831ec7"),
+                ),
+            ],
+            Chain::Base => vec![(
+                address!("4200000000000000000000000000000000000006"),
+                address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"),
+            )],
+            Chain::Arbitrum => vec![(
+                address!("82aF49447D8a07e3bd95BD0d56f35241523fBab1"),
+                address!("af88d065e77c8cC2239327C5EDb3A432268e5831"),
+            )],
+            Chain::Optimism => vec![(
+                address!("4200000000000000000000000000000000000006"),
+                address!("0b2C639c533813f4Aa9D7837CAf62653d097Ff85"),
+            )],
+            Chain::BSC => vec![(
+                address!("bb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c"),
+                address!("55d398326f99059fF775485246999027B3197955"),
+            )],
+            Chain::Custom { .. } => vec![],
+        }
+    }
+
+    /// Block the factory of `pool_type` was deployed at on this chain, a fresh sync has nothing
+    /// to find before it. Only known for the major protocols
+    pub fn deployment_block(&self, pool_type: PoolType) -> Option<u64> {
+        match (self, pool_type) {
+            (Chain::Ethereum, PoolType::UniswapV2) => Some(10_000_835),
+            (Chain::Ethereum, PoolType::SushiSwapV2) => Some(10_794_229),
+            (Chain::Ethereum, PoolType::UniswapV3) => Some(12_369_621),
+            (Chain::Ethereum, PoolType::BalancerV2) => Some(12_272_146),
+            (Chain::Ethereum, PoolType::UniswapV4) => Some(21_688_329),
+            _ => None,
+        }
+    }
+
+    /// Whether any pool type is natively supported on this chain, false for custom chains
+    pub fn is_supported(&self) -> bool {
+        CHAIN_POOLS.contains_key(self)
+    }
+
     /// Determines if a given pool type is supported on this chain
     pub fn supported(&self, pool_type: &PoolType) -> bool {
         CHAIN_POOLS
@@ -90,11 +220,40 @@ impl Chain {
             .map(|pools| pools.contains(pool_type))
             .unwrap_or(false)
     }
+
+    /// Every pool type supported on this chain, in the order of `PoolType::all`. Custom chains
+    /// have no natively supported pool types
+    pub fn supported_pools(&self) -> Vec<PoolType> {
+        PoolType::all()
+            .iter()
+            .copied()
+            .filter(|pool_type| self.supported(pool_type))
+            .collect()
+    }
+}
+
+// Map a chain id to a natively supported chain, custom chains can not be recovered from an id
+impl TryFrom<u64> for Chain {
+    type Error = PoolSyncError;
+
+    fn try_from(chain_id: u64) -> Result<Self, Self::Error> {
+        match chain_id {
+            1 => Ok(Chain::Ethereum),
+            8453 => Ok(Chain::Base),
+            42161 => Ok(Chain::Arbitrum),
+            10 => Ok(Chain::Optimism),
+            56 => Ok(Chain::BSC),
+            _ => Err(PoolSyncError::UnsupportedChainId(chain_id)),
+        }
+    }
 }
 
 // Display implementation for Chain, used for file naming and debugging purposes
 impl fmt::Display for Chain {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Chain::Custom { name, .. } => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
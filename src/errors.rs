@@ -6,6 +6,8 @@
 
 use thiserror::Error;
 
+use crate::Chain;
+
 /// Enumerates the various error types that can occur during PoolSync operations
 #[derive(Error, Debug)]
 pub enum PoolSyncError {
@@ -21,10 +23,47 @@ pub enum PoolSyncError {
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    /// Represents errors that occur when reading or writing the pool cache
+    #[error("Cache error: {0}")]
+    CacheError(String),
+
     /// Indicates that an unsupported pool type was encountered
     #[error("Pool not supported")]
     UnsupportedPoolType,
 
+    /// The sync was cancelled, progress up to the last checkpoint is kept in the cache
+    #[error("Sync cancelled")]
+    Cancelled,
+
+    /// A swap could not be computed against the pool state
+    #[error("Swap error: {0}")]
+    SwapError(String),
+
+    /// The pool does not hold enough liquidity to consume the whole swap input
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+
+    /// The chain id does not belong to a natively supported chain
+    #[error("Unsupported chain id: {0}")]
+    UnsupportedChainId(u64),
+
+    /// No pool type can be synced on the chain
+    #[error("Chain not supported: {0}")]
+    UnsupportedChain(Chain),
+
+    /// The pool data returned by a data sync contract could not be decoded
+    #[error("Decode error: {0}")]
+    DecodeError(String),
+
+    /// A builder option was set to a value the sync can not run with
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// A serialized pool was written with a format version this crate can not read. Pools
+    /// written before the format was versioned report version 0
+    #[error("Incompatible pool format: found version {found}, expected {expected}")]
+    IncompatibleFormat { found: u32, expected: u32 },
+
     /// Indicates that the chain was not set when it was required
     #[error("Chain not set")]
     ChainNotSet,
@@ -49,3 +49,135 @@ sol!(
         event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1);
     }
 );
+
+// Algebra pools share the V3 Mint and Burn layout. Newer Algebra pools add the fee the swap was
+// charged to the Swap event, older ones emit Fee whenever the dynamic fee changes
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract AlgebraEvents {
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 price,
+            uint128 liquidity,
+            int24 tick,
+            uint24 overrideFee,
+            uint24 pluginFee
+        );
+        event Fee(uint16 fee);
+    }
+);
+
+// StableSwap NG pools. The balances are read from the pool after these events since the
+// amounts do not include the admin fee taken from the balances
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract CurveStableSwapEvents {
+        event TokenExchange(address indexed buyer, int128 sold_id, uint256 tokens_sold, int128 bought_id, uint256 tokens_bought);
+        event AddLiquidity(address indexed provider, uint256[] token_amounts, uint256[] fees, uint256 invariant, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[] token_amounts, uint256[] fees, uint256 token_supply);
+        event RemoveLiquidityOne(address indexed provider, int128 token_id, uint256 token_amount, uint256 coin_amount, uint256 token_supply);
+        event RemoveLiquidityImbalance(address indexed provider, uint256[] token_amounts, uint256[] fees, uint256 invariant, uint256 token_supply);
+        event RampA(uint256 old_A, uint256 new_A, uint256 initial_time, uint256 future_time);
+        event StopRampA(uint256 A, uint256 t);
+        event ApplyNewFee(uint256 fee, uint256 offpeg_fee_multiplier);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract CurveTwoCryptoEvents {
+        event TokenExchange(address indexed buyer, uint256 sold_id, uint256 tokens_sold, uint256 bought_id, uint256 tokens_bought, uint256 fee, uint256 packed_price_scale);
+        event AddLiquidity(address indexed provider, uint256[2] token_amounts, uint256 fee, uint256 token_supply, uint256 packed_price_scale);
+        event RemoveLiquidity(address indexed provider, uint256[2] token_amounts, uint256 token_supply);
+        event RemoveLiquidityOne(address indexed provider, uint256 token_amount, uint256 coin_index, uint256 coin_amount, uint256 approx_fee, uint256 packed_price_scale);
+        event ClaimAdminFee(address indexed admin, uint256[2] tokens);
+        event NewParameters(uint256 mid_fee, uint256 out_fee, uint256 fee_gamma, uint256 allowed_extra_profit, uint256 adjustment_step, uint256 ma_time, uint256 xcp_ma_time);
+        event RampAgamma(uint256 initial_A, uint256 future_A, uint256 initial_gamma, uint256 future_gamma, uint256 initial_time, uint256 future_time);
+        event StopRampA(uint256 current_A, uint256 current_gamma, uint256 time);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract MaverickV1Events {
+        struct BinDelta {
+            uint128 deltaA;
+            uint128 deltaB;
+            uint256 deltaLpBalance;
+            uint128 binId;
+            uint8 kind;
+            int32 lowerTick;
+            bool isActive;
+        }
+        event Swap(address sender, address recipient, bool tokenAIn, bool exactOutput, uint256 amountIn, uint256 amountOut, int32 activeTick);
+        event AddLiquidity(address indexed sender, uint256 indexed tokenId, BinDelta[] binDeltas);
+        event RemoveLiquidity(address indexed sender, address indexed recipient, uint256 indexed tokenId, BinDelta[] binDeltas);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract MaverickV2Events {
+        struct SwapParams {
+            uint256 amount;
+            bool tokenAIn;
+            bool exactOutput;
+            int32 tickLimit;
+        }
+        struct AddLiquidityParams {
+            uint8 kind;
+            int32[] ticks;
+            uint128[] amounts;
+        }
+        struct RemoveLiquidityParams {
+            uint32[] binIds;
+            uint128[] amounts;
+        }
+        event PoolSwap(address sender, address recipient, SwapParams params, uint256 amountIn, uint256 amountOut);
+        event PoolAddLiquidity(address sender, address recipient, uint256 subaccount, AddLiquidityParams params, uint256 tokenAAmount, uint256 tokenBAmount, uint32[] binIds);
+        event PoolRemoveLiquidity(address sender, address recipient, uint256 subaccount, RemoveLiquidityParams params, uint256 tokenAOut, uint256 tokenBOut);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract UniswapV4Events {
+        event Initialize(
+            bytes32 indexed id,
+            address indexed currency0,
+            address indexed currency1,
+            uint24 fee,
+            int24 tickSpacing,
+            address hooks,
+            uint160 sqrtPriceX96,
+            int24 tick
+        );
+        event ModifyLiquidity(
+            bytes32 indexed id,
+            address indexed sender,
+            int24 tickLower,
+            int24 tickUpper,
+            int256 liquidityDelta,
+            bytes32 salt
+        );
+        event Swap(
+            bytes32 indexed id,
+            address indexed sender,
+            int128 amount0,
+            int128 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint24 fee
+        );
+    }
+);
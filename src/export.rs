@@ -0,0 +1,71 @@
+//! Pool Export
+//!
+//! Writers that dump synced pools to JSON or CSV for use outside of the library. Pools are
+//! written one at a time as they are read from the caches so an export never needs every pool
+//! in memory at once.
+
+use crate::errors::PoolSyncError;
+use crate::pools::{Pool, PoolInfo};
+use std::io::Write;
+
+/// Columns of the CSV export, shared by every pool type
+const CSV_HEADER: &str =
+    "address,pool_type,token0,token1,token0_name,token1_name,token0_decimals,token1_decimals,fee";
+
+/// Write the pools as a single JSON array, the same format `Vec<Pool>` deserializes from
+pub(crate) fn write_pools_json<W: Write>(
+    mut writer: W,
+    caches: impl IntoIterator<Item = Result<Vec<Pool>, PoolSyncError>>,
+) -> Result<(), PoolSyncError> {
+    writer.write_all(b"[")?;
+    let mut first = true;
+    for pools in caches {
+        for pool in pools? {
+            if !first {
+                writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut writer, &pool)?;
+            first = false;
+        }
+    }
+    writer.write_all(b"]")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the pools as CSV with one row per pool. Only the fields common to every pool type
+/// are included, extra tokens of multi asset pools are left out
+pub(crate) fn write_pools_csv<W: Write>(
+    mut writer: W,
+    caches: impl IntoIterator<Item = Result<Vec<Pool>, PoolSyncError>>,
+) -> Result<(), PoolSyncError> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for pools in caches {
+        for pool in pools? {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{}",
+                pool.address(),
+                pool.pool_type(),
+                pool.token0_address(),
+                pool.token1_address(),
+                csv_field(&pool.token0_name()),
+                csv_field(&pool.token1_name()),
+                pool.token0_decimals(),
+                pool.token1_decimals(),
+                pool.fee()
+            )?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// token names come from the token contracts, quote them if they could break the row
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -7,17 +7,26 @@
 
 // Public re-exports
 pub use chain::Chain;
-pub use pool_sync::PoolSync;
+pub use log_cache::LogCache;
+pub use pool_filter::{MinReserveFilter, PoolFilter, TokenAllowlistFilter};
+pub use pool_sync::{AggregateDepth, PoolSync, SyncedPools};
 pub use pools::pool_structures::{
     balancer_v2_structure::BalancerV2Pool,
-    maverick_structure::MaverickPool,
+    maverick_structure::{Bin, MaverickPool},
+    stable_swap_curve_structure::CurveStableSwapPool,
     tri_crypto_curve_structure::CurveTriCryptoPool,
     two_crypto_curve_structure::CurveTwoCryptoPool,
     v2_structure::UniswapV2Pool,
     v3_structure::{TickInfo, UniswapV3Pool},
+    v4_structure::UniswapV4Pool,
 };
-pub use pools::{Pool, PoolInfo, PoolType};
-pub use rpc::Rpc;
+pub use pools::{
+    group_equivalent_pools, AmmSimulate, Pool, PoolInfo, PoolType, POOL_FORMAT_VERSION,
+};
+pub use router::Router;
+pub use rpc::{RetryConfig, Rpc};
+pub use snapshot::PoolState;
+pub use sync_log::{JsonLineSink, LogEvent, LogSink};
 
 // Internal modules
 mod builder;
@@ -25,8 +34,15 @@ mod cache;
 mod chain;
 mod errors;
 mod events;
+mod export;
+mod log_cache;
+mod pool_filter;
 mod pool_sync;
 mod pools;
+mod router;
 mod rpc;
+mod snapshot;
+mod sync_log;
+mod transport;
 mod util;
 mod tests;
@@ -0,0 +1,91 @@
+//! On Disk Log Cache
+//!
+//! A read-through cache for `eth_getLogs` responses. Each request is keyed by a hash of its
+//! filter and block range and stored as its own json file. Only ranges that are far enough
+//! behind the chain head to be safe from reorgs are cached.
+
+use alloy::primitives::keccak256;
+use alloy::rpc::types::{Filter, Log};
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of blocks a range must trail the head by before it is cached
+const CACHE_CONFIRMATIONS: u64 = 64;
+
+pub struct LogCache {
+    /// Directory holding the cached responses
+    dir: PathBuf,
+    /// Latest known chain head, ranges close to it are always fetched from the provider
+    head: AtomicU64,
+}
+
+impl LogCache {
+    /// Open a log cache in `dir`, creating the directory if needed
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create log cache dir: {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            head: AtomicU64::new(0),
+        })
+    }
+
+    /// Update the chain head used to decide which ranges are final
+    pub fn set_head(&self, head: u64) {
+        self.head.fetch_max(head, Ordering::Relaxed);
+    }
+
+    /// Cached logs for the filter, if present
+    pub fn get(&self, filter: &Filter) -> Option<Vec<Log>> {
+        let file = File::open(self.entry_path(filter)?).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    /// Store the logs for the filter if its range is final. Entries are written to a
+    /// temporary file and renamed so a partial write is never read back
+    pub fn insert(&self, filter: &Filter, logs: &[Log]) -> Result<()> {
+        let Some(to_block) = filter.get_to_block() else {
+            return Ok(());
+        };
+        if to_block + CACHE_CONFIRMATIONS > self.head.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let Some(path) = self.entry_path(filter) else {
+            return Ok(());
+        };
+
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create log cache entry: {}", tmp_path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, logs)?;
+        writer.flush()?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to move log cache entry: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// The filter sets are hash sets with no stable order, so the key is built from the sorted
+    /// addresses and topics along with the block range
+    fn entry_path(&self, filter: &Filter) -> Option<PathBuf> {
+        let mut key = Vec::new();
+        key.extend(filter.get_from_block()?.to_be_bytes());
+        key.extend(filter.get_to_block()?.to_be_bytes());
+
+        let mut addresses: Vec<_> = filter.address.iter().collect();
+        addresses.sort();
+        key.extend((addresses.len() as u32).to_be_bytes());
+        addresses.iter().for_each(|address| key.extend(address.as_slice()));
+        for topic in &filter.topics {
+            let mut values: Vec<_> = topic.iter().collect();
+            values.sort();
+            key.extend((values.len() as u32).to_be_bytes());
+            values.iter().for_each(|value| key.extend(value.as_slice()));
+        }
+        Some(self.dir.join(format!("{:x}.json", keccak256(key))))
+    }
+}
@@ -0,0 +1,61 @@
+//! Pool Filters
+//!
+//! Filters decide which newly discovered pools are kept by a sync. They run once the pool info
+//! has been fetched and the liquidity events of the synced range have been applied, so a filter
+//! sees the tokens, decimals and the reserves at the end of the range. Pools that are filtered
+//! out are never written to the cache.
+
+use alloy::primitives::{Address, U256};
+use std::collections::HashSet;
+
+use crate::pools::{Pool, PoolInfo};
+
+/// Decides whether a newly discovered pool is kept
+pub trait PoolFilter: Send + Sync {
+    /// Returns false to drop the pool
+    fn keep(&self, pool: &Pool) -> bool;
+}
+
+/// Drops V2 pools holding less than `min_reserve` of either token, in raw token units. Pools of
+/// other types are always kept
+pub struct MinReserveFilter {
+    pub min_reserve: U256,
+}
+
+impl MinReserveFilter {
+    pub fn new(min_reserve: U256) -> Self {
+        Self { min_reserve }
+    }
+}
+
+impl PoolFilter for MinReserveFilter {
+    fn keep(&self, pool: &Pool) -> bool {
+        pool.get_v2().is_none_or(|pool| {
+            pool.token0_reserves >= self.min_reserve && pool.token1_reserves >= self.min_reserve
+        })
+    }
+}
+
+/// Keeps only pools where every token is in the allowlist
+pub struct TokenAllowlistFilter {
+    pub tokens: HashSet<Address>,
+}
+
+impl TokenAllowlistFilter {
+    pub fn new(tokens: impl IntoIterator<Item = Address>) -> Self {
+        Self {
+            tokens: tokens.into_iter().collect(),
+        }
+    }
+}
+
+impl PoolFilter for TokenAllowlistFilter {
+    fn keep(&self, pool: &Pool) -> bool {
+        pool.all_tokens().iter().all(|token| self.tokens.contains(token))
+    }
+}
+
+/// Drop the pools rejected by any of the filters
+pub(crate) fn apply_filters(pools: &mut Vec<Pool>, filters: &[Box<dyn PoolFilter>]) {
+    pools.retain(|pool| filters.iter().all(|filter| filter.keep(pool)));
+}
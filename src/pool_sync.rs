@@ -4,26 +4,212 @@
 //! blockchain networks and protocols. It includes the main `PoolSync` struct and its
 //! associated methods for configuring and executing the synchronization process.
 //!
+use alloy::consensus::BlockHeader;
+use alloy::eips::BlockId;
+use alloy::primitives::{Address, B256, U256};
 use alloy::providers::Provider;
-use alloy::providers::ProviderBuilder;
-use std::collections::HashMap;
-use std::sync::Arc;
+use alloy::network::primitives::HeaderResponse;
+use alloy::network::{AnyNetwork, BlockResponse, Network};
+use alloy::providers::{ProviderBuilder, RootProvider, WsConnect};
+use alloy::rpc::types::{BlockTransactionsKind, Filter, Log};
+use alloy::sol_types::SolEvent;
+use alloy::rpc::client::RpcClient;
+use alloy::transports::http::reqwest::{Client, Url};
+use alloy::transports::http::Http;
+use alloy::transports::Transport;
+use dashmap::DashMap;
+use futures::{Stream, StreamExt, TryStreamExt};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 
 use crate::builder::PoolSyncBuilder;
-use crate::cache::{read_cache_file, write_cache_file, PoolCache};
+use crate::cache::{
+    cache_error, cache_stamp, cached_pool_types, read_cache_file, write_cache_file, CacheStamp,
+    PoolCache,
+};
 use crate::chain::Chain;
 use crate::errors::*;
+use crate::events::{
+    AerodromeSync, AlgebraEvents, BalancerV2Event, DataEvents, PancakeSwapEvents,
+    UniswapV4Events,
+};
+use crate::export::{write_pools_csv, write_pools_json};
+use crate::log_cache::LogCache;
+use crate::pool_filter::{apply_filters, PoolFilter};
+use crate::pools::pool_structures::balancer_v2_structure::process_balance_data;
+use crate::pools::pool_structures::v2_structure::process_sync_data;
+use crate::pools::pool_structures::v3_structure::process_tick_data;
+use crate::pools::pool_structures::v4_structure::process_v4_data;
 use crate::pools::*;
-use crate::rpc::Rpc;
+use crate::rpc::{RetryConfig, Rpc};
+use crate::snapshot::{snapshot_pool, PoolState};
+use crate::sync_log::{JsonLineSink, LogEvent, LogSink};
+use crate::transport::LimitedTransport;
+
+/// Number of blocks scanned for new pools between discovery checkpoints
+const DISCOVERY_CHECKPOINT_BLOCKS: u64 = 1_000_000;
+/// Pools buffered by `sync_pools_stream` before the sync waits for the consumer
+const POOL_STREAM_BUFFER: usize = 1024;
+/// Longest wait between live sync reconnection attempts
+const MAX_LIVE_BACKOFF: Duration = Duration::from_secs(60);
+/// Blocks the live sync applies between writes of its pools back to the caches
+const LIVE_CHECKPOINT_BLOCKS: u64 = 100;
+/// Pool addresses or ids per live log request
+const LIVE_FILTER_POOLS: usize = 1_000;
+
+/// Run the future to completion, or return None as soon as the token is cancelled
+pub(crate) async fn until_cancelled<F: Future>(
+    cancel: Option<&CancellationToken>,
+    future: F,
+) -> Option<F::Output> {
+    match cancel {
+        Some(token) => tokio::select! {
+            _ = token.cancelled() => None,
+            output = future => Some(output),
+        },
+        None => Some(future.await),
+    }
+}
+
+/// Pools of the checkpointed caches along with the lowest block the caches are synced to, the
+/// block every returned pool is at least synced to
+pub(crate) fn synced_progress(pool_caches: Vec<PoolCache>) -> (Vec<Pool>, u64) {
+    let block = pool_caches
+        .iter()
+        .map(|cache| cache.last_synced_block)
+        .min()
+        .unwrap_or(0);
+    let pools = pool_caches
+        .into_iter()
+        .flat_map(|cache| cache.pools)
+        .collect();
+    (pools, block)
+}
+
+/// Hand every pool that was not sent yet, or was active after the state it was last sent with, to
+/// the json lines output and the channel, waiting on a full channel. `sent` maps the pools to the
+/// block of the last activity they were sent with
+pub(crate) async fn publish_updated_pools<W: Write + Send>(
+    pools: &[Pool],
+    last_activity: &HashMap<Address, u64>,
+    sent: &mut HashMap<Address, u64>,
+    tx: Option<&Sender<Pool>>,
+    ndjson: Option<&JsonLineSink<W>>,
+) -> Result<(), PoolSyncError> {
+    for pool in pools {
+        let active = last_activity.get(&pool.address()).copied().unwrap_or_default();
+        if sent.get(&pool.address()).is_some_and(|block| *block >= active) {
+            continue;
+        }
+        sent.insert(pool.address(), active);
+        if let Some(ndjson) = ndjson {
+            ndjson.write_line(pool);
+        }
+        if let Some(tx) = tx {
+            tx.send(pool.clone())
+                .await
+                .map_err(|_| PoolSyncError::Cancelled)?;
+        }
+    }
+    Ok(())
+}
+
+/// Result of a sync with the pools split by where they came from
+#[derive(Debug)]
+pub struct SyncedPools {
+    /// Pools loaded from the cache. Their liquidity was caught up to `last_synced_block` unless
+    /// the sync is metadata only
+    pub loaded: Vec<Pool>,
+    /// Pools discovered and populated in this sync
+    pub discovered: Vec<Pool>,
+    /// The block the pools are synced to
+    pub last_synced_block: u64,
+}
+
+/// Combined liquidity of every synced pool for a pair, in raw token units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AggregateDepth {
+    /// Sum of token a held by the pools, V3 pools contribute their virtual reserves
+    pub total_reserve_a: U256,
+    /// Sum of token b held by the pools
+    pub total_reserve_b: U256,
+    /// Number of pools that contributed
+    pub pool_count: usize,
+}
 
 /// The main struct for pool synchronization
 pub struct PoolSync {
-    /// Map of pool types to their fetcher implementations
-    pub fetchers: HashMap<PoolType, Arc<dyn PoolFetcher>>,
+    /// Map of pool types to their fetcher implementations, iterated in pool type order so
+    /// every sync processes the types in the same order
+    pub fetchers: BTreeMap<PoolType, Arc<dyn PoolFetcher>>,
     /// The chain to sync on
     pub chain: Chain,
     /// The rate limit of the rpc
     pub rate_limit: u64,
+    /// Only discover pools and their token metadata, skipping the liquidity sync
+    pub metadata_only: bool,
+    /// Write the cache files compressed
+    pub compress: bool,
+    /// Receives structured sync events, falls back to text logs when unset
+    pub log_sink: Option<Arc<dyn LogSink>>,
+    /// Maximum number of ticks kept for each V3 pool, all ticks are kept when unset
+    pub max_ticks_per_pool: Option<usize>,
+    /// Read-through cache for log requests
+    pub log_cache: Option<Arc<LogCache>>,
+    /// Only keep pools with activity in this many most recent blocks
+    pub liquidity_window: Option<u64>,
+    /// Number of post swap prices kept per V3 pool
+    pub price_history: Option<usize>,
+    /// Number of pool data batch calls in flight at once
+    pub metadata_concurrency: usize,
+    /// Token symbols used in place of the on chain metadata
+    pub token_list: Arc<HashMap<Address, String>>,
+    /// Token symbols and decimals read during earlier syncs, so popular tokens are only read
+    /// once
+    pub token_cache: Arc<DashMap<Address, (String, u8)>>,
+    /// Known token decimals that new pools are checked against
+    pub token_decimals: HashMap<Address, u8>,
+    /// Fraction a pool price may deviate from the median and still count towards `robust_price`
+    pub max_price_deviation: f64,
+    /// Receives every pool as a json line once it is synced
+    pub ndjson_sink: Option<Arc<JsonLineSink<Box<dyn Write + Send>>>>,
+    /// Only rebuild V3 positions within this many ticks of the price during the initial sync
+    pub initial_tick_window: Option<i32>,
+    /// Http client shared by the rpc providers
+    pub http_client: Client,
+    /// Maximum connections open to each rpc endpoint
+    pub http_pool_size: Option<usize>,
+    /// Permits bounding the connections of each rpc endpoint, shared by its providers
+    pub http_permits: DashMap<Url, Arc<Semaphore>>,
+    /// Pool counts of the caches, kept for `load_pools_paginated`
+    pub(crate) page_index: Mutex<PageIndex>,
+    /// Blocks per log query when discovering pools, defaults to 10000
+    pub address_batch_size: Option<u64>,
+    /// Pools per contract call when fetching pool info, defaults to 50 or 10 for balancer
+    pub info_batch_size: Option<usize>,
+    /// Blocks per log query when syncing liquidity, defaults to the step of the event type
+    pub liquidity_step: Option<u64>,
+    /// Websocket endpoint followed by `live_sync`
+    pub ws_endpoint: Option<String>,
+    /// Retry limit and backoff of failed rpc requests
+    pub retry: RetryConfig,
+    /// Blocks rewound when the last synced block of a cache was reorged out
+    pub reorg_depth: u64,
+    /// Unix timestamp that caches without any progress start syncing from
+    pub start_timestamp: Option<u64>,
+    /// Block that caches without any progress start syncing from, per pool type
+    pub start_blocks: HashMap<PoolType, u64>,
+    /// Filters a new pool has to pass to be kept
+    pub filters: Vec<Box<dyn PoolFilter>>,
 }
 
 impl PoolSync {
@@ -32,24 +218,529 @@ impl PoolSync {
         PoolSyncBuilder::default()
     }
 
+    /// Emit a sync event to the configured sink or the logger
+    pub(crate) fn emit(&self, event: LogEvent) {
+        match &self.log_sink {
+            Some(sink) => sink.emit(&event),
+            None => info!("{}", event),
+        }
+    }
+
+    /// Keep `pools` up to date with every new block. The pools are expected to be synced to
+    /// the last synced block of their caches, like the pools returned by `sync_pools`, and the
+    /// live sync picks up from there. Subscribes to block headers on the configured
+    /// `ws_endpoint` and applies the reserve and liquidity logs of the tracked pools to them in
+    /// place. The pools are written back to the caches every `LIVE_CHECKPOINT_BLOCKS` blocks
+    /// so a restart continues from the checkpoint. Pools touched by reorged blocks have their
+    /// state read again. Dropped connections are retried with exponential backoff and the
+    /// blocks missed in between are caught up. Curve and Maverick pools are not updated.
+    /// Only returns if no websocket endpoint is configured or no cache has been synced
+    pub async fn live_sync(
+        &self,
+        pools: Arc<Mutex<HashMap<Address, Pool>>>,
+    ) -> Result<(), PoolSyncError> {
+        let endpoint = self
+            .ws_endpoint
+            .clone()
+            .ok_or_else(|| PoolSyncError::ProviderError("No ws endpoint configured".to_string()))?;
+
+        let mut synced = HashMap::new();
+        for pool_type in self.fetchers.keys() {
+            let cache = read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                .map_err(cache_error)?;
+            if !cache.is_fresh(self.chain.clone()) {
+                synced.insert(*pool_type, cache.last_synced_block);
+            }
+        }
+        let block = synced.values().copied().min().ok_or_else(|| {
+            PoolSyncError::InvalidConfig("Pools must be synced before following blocks".into())
+        })?;
+        let mut progress = LiveProgress {
+            block,
+            synced,
+            recent: VecDeque::new(),
+            activity: HashMap::new(),
+            checkpoint: block,
+        };
+
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match self
+                .follow_blocks(&endpoint, &pools, &mut progress, &mut backoff)
+                .await
+            {
+                Ok(()) => warn!("Block subscription ended, reconnecting in {:?}", backoff),
+                Err(e) => warn!("Live sync failed: {}, reconnecting in {:?}", e, backoff),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_LIVE_BACKOFF);
+        }
+    }
+
+    /// Apply the logs of each new block until the subscription ends
+    async fn follow_blocks(
+        &self,
+        endpoint: &str,
+        pools: &Mutex<HashMap<Address, Pool>>,
+        progress: &mut LiveProgress,
+        backoff: &mut Duration,
+    ) -> Result<(), PoolSyncError> {
+        let provider = Arc::new(
+            ProviderBuilder::new()
+                .on_ws(WsConnect::new(endpoint))
+                .await
+                .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?,
+        );
+        let mut blocks = provider
+            .subscribe_blocks()
+            .await
+            .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?
+            .into_stream();
+
+        while let Some(header) = blocks.next().await {
+            let block = header.number;
+            // a header that does not extend the last applied range may replace blocks of it,
+            // this also covers a new header at an already applied height
+            let extends = progress.recent.back().is_some_and(|range| {
+                block == range.to_block + 1 && header.parent_hash == range.hash
+            });
+            let mut reorged = HashSet::new();
+            if !extends {
+                let hash_of = |number: u64| block_hash(provider.as_ref(), number);
+                if let Some((from_block, touched)) =
+                    unwind_live_reorg(&mut progress.recent, block, hash_of).await?
+                {
+                    warn!("Live synced blocks from {} were reorged out", from_block);
+                    progress.block = progress.block.min(from_block - 1);
+                    reorged = touched;
+                }
+            }
+
+            // also covers the blocks missed while reconnecting
+            let from_block = progress.block + 1;
+            if from_block > block {
+                continue;
+            }
+            let filters = live_filters(&pools.lock().unwrap());
+            let mut logs = Vec::new();
+            for filter in filters {
+                logs.extend(
+                    provider
+                        .get_logs(&filter.from_block(from_block).to_block(block))
+                        .await
+                        .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?,
+                );
+            }
+
+            let mut updated = HashSet::new();
+            {
+                let mut pools = pools.lock().unwrap();
+                for log in Rpc::order_logs(logs).into_values().flatten() {
+                    let address = Rpc::log_pool_address(&log);
+                    // reorged pools are read at the new block below, and logs up to the block
+                    // a cache was synced to are already in its pools
+                    let Some(pool) = pools.get(&address) else {
+                        continue;
+                    };
+                    let synced = progress.synced.get(&pool.pool_type()).copied().unwrap_or(0);
+                    if reorged.contains(&address) || log.block_number <= Some(synced) {
+                        continue;
+                    }
+                    if apply_live_log(&mut pools, log) {
+                        updated.insert(address);
+                    }
+                }
+            }
+            if !reorged.is_empty() {
+                let mut stale: Vec<Pool> = {
+                    let pools = pools.lock().unwrap();
+                    reorged.iter().filter_map(|address| pools.get(address).cloned()).collect()
+                };
+                self.refresh_pools(&mut stale, &reorged, provider.clone(), block).await?;
+                let mut pools = pools.lock().unwrap();
+                for pool in stale {
+                    updated.insert(pool.address());
+                    pools.insert(pool.address(), pool);
+                }
+            }
+
+            progress.block = block;
+            progress.activity.extend(updated.iter().map(|address| (*address, block)));
+            progress.recent.push_back(LiveRange {
+                from_block,
+                to_block: block,
+                hash: header.hash,
+                updated: updated.clone(),
+            });
+            while progress
+                .recent
+                .front()
+                .is_some_and(|range| range.to_block + self.reorg_depth < block)
+            {
+                progress.recent.pop_front();
+            }
+            if block >= progress.checkpoint + LIVE_CHECKPOINT_BLOCKS {
+                self.checkpoint_live(pools, progress, header.hash)?;
+            }
+            *backoff = Duration::from_secs(1);
+            self.emit(LogEvent::LiveBlockSynced {
+                block,
+                updated_pools: updated.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Write the live pools back to the caches at the last applied block
+    fn checkpoint_live(
+        &self,
+        pools: &Mutex<HashMap<Address, Pool>>,
+        progress: &mut LiveProgress,
+        hash: B256,
+    ) -> Result<(), PoolSyncError> {
+        for pool_type in progress.synced.keys() {
+            let mut cache = read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                .map_err(cache_error)?;
+            {
+                let pools = pools.lock().unwrap();
+                for pool in cache.pools.iter_mut() {
+                    if let Some(live) = pools.get(&pool.address()) {
+                        *pool = live.clone();
+                    }
+                }
+            }
+            let cached: HashSet<Address> = cache.pools.iter().map(|pool| pool.address()).collect();
+            cache.record_activity(
+                progress
+                    .activity
+                    .iter()
+                    .filter(|(address, _)| cached.contains(*address))
+                    .map(|(address, block)| (*address, *block)),
+            );
+            cache.last_synced_block = progress.block;
+            cache.last_block_hash = Some(hash);
+            write_cache_file(&cache, self.chain.clone(), self.metadata_only, self.compress)
+                .map_err(|e| PoolSyncError::CacheError(e.to_string()))?;
+        }
+        progress.activity.clear();
+        progress.checkpoint = progress.block;
+        Ok(())
+    }
+
+    /// Pools of the cache that a reorg of `from_block..=to_block` touched, the ones with activity
+    /// recorded from the replaced blocks along with the ones with events in the canonical blocks
+    async fn reorged_pools(
+        &self,
+        cache: &PoolCache,
+        from_block: u64,
+        to_block: u64,
+        provider: Arc<RootProvider<LimitedTransport<Http<Client>>, AnyNetwork>>,
+    ) -> Result<HashSet<Address>, PoolSyncError> {
+        let mut reorged = cache.reorged_pools(from_block);
+        let known: HashSet<Address> = cache.pools.iter().map(|pool| pool.address()).collect();
+        let logs = Rpc::fetch_pool_events(
+            from_block,
+            to_block,
+            provider,
+            cache.pool_type,
+            self.rate_limit,
+            self.log_cache.clone(),
+            self.retry,
+        )
+        .await
+        .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+        reorged.extend(
+            logs.iter()
+                .map(Rpc::log_pool_address)
+                .filter(|address| known.contains(address)),
+        );
+        Ok(reorged)
+    }
+
+    /// Read the state of the pools in `addresses` at `block`, see `Pool::refresh_state`
+    async fn refresh_pools<P, T, N>(
+        &self,
+        pools: &mut [Pool],
+        addresses: &HashSet<Address>,
+        provider: Arc<P>,
+        block: u64,
+    ) -> Result<(), PoolSyncError>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        futures::stream::iter(
+            pools
+                .iter_mut()
+                .filter(|pool| addresses.contains(&pool.address()))
+                .map(|pool| pool.refresh_state(provider.clone(), block)),
+        )
+        .buffer_unordered(self.rate_limit.max(1) as usize)
+        .try_collect()
+        .await
+    }
+
+    /// Move the caches without any progress to the configured start block of their pool type,
+    /// or to the deployment block of the protocol when none is configured
+    pub(crate) fn apply_start_blocks(&self, pool_caches: &mut [PoolCache]) {
+        for cache in pool_caches {
+            let start = self
+                .start_blocks
+                .get(&cache.pool_type)
+                .copied()
+                .or_else(|| self.chain.deployment_block(cache.pool_type));
+            if let Some(block) = start {
+                if cache.start_at(block, self.chain.clone()) {
+                    info!("Starting {} sync at block {}", cache.pool_type, block);
+                }
+            }
+        }
+    }
+
+    /// First block with a timestamp at or after `unix_ts`, found by binary searching the block
+    /// headers of the full node. Fails if the latest block is older than the timestamp
+    pub async fn block_at_timestamp(&self, unix_ts: u64) -> Result<u64, PoolSyncError> {
+        let full = self.connect_env("FULL")?;
+        let latest = full
+            .get_block_number()
+            .await
+            .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+        first_block_at(latest, unix_ts, |number| block_timestamp(&full, number)).await
+    }
+
+    /// Provider for the rpc endpoint in the environment variable `var`, loading a `.env` file
+    /// first if there is one
+    fn connect_env(
+        &self,
+        var: &str,
+    ) -> Result<RootProvider<LimitedTransport<Http<Client>>, AnyNetwork>, PoolSyncError> {
+        dotenv::dotenv().ok();
+        let url = std::env::var(var)
+            .map_err(|_| PoolSyncError::ProviderError(format!("{} endpoint not set", var)))?;
+        let url = url.parse().map_err(|e| PoolSyncError::ProviderError(format!("{}", e)))?;
+        Ok(self.connect(url))
+    }
+
+    /// Provider for the rpc endpoint, using the configured http client and connection limit
+    fn connect(&self, url: Url) -> RootProvider<LimitedTransport<Http<Client>>, AnyNetwork> {
+        let permits = self.http_pool_size.map(|limit| {
+            self.http_permits
+                .entry(url.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+                .clone()
+        });
+        let http = Http::with_client(self.http_client.clone(), url);
+        let is_local = http.guess_local();
+        ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_client(RpcClient::new(LimitedTransport::new(http, permits), is_local))
+    }
+
     /// Synchronizes all added pools for the specified chain
     pub async fn sync_pools(&self) -> Result<(Vec<Pool>, u64), PoolSyncError> {
-        // load in the dotenv
-        dotenv::dotenv().ok();
+        let (pool_caches, _, last_synced_block) = self.sync_caches(None, None).await?;
+        Ok((
+            pool_caches
+                .into_iter()
+                .flat_map(|cache| cache.pools)
+                .collect(),
+            last_synced_block,
+        ))
+    }
+
+    /// Synchronizes all added pools like `sync_pools`, stopping early with
+    /// `PoolSyncError::Cancelled` once the token is cancelled. Progress is checkpointed to the
+    /// cache after every discovery window and synced range, so the next sync resumes from there
+    pub async fn sync_pools_with_cancel(
+        &self,
+        token: CancellationToken,
+    ) -> Result<(Vec<Pool>, u64), PoolSyncError> {
+        let (pool_caches, _, last_synced_block) = self.sync_caches(Some(&token), None).await?;
+        Ok((
+            pool_caches
+                .into_iter()
+                .flat_map(|cache| cache.pools)
+                .collect(),
+            last_synced_block,
+        ))
+    }
+
+    /// Synchronizes all added pools like `sync_pools` until `shutdown` resolves. The sync then
+    /// stops at the next batch of blocks and returns the pools checkpointed so far along with
+    /// the block every one of them has reached, instead of failing with
+    /// `PoolSyncError::Cancelled`. The next sync resumes from the checkpoints
+    pub async fn sync_pools_with_shutdown(
+        &self,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(Vec<Pool>, u64), PoolSyncError> {
+        let token = CancellationToken::new();
+        let sync = self.sync_caches(Some(&token), None);
+        tokio::pin!(sync);
+        let result = tokio::select! {
+            result = &mut sync => result,
+            _ = shutdown => {
+                token.cancel();
+                sync.await
+            }
+        };
+
+        match result {
+            Ok((pool_caches, _, last_synced_block)) => Ok((
+                pool_caches
+                    .into_iter()
+                    .flat_map(|cache| cache.pools)
+                    .collect(),
+                last_synced_block,
+            )),
+            Err(PoolSyncError::Cancelled) => {
+                info!("Sync shut down, returning the pools synced so far");
+                let mut pool_caches = Vec::new();
+                for pool_type in self.fetchers.keys() {
+                    pool_caches.push(
+                        read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                            .map_err(cache_error)?,
+                    );
+                }
+                Ok(synced_progress(pool_caches))
+            }
+            Err(e) => Err(e),
+        }
+    }
 
+    /// Synchronizes all added pools like `sync_pools`, sending each pool into `tx` once it is
+    /// populated and caught up instead of returning them. Pools of a pool type are sent as soon
+    /// as its range is synced, and a pool is sent again with its new state when a later range
+    /// updates it, so the last pool received for an address is the latest. Sending waits while
+    /// the channel is full. Returns the block the pools are synced to. Dropping the receiver stops
+    /// the sync with `PoolSyncError::Cancelled`
+    pub async fn sync_pools_to_channel(&self, tx: Sender<Pool>) -> Result<u64, PoolSyncError> {
+        let (_, _, last_synced_block) = self.sync_caches(None, Some(&tx)).await?;
+        Ok(last_synced_block)
+    }
+
+    /// Synchronizes all added pools like `sync_pools_to_channel`, yielding every pool from the
+    /// returned stream once it is populated and caught up, and again when a later range updates
+    /// it. The caches are written as usual. A failed sync ends the stream with its error and
+    /// dropping the stream stops the sync
+    pub fn sync_pools_stream(&self) -> impl Stream<Item = Result<Pool, PoolSyncError>> + '_ {
+        let (tx, rx) = tokio::sync::mpsc::channel(POOL_STREAM_BUFFER);
+        stream_synced_pools(self.sync_pools_to_channel(tx), rx)
+    }
+
+    /// Populate the given pools of `pool_type` up to the latest block without scanning the
+    /// factory for them. Only the liquidity logs of these pools are requested, starting at the
+    /// protocol deployment when it is known. Addresses that are not pools of that type are
+    /// skipped. The pools are returned as is and not written to the cache
+    pub async fn sync_addresses(
+        &self,
+        pool_type: PoolType,
+        addresses: Vec<Address>,
+    ) -> Result<Vec<Pool>, PoolSyncError> {
+        let fetcher = self
+            .fetchers
+            .get(&pool_type)
+            .ok_or(PoolSyncError::UnsupportedPoolType)?
+            .clone();
+        let archive = Arc::new(self.connect_env("ARCHIVE")?);
+        let full = Arc::new(self.connect_env("FULL")?);
+        let end_block = full
+            .get_block_number()
+            .await
+            .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+
+        let mut seen = HashSet::new();
+        let addresses = addresses.into_iter().filter(|address| seen.insert(*address)).collect();
+        // nothing of the protocol exists before its deployment
+        let start_block = PoolCache::new(pool_type, self.chain.clone())
+            .last_synced_block
+            .max(self.chain.deployment_block(pool_type).unwrap_or(0).saturating_sub(1))
+            + 1;
+        let populated = if pool_type.is_v4() {
+            // v4 pools are keyed by their truncated pool id and built from the Initialize logs
+            Rpc::populate_v4_pools(
+                start_block,
+                end_block,
+                addresses,
+                archive.clone(),
+                fetcher,
+                self.chain.clone(),
+                self.rate_limit,
+                self.log_cache.clone(),
+                self.token_list.clone(),
+                self.token_cache.clone(),
+                self.retry,
+                self.address_batch_size,
+            )
+            .await
+        } else {
+            Rpc::populate_pools(
+                addresses,
+                full,
+                pool_type,
+                fetcher,
+                self.rate_limit,
+                self.chain.clone(),
+                self.metadata_concurrency,
+                self.token_list.clone(),
+                self.token_cache.clone(),
+                self.info_batch_size,
+                self.retry,
+            )
+            .await
+        };
+        let mut pools = populated.map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+        // other contracts may answer the info calls without reverting, but not with two tokens
+        pools.retain(|pool| pool.is_valid());
+
+        if !self.metadata_only {
+            Rpc::populate_liquidity(
+                start_block,
+                end_block,
+                &mut pools,
+                archive,
+                pool_type,
+                self.rate_limit,
+                true,
+                self.log_cache.clone(),
+                self.initial_tick_window,
+                self.liquidity_step,
+                self.retry,
+                true,
+            )
+            .await
+            .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+        }
+        Ok(pools)
+    }
+
+    /// Synchronizes all added pools like `sync_pools`, keeping the pools that were loaded from
+    /// the cache apart from the pools discovered in this run
+    pub async fn sync_pools_by_origin(&self) -> Result<SyncedPools, PoolSyncError> {
+        let (pool_caches, loaded, last_synced_block) = self.sync_caches(None, None).await?;
+        let (loaded, discovered) = pool_caches
+            .into_iter()
+            .flat_map(|cache| cache.pools)
+            .partition(|pool| loaded.contains(&pool.address()));
+        Ok(SyncedPools {
+            loaded,
+            discovered,
+            last_synced_block,
+        })
+    }
+
+    /// Run the sync and return the updated caches along with the addresses of the pools that
+    /// were loaded from them
+    async fn sync_caches(
+        &self,
+        cancel: Option<&CancellationToken>,
+        pool_tx: Option<&Sender<Pool>>,
+    ) -> Result<(Vec<PoolCache>, HashSet<Address>, u64), PoolSyncError> {
         // setup arvhice node provider
-        let archive = Arc::new(
-            ProviderBuilder::new()
-                .network::<alloy::network::AnyNetwork>()
-                .on_http(std::env::var("ARCHIVE").unwrap().parse().unwrap()),
-        );
+        let archive = Arc::new(self.connect_env("ARCHIVE")?);
 
         // setup full node provider
-        let full = Arc::new(
-            ProviderBuilder::new()
-                .network::<alloy::network::AnyNetwork>()
-                .on_http(std::env::var("FULL").unwrap().parse().unwrap()),
-        );
+        let full = Arc::new(self.connect_env("FULL")?);
 
         // create the cache files
         std::fs::create_dir_all("cache").unwrap();
@@ -58,103 +749,1166 @@ impl PoolSync {
         let mut pool_caches: Vec<PoolCache> = self
             .fetchers
             .keys()
-            .map(|pool_type| read_cache_file(pool_type, self.chain).unwrap())
+            .map(|pool_type| read_cache_file(pool_type, self.chain.clone(), self.metadata_only))
+            .collect::<anyhow::Result<_>>()
+            .map_err(cache_error)?;
+        let loaded: HashSet<Address> = pool_caches
+            .iter()
+            .flat_map(|cache| cache.pools.iter().map(|pool| pool.address()))
             .collect();
 
+        self.apply_start_blocks(&mut pool_caches);
+
+        // caches that have never synced start at the configured date. The block is only looked
+        // up when there is such a cache, it takes a few dozen header requests
+        if let Some(unix_ts) = self.start_timestamp {
+            if pool_caches.iter().any(|cache| cache.is_fresh(self.chain.clone())) {
+                let latest = full
+                    .get_block_number()
+                    .await
+                    .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+                let block =
+                    first_block_at(latest, unix_ts, |number| block_timestamp(&full, number)).await?;
+                for cache in &mut pool_caches {
+                    if cache.start_at(block, self.chain.clone()) {
+                        info!("Starting {} sync at block {}", cache.pool_type, block);
+                    }
+                }
+            }
+        }
+
+        self.emit(LogEvent::SyncStarted {
+            chain: self.chain.to_string(),
+            pool_types: pool_caches.iter().map(|cache| cache.pool_type).collect(),
+        });
+
         let mut fully_synced = false;
         let mut last_synced_block = 0;
+        let mut sent = HashMap::new();
 
         while !fully_synced {
             fully_synced = true;
             let end_block = full.get_block_number().await.unwrap();
+            if let Some(log_cache) = &self.log_cache {
+                log_cache.set_head(end_block);
+            }
+            let end_hash = block_hash(&full, end_block).await?;
 
             for cache in &mut pool_caches {
+                // pools a reorg touched that are read again once the range is synced
+                let mut reorged = HashSet::new();
+                if cache.last_block_hash.is_some() {
+                    let synced_block = cache.last_synced_block;
+                    let canonical = block_hash(&full, synced_block).await?;
+                    if canonical.is_none() {
+                        debug!("Node has no block {} yet, reorg check skipped", synced_block);
+                    }
+                    if let Some(from_block) =
+                        rewind_on_reorg(cache, canonical, self.reorg_depth, self.chain.clone())
+                    {
+                        warn!(
+                            "Block {} synced for {} was reorged out, rewinding to block {}",
+                            synced_block, cache.pool_type, from_block
+                        );
+                        if !cache.pool_type.is_v2() {
+                            reorged = self
+                                .reorged_pools(cache, from_block, synced_block, archive.clone())
+                                .await?;
+                        }
+                    }
+                }
+
                 let start_block = cache.last_synced_block + 1;
                 if start_block <= end_block {
                     fully_synced = false;
 
                     let fetcher = self.fetchers[&cache.pool_type].clone();
-
-                    // fetch all of the pool addresses
-                    let pool_addrs = Rpc::fetch_pool_addrs(
+                    // pools found in a rescanned reorg window replay their liquidity from there
+                    let mut new_pools_from = start_block;
+                    self.emit(LogEvent::RangeStarted {
+                        pool_type: cache.pool_type,
                         start_block,
                         end_block,
-                        archive.clone(),
-                        fetcher.clone(),
-                        self.chain,
-                        self.rate_limit,
-                    )
-                    .await
-                    .expect(
-                        "Failed to fetch pool addresses. Exiting due to having inconclusive state",
-                    );
+                    });
 
-                    // populate all of the pool data
-                    let mut new_pools = Rpc::populate_pools(
-                        pool_addrs,
-                        full.clone(),
-                        cache.pool_type,
-                        fetcher.clone(),
-                        self.rate_limit,
-                        self.chain,
-                    )
-                    .await
-                    .expect("Failed to sync pool data, Exiting due to haveing inconclusive state");
+                    if fetcher.pool_registry().is_some() {
+                        // the registry lists every pool, the ones past the listed count are new
+                        if cache.discovery_block < end_block {
+                            let (pool_addrs, count) = until_cancelled(
+                                cancel,
+                                Rpc::enumerate_pool_addrs(
+                                    full.clone(),
+                                    fetcher.clone(),
+                                    self.chain.clone(),
+                                    self.rate_limit,
+                                    cache.registry_count,
+                                    end_block,
+                                ),
+                            )
+                            .await
+                            .ok_or(PoolSyncError::Cancelled)?
+                            .expect(
+                                "Failed to enumerate pool addresses. Exiting due to having inconclusive state",
+                            );
+                            cache.add_pending(pool_addrs);
+                            cache.registry_count = count;
+                            cache.discovery_block = end_block;
+                            let chain = self.chain.clone();
+                            write_cache_file(cache, chain, self.metadata_only, self.compress)
+                                .unwrap();
+                        }
+                    } else {
+                        // discover new pools in windows, checkpointing the cursor and the found
+                        // addresses after each one so an interrupted sync resumes where it stopped.
+                        // A rewound range is scanned again, so known pools are skipped
+                        let mut discovery_start = cache.discovery_start();
+                        new_pools_from = new_pools_from.min(discovery_start);
+                        while discovery_start <= end_block {
+                            let window_end =
+                                (discovery_start + DISCOVERY_CHECKPOINT_BLOCKS - 1).min(end_block);
+                            let pool_addrs = until_cancelled(
+                                cancel,
+                                Rpc::fetch_pool_addrs(
+                                    discovery_start,
+                                    window_end,
+                                    archive.clone(),
+                                    fetcher.clone(),
+                                    self.chain.clone(),
+                                    self.rate_limit,
+                                    self.log_cache.clone(),
+                                    self.retry,
+                                    self.address_batch_size,
+                                ),
+                            )
+                            .await
+                            .ok_or(PoolSyncError::Cancelled)?
+                            .expect(
+                                "Failed to fetch pool addresses. Exiting due to having inconclusive state",
+                            );
+                            cache.add_pending(pool_addrs);
+                            cache.discovery_block = window_end;
+                            let chain = self.chain.clone();
+                            write_cache_file(cache, chain, self.metadata_only, self.compress)
+                                .unwrap();
+                            discovery_start = window_end + 1;
+                        }
+                    }
+                    let pool_addrs = std::mem::take(&mut cache.pending_addrs);
+                    self.emit(LogEvent::AddressesFetched {
+                        pool_type: cache.pool_type,
+                        count: pool_addrs.len(),
+                    });
 
+                    // populate all of the pool data. Pending V4 pools were all initialized after
+                    // the last synced block, so the range holds their Initialize logs
+                    let populated = if cache.pool_type.is_v4() {
+                        until_cancelled(
+                            cancel,
+                            Rpc::populate_v4_pools(
+                                start_block,
+                                end_block,
+                                pool_addrs,
+                                archive.clone(),
+                                fetcher.clone(),
+                                self.chain.clone(),
+                                self.rate_limit,
+                                self.log_cache.clone(),
+                                self.token_list.clone(),
+                                self.token_cache.clone(),
+                                self.retry,
+                                self.address_batch_size,
+                            ),
+                        )
+                        .await
+                    } else {
+                        until_cancelled(
+                            cancel,
+                            Rpc::populate_pools(
+                                pool_addrs,
+                                full.clone(),
+                                cache.pool_type,
+                                fetcher.clone(),
+                                self.rate_limit,
+                                self.chain.clone(),
+                                self.metadata_concurrency,
+                                self.token_list.clone(),
+                                self.token_cache.clone(),
+                                self.info_batch_size,
+                                self.retry,
+                            ),
+                        )
+                        .await
+                    };
+                    let mut new_pools = populated
+                        .ok_or(PoolSyncError::Cancelled)?
+                        .expect("Failed to sync pool data, Exiting due to haveing inconclusive state");
+                    if !self.token_decimals.is_empty() {
+                        for pool in &mut new_pools {
+                            if pool.verify_decimals(&self.token_decimals) == Some(false) {
+                                warn!(
+                                    "Decimals of {} pool {} do not match the configured token decimals",
+                                    pool.pool_type(),
+                                    pool.address()
+                                );
+                            }
+                        }
+                    }
+                    // the depth is not persisted, so apply it before replaying swaps
+                    let depth = self.price_history.unwrap_or(0);
+                    cache
+                        .pools
+                        .iter_mut()
+                        .chain(new_pools.iter_mut())
+                        .filter_map(|pool| pool.get_v3_mut())
+                        .for_each(|pool| pool.set_price_history_depth(depth));
 
-                    // catch up all the old pools
-                    Rpc::populate_liquidity(
-                        start_block,
-                        end_block,
-                        &mut cache.pools,
-                        archive.clone(),
-                        cache.pool_type,
-                        self.rate_limit,
-                        cache.is_initial_sync,
-                    )
-                    .await
-                    .expect("Failed to populate liquidity information, Exiting due to having inconclusive state");
-
-                    // update the new pools
-                    if !new_pools.is_empty() {
-                        Rpc::populate_liquidity(
-                            start_block,
-                            end_block,
-                            &mut new_pools,
-                            archive.clone(),
-                            cache.pool_type,
-                            self.rate_limit,
-                            true,
+                    if !self.metadata_only {
+                        // catch up all the old pools
+                        let activity = until_cancelled(
+                            cancel,
+                            Rpc::populate_liquidity(
+                                start_block,
+                                end_block,
+                                &mut cache.pools,
+                                archive.clone(),
+                                cache.pool_type,
+                                self.rate_limit,
+                                cache.is_initial_sync,
+                                self.log_cache.clone(),
+                                self.initial_tick_window,
+                                self.liquidity_step,
+                                self.retry,
+                                false,
+                            ),
                         )
                         .await
+                        .ok_or(PoolSyncError::Cancelled)?
                         .expect("Failed to populate liquidity information, Exiting due to having inconclusive state");
+                        cache.record_activity(activity);
+
+                        // update the new pools
+                        if !new_pools.is_empty() {
+                            let activity = until_cancelled(
+                                cancel,
+                                Rpc::populate_liquidity(
+                                    new_pools_from,
+                                    end_block,
+                                    &mut new_pools,
+                                    archive.clone(),
+                                    cache.pool_type,
+                                    self.rate_limit,
+                                    true,
+                                    self.log_cache.clone(),
+                                    self.initial_tick_window,
+                                    self.liquidity_step,
+                                    self.retry,
+                                    false,
+                                ),
+                            )
+                            .await
+                            .ok_or(PoolSyncError::Cancelled)?
+                            .expect("Failed to populate liquidity information, Exiting due to having inconclusive state");
+                            cache.record_activity(activity);
+                        }
                     }
 
+                    // filter the new pools once the range is replayed, so a pool created empty
+                    // that received liquidity inside the range is judged on its synced reserves
+                    apply_filters(&mut new_pools, &self.filters);
+                    self.emit(LogEvent::PoolsDiscovered {
+                        pool_type: cache.pool_type,
+                        count: new_pools.len(),
+                    });
+
+                    // new pools count as active when discovered, so they are not pruned before
+                    // their first liquidity event
+                    cache.record_activity(new_pools.iter().map(|pool| (pool.address(), end_block)));
 
-                    // merge old and new
-                    cache.pools.extend(new_pools);
+                    // merge old and new, a rediscovered pool keeps its fresh state
+                    cache.merge_pools(new_pools);
+
+                    // drop pools with no activity inside the window. Pools without any recorded
+                    // activity yet, such as new pools, are treated as active now
+                    if let Some(window) = self.liquidity_window {
+                        for pool in &cache.pools {
+                            cache.last_activity.entry(pool.address()).or_insert(end_block);
+                        }
+                        cache.prune_inactive(end_block.saturating_sub(window));
+                    }
+
+                    // evict far ticks now that the replay for this range is done
+                    if let Some(max_ticks) = self.max_ticks_per_pool {
+                        cache
+                            .pools
+                            .iter_mut()
+                            .filter_map(|pool| pool.get_v3_mut())
+                            .for_each(|pool| pool.truncate_ticks(max_ticks));
+                    }
 
 
                     // update info for cache
                     cache.last_synced_block = end_block;
+                    cache.last_block_hash = end_hash;
                     last_synced_block = end_block;
                     cache.is_initial_sync = false;
+                    // checkpoint the range so a cancelled sync resumes from here
+                    write_cache_file(cache, self.chain.clone(), self.metadata_only, self.compress)
+                        .unwrap();
+                    self.emit(LogEvent::RangeSynced {
+                        pool_type: cache.pool_type,
+                        block: end_block,
+                        total_pools: cache.pools.len(),
+                    });
+                    if pool_tx.is_some() || self.ndjson_sink.is_some() {
+                        let ndjson = self.ndjson_sink.as_deref();
+                        publish_updated_pools(
+                            &cache.pools,
+                            &cache.last_activity,
+                            &mut sent,
+                            pool_tx,
+                            ndjson,
+                        )
+                        .await?;
+                    }
                 }
+
+                // the replay could not undo the replaced blocks, so the state of the pools they
+                // touched is read at the head instead
+                if !reorged.is_empty() {
+                    self.refresh_pools(&mut cache.pools, &reorged, full.clone(), end_block)
+                        .await?;
+                    // the refreshed state counts as an update, so the pools are sent again
+                    cache.record_activity(reorged.iter().map(|address| (*address, end_block)));
+                    write_cache_file(cache, self.chain.clone(), self.metadata_only, self.compress)
+                        .unwrap();
+                    if pool_tx.is_some() || self.ndjson_sink.is_some() {
+                        let ndjson = self.ndjson_sink.as_deref();
+                        publish_updated_pools(
+                            &cache.pools,
+                            &cache.last_activity,
+                            &mut sent,
+                            pool_tx,
+                            ndjson,
+                        )
+                        .await?;
+                    }
+                }
+            }
+        }
+
+        // pool types that were already caught up have not been published yet
+        if pool_tx.is_some() || self.ndjson_sink.is_some() {
+            for cache in &pool_caches {
+                let ndjson = self.ndjson_sink.as_deref();
+                let activity = &cache.last_activity;
+                publish_updated_pools(&cache.pools, activity, &mut sent, pool_tx, ndjson).await?;
             }
         }
 
         // write all of the cache files
         pool_caches
             .iter()
-            .for_each(|cache| write_cache_file(cache, self.chain).unwrap());
+            .for_each(|cache| {
+                let chain = self.chain.clone();
+                write_cache_file(cache, chain, self.metadata_only, self.compress).unwrap()
+            });
+        self.emit(LogEvent::SyncCompleted {
+            block: last_synced_block,
+            total_pools: pool_caches.iter().map(|cache| cache.pools.len()).sum(),
+        });
 
-        // return all the pools
-        Ok((
-            pool_caches
-                .into_iter()
-                .flat_map(|cache| cache.pools)
-                .collect(),
-            last_synced_block,
+        Ok((pool_caches, loaded, last_synced_block))
+    }
+
+    /// Load the synced pools of every configured pool type from the cache
+    fn read_cached_pools(&self) -> Result<Vec<Pool>, PoolSyncError> {
+        let mut pools = Vec::new();
+        for pool_type in self.fetchers.keys() {
+            let cache = read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                .map_err(cache_error)?;
+            pools.extend(cache.pools);
+        }
+        Ok(pools)
+    }
+
+    /// Synced pool types in a fixed order, optionally limited to `pool_types`
+    fn ordered_pool_types(&self, pool_types: Option<&[PoolType]>) -> Vec<PoolType> {
+        self.fetchers
+            .keys()
+            .filter(|pool_type| pool_types.is_none_or(|types| types.contains(pool_type)))
+            .copied()
+            .collect()
+    }
+
+    /// Number of cached pools, optionally only counting `pool_types`. Use with
+    /// `load_pools_paginated` to walk the pools in pages
+    pub fn count_pools(&self, pool_types: Option<&[PoolType]>) -> Result<u64, PoolSyncError> {
+        let mut index = self.page_index.lock().unwrap();
+        let mut count = 0;
+        for (pool_type, stamp) in self.cache_stamps(pool_types) {
+            count += index.count(pool_type, stamp, |pool_type| self.load_cache_pools(pool_type))?
+                as u64;
+        }
+        Ok(count)
+    }
+
+    /// Up to `limit` cached pools starting at `offset`, optionally only of `pool_types`. Pools
+    /// are ordered by pool type name and then by their position in the cache, so consecutive
+    /// pages never overlap. The pool count of every cache read and the pools of the last one
+    /// are kept until the cache file changes, so a page only reads the caches it overlaps and
+    /// walking all pages reads each cache once
+    pub fn load_pools_paginated(
+        &self,
+        pool_types: Option<&[PoolType]>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<Pool>, PoolSyncError> {
+        let mut index = self.page_index.lock().unwrap();
+        page_pools(
+            &mut index,
+            self.cache_stamps(pool_types),
+            |pool_type| self.load_cache_pools(pool_type),
+            limit as usize,
+            offset as usize,
+        )
+    }
+
+    /// Pool types in `ordered_pool_types` order along with the stamp of their cache file
+    fn cache_stamps(
+        &self,
+        pool_types: Option<&[PoolType]>,
+    ) -> Vec<(PoolType, CacheStamp)> {
+        self.ordered_pool_types(pool_types)
+            .into_iter()
+            .map(|pool_type| {
+                let stamp = cache_stamp(&pool_type, self.chain.clone(), self.metadata_only);
+                (pool_type, stamp)
+            })
+            .collect()
+    }
+
+    /// Pools of the cache of `pool_type`
+    fn load_cache_pools(&self, pool_type: PoolType) -> Result<Vec<Pool>, PoolSyncError> {
+        read_cache_file(&pool_type, self.chain.clone(), self.metadata_only)
+            .map(|cache| cache.pools)
+            .map_err(cache_error)
+    }
+
+    /// Write every cached pool to `path` as a JSON array that deserializes into a `Vec<Pool>`
+    pub fn export_json(&self, path: &Path) -> Result<(), PoolSyncError> {
+        let writer = BufWriter::new(File::create(path)?);
+        write_pools_json(writer, self.cache_pools(None))
+    }
+
+    /// Write every cached pool to `path` as CSV with the address, pool type, tokens, token
+    /// names, decimals and fee of each pool
+    pub fn export_csv(&self, path: &Path) -> Result<(), PoolSyncError> {
+        let writer = BufWriter::new(File::create(path)?);
+        write_pools_csv(writer, self.cache_pools(None))
+    }
+
+    /// Pools of each cache in `ordered_pool_types` order, reading a cache only once the
+    /// previous one has been consumed
+    fn cache_pools(
+        &self,
+        pool_types: Option<&[PoolType]>,
+    ) -> impl Iterator<Item = Result<Vec<Pool>, PoolSyncError>> + '_ {
+        self.ordered_pool_types(pool_types).into_iter().map(|pool_type| {
+            read_cache_file(&pool_type, self.chain.clone(), self.metadata_only)
+                .map(|cache| cache.pools)
+                .map_err(cache_error)
+        })
+    }
+
+    /// Remove the pools from the caches, for example after they were delisted. Returns the
+    /// number of pools removed. Log scanned pool types never rescan a synced range, so their
+    /// pools stay removed. Pool types enumerated from a registry list every pool on each sync
+    /// and bring a removed pool back
+    pub fn delete_pools(&self, addresses: &[Address]) -> Result<usize, PoolSyncError> {
+        let addresses: HashSet<Address> = addresses.iter().copied().collect();
+        self.rewrite_caches(|cache| cache.remove_pools(&addresses))
+    }
+
+    /// Remove the pools without a liquidity event or discovery in the last `older_than_blocks`
+    /// blocks of their cache, returns the number of pools removed. Pools without any recorded
+    /// activity are kept. Metadata only syncs do not track liquidity events, so pruning them
+    /// is an error
+    pub fn prune_stale(&self, older_than_blocks: u64) -> Result<usize, PoolSyncError> {
+        if self.metadata_only {
+            return Err(PoolSyncError::InvalidConfig(
+                "prune_stale needs liquidity activity, which metadata only syncs do not track"
+                    .to_string(),
+            ));
+        }
+        self.rewrite_caches(|cache| {
+            cache.prune_inactive(cache.last_synced_block.saturating_sub(older_than_blocks))
+        })
+    }
+
+    /// Apply `edit` to every cache and write back the ones it removed pools from
+    fn rewrite_caches(
+        &self,
+        mut edit: impl FnMut(&mut PoolCache) -> usize,
+    ) -> Result<usize, PoolSyncError> {
+        let mut removed = 0;
+        for pool_type in self.fetchers.keys() {
+            let mut cache = read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                .map_err(cache_error)?;
+            let count = edit(&mut cache);
+            if count > 0 {
+                write_cache_file(&cache, self.chain.clone(), self.metadata_only, self.compress)
+                    .map_err(|e| PoolSyncError::CacheError(e.to_string()))?;
+                removed += count;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Returns the deepest synced pool for each canonical reference pair of the chain, keyed by
+    /// (wrapped native, stablecoin). Pools are ranked by how much of the wrapped native token
+    /// they hold. Pairs without a synced pool are left out
+    pub fn reference_pools(&self) -> Result<HashMap<(Address, Address), Pool>, PoolSyncError> {
+        let pools = self.read_cached_pools()?;
+        Ok(select_reference_pools(&self.chain.reference_pairs(), pools))
+    }
+
+    /// Total liquidity for the pair across every synced pool and protocol. Pools whose
+    /// reserves are not tracked, such as Curve and Maverick, are not counted
+    pub fn aggregate_depth(
+        &self,
+        token_a: Address,
+        token_b: Address,
+    ) -> Result<AggregateDepth, PoolSyncError> {
+        Ok(aggregate_pair_depth(&self.read_cached_pools()?, token_a, token_b))
+    }
+
+    /// Every synced pool trading the pair, across all protocols. Pools with more than two
+    /// tokens are included when they hold both, the order of the tokens does not matter
+    pub fn get_pools_for_pair(
+        &self,
+        token_a: Address,
+        token_b: Address,
+    ) -> Result<Vec<Pool>, PoolSyncError> {
+        Ok(filter_pair_pools(self.read_cached_pools()?, token_a, token_b))
+    }
+
+    /// Pool types with a cache for the chain, including ones this instance is not configured to
+    /// sync
+    pub fn contained_pool_types(&self) -> Result<Vec<PoolType>, PoolSyncError> {
+        cached_pool_types(self.chain.clone(), self.metadata_only)
+            .map_err(|e| PoolSyncError::CacheError(e.to_string()))
+    }
+
+    /// Current cached state of a single pool, or None if it has not been synced
+    pub fn get_pool(&self, address: Address) -> Result<Option<Pool>, PoolSyncError> {
+        Ok(self
+            .read_cached_pools()?
+            .into_iter()
+            .find(|pool| pool.address() == address))
+    }
+
+    /// Addresses of the cached pools with missing token names, zero decimals or, unless only
+    /// metadata is synced, V3 pools without ticks. These are left behind by a sync that
+    /// partially failed and can be repaired without re-syncing every pool
+    pub fn incomplete_pools(&self) -> Result<Vec<Address>, PoolSyncError> {
+        Ok(self
+            .read_cached_pools()?
+            .iter()
+            .filter(|pool| !pool.is_complete(!self.metadata_only))
+            .map(|pool| pool.address())
+            .collect())
+    }
+
+    /// All synced pools deployed by `factory`. Every pool type is discovered from a single
+    /// factory, so this returns the cached pools of the pool types using it
+    pub fn pools_by_factory(&self, factory: Address) -> Result<Vec<Pool>, PoolSyncError> {
+        let mut pools = Vec::new();
+        for (pool_type, fetcher) in &self.fetchers {
+            if fetcher.factory_address(self.chain.clone()) != Some(factory) {
+                continue;
+            }
+            let cache = read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                .map_err(cache_error)?;
+            pools.extend(cache.pools);
+        }
+        Ok(pools)
+    }
+
+    /// Manipulation resistant price of `token` in `reference`. Every synced pool of the pair
+    /// is weighted by its `reference` liquidity, pools further than `max_price_deviation` from
+    /// the weighted median are dropped, and the weighted geometric mean of the rest is returned
+    pub fn robust_price(&self, token: Address, reference: Address) -> Option<f64> {
+        let pools = self.read_cached_pools().ok()?;
+        robust_pair_price(&pools, token, reference, self.max_price_deviation)
+    }
+
+    /// The `n` synced pools with the most liquidity. Balances are valued in the chain's
+    /// wrapped native token using the robust price of each token, and simply normalized by
+    /// decimals on chains without reference tokens. Pools that do not track balances, such as
+    /// Curve and Maverick, are not ranked
+    pub fn top_pools(&self, n: usize) -> Result<Vec<Pool>, PoolSyncError> {
+        let native = self.chain.reference_pairs().first().map(|(native, _)| *native);
+        Ok(rank_pools_by_liquidity(
+            self.read_cached_pools()?,
+            native,
+            self.max_price_deviation,
+            n,
         ))
     }
+
+    /// Map every token to the addresses of the synced pools that hold it
+    pub fn pools_by_token(&self) -> Result<HashMap<Address, Vec<Address>>, PoolSyncError> {
+        Ok(index_pools_by_token(&self.read_cached_pools()?))
+    }
+
+    /// Reserves or slot0 state for each address as of `block`, read directly from the chain
+    /// without a sync. Reads go to the archive endpoint so any historical block can be used.
+    /// Addresses that are not v2 or v3 style pools come back as `PoolState::Unknown`
+    pub async fn snapshot_reserves(
+        &self,
+        addresses: &[Address],
+        block: u64,
+    ) -> Result<Vec<PoolState>, PoolSyncError> {
+        let archive = Arc::new(self.connect_env("ARCHIVE")?);
+
+        futures::stream::iter(
+            addresses
+                .iter()
+                .map(|address| snapshot_pool(archive.clone(), *address, BlockId::number(block))),
+        )
+        .buffered(self.rate_limit.max(1) as usize)
+        .map(|state| state.map_err(|e| PoolSyncError::ProviderError(e.to_string())))
+        .try_collect()
+        .await
+    }
+
+    /// Refresh the spot state of a mixed set of pools to `block` without replaying any events.
+    /// V2 reserves, V3 price, tick and active liquidity, and balancer balances are read
+    /// directly. Tick maps are not touched and pools without tracked balances are skipped
+    pub async fn refresh_all_spot(
+        &self,
+        pools: &mut [Pool],
+        block: u64,
+    ) -> Result<(), PoolSyncError> {
+        let archive = Arc::new(self.connect_env("ARCHIVE")?);
+
+        let addresses = pools.iter().map(|pool| pool.address()).collect();
+        self.refresh_pools(pools, &addresses, archive, block).await
+    }
+
+    /// Returns the addresses of all synced pools that had a liquidity event in the block range.
+    /// The cached pools are not modified
+    pub async fn pools_active_in_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<Address>, PoolSyncError> {
+        let archive = Arc::new(self.connect_env("ARCHIVE")?);
+
+        if let Some(log_cache) = &self.log_cache {
+            let head = archive
+                .get_block_number()
+                .await
+                .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+            log_cache.set_head(head);
+        }
+
+        let mut active = BTreeSet::new();
+        for pool_type in self.fetchers.keys() {
+            let cache = read_cache_file(pool_type, self.chain.clone(), self.metadata_only)
+                .map_err(cache_error)?;
+            let known: HashSet<Address> = cache.pools.iter().map(|pool| pool.address()).collect();
+
+            let logs = Rpc::fetch_pool_events(
+                from,
+                to,
+                archive.clone(),
+                *pool_type,
+                self.rate_limit,
+                self.log_cache.clone(),
+                self.retry,
+            )
+            .await
+            .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+            active.extend(
+                logs.iter()
+                    .map(Rpc::log_pool_address)
+                    .filter(|address| known.contains(address)),
+            );
+        }
+
+        Ok(active.into_iter().collect())
+    }
+}
+
+/// Liquidity weighted geometric mean price of `token` in `reference` over the two token pools
+/// of the pair, ignoring prices more than `max_deviation` away from the weighted median
+pub(crate) fn robust_pair_price(
+    pools: &[Pool],
+    token: Address,
+    reference: Address,
+    max_deviation: f64,
+) -> Option<f64> {
+    let mut quotes: Vec<(f64, f64)> = pools
+        .iter()
+        .filter(|pool| {
+            let (token0, token1) = (pool.token0_address(), pool.token1_address());
+            (token0, token1) == (token, reference) || (token0, token1) == (reference, token)
+        })
+        .filter_map(|pool| {
+            let price = pool.price(token)?;
+            let weight = f64::from(pool.token_depth(reference)?);
+            (price.is_finite() && price > 0.0 && weight > 0.0).then_some((price, weight))
+        })
+        .collect();
+    if quotes.is_empty() {
+        return None;
+    }
+
+    // the median can not be moved by a single shallow pool
+    quotes.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let total_weight: f64 = quotes.iter().map(|(_, weight)| weight).sum();
+    let mut cumulative = 0.0;
+    let median = quotes
+        .iter()
+        .find(|(_, weight)| {
+            cumulative += weight;
+            cumulative >= total_weight / 2.0
+        })
+        .map(|(price, _)| *price)?;
+
+    let (log_sum, weight_sum) = quotes
+        .iter()
+        .filter(|(price, _)| (price / median - 1.0).abs() <= max_deviation)
+        .fold((0.0, 0.0), |(log_sum, weight_sum), (price, weight)| {
+            (log_sum + price.ln() * weight, weight_sum + weight)
+        });
+    Some((log_sum / weight_sum).exp())
+}
+
+/// Sort pools by the value of their balances in `native` and keep the first `n`
+pub(crate) fn rank_pools_by_liquidity(
+    pools: Vec<Pool>,
+    native: Option<Address>,
+    max_deviation: f64,
+    n: usize,
+) -> Vec<Pool> {
+    // price every token paired with the native token from the pools of that pair
+    let mut prices = HashMap::new();
+    if let Some(native) = native {
+        let mut native_pools: HashMap<Address, Vec<Pool>> = HashMap::new();
+        for pool in pools.iter().filter(|pool| pool.get_tokens().len() == 2) {
+            let (token0, token1) = (pool.token0_address(), pool.token1_address());
+            if token0 == native {
+                native_pools.entry(token1).or_default().push(pool.clone());
+            } else if token1 == native {
+                native_pools.entry(token0).or_default().push(pool.clone());
+            }
+        }
+        for (token, pair_pools) in native_pools {
+            if let Some(price) = robust_pair_price(&pair_pools, token, native, max_deviation) {
+                prices.insert(token, price);
+            }
+        }
+        prices.insert(native, 1.0);
+    }
+
+    let score = |pool: &Pool| -> Option<f64> {
+        let tokens = pool.get_tokens();
+        let mut value = 0.0;
+        let mut priced = 0;
+        for (token, decimals) in tokens.iter().zip(pool.get_token_decimals()) {
+            let amount = f64::from(pool.token_depth(*token)?) / 10f64.powi(decimals as i32);
+            let price = if native.is_some() { prices.get(token) } else { Some(&1.0) };
+            if let Some(price) = price {
+                value += amount * price;
+                priced += 1;
+            }
+        }
+        // tokens without a price are assumed to hold the same value as the priced ones
+        (priced > 0).then(|| value * tokens.len() as f64 / priced as f64)
+    };
+
+    let mut scored: Vec<(f64, Pool)> = pools
+        .into_iter()
+        .filter_map(|pool| Some((score(&pool)?, pool)))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(n).map(|(_, pool)| pool).collect()
+}
+
+/// Apply a log to the pool it belongs to, returns false if the log is not from a tracked v2,
+/// v3 or balancer pool
+pub(crate) fn apply_live_log(pools: &mut HashMap<Address, Pool>, log: Log) -> bool {
+    let Some(pool) = pools.get_mut(&Rpc::log_pool_address(&log)) else {
+        return false;
+    };
+    let pool_type = pool.pool_type();
+    if let Some(pool) = pool.get_v3_mut() {
+        process_tick_data(pool, log, pool_type, false);
+    } else if let Some(pool) = pool.get_v4_mut() {
+        process_v4_data(pool, log);
+    } else if let Some(pool) = pool.get_balancer_mut() {
+        if log.topic0() != Some(&BalancerV2Event::Swap::SIGNATURE_HASH) {
+            return false;
+        }
+        process_balance_data(pool, log);
+    } else if let Some(pool) = pool.get_v2_mut() {
+        let is_sync = log.topic0().is_some_and(|topic| {
+            *topic == DataEvents::Sync::SIGNATURE_HASH
+                || *topic == AerodromeSync::Sync::SIGNATURE_HASH
+        });
+        if !is_sync {
+            return false;
+        }
+        process_sync_data(pool, log, pool_type);
+    } else {
+        return false;
+    }
+    true
+}
+
+/// Block range the live sync applied, kept to undo it after a reorg
+pub(crate) struct LiveRange {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Hash of `to_block` when the range was applied
+    pub hash: B256,
+    /// Pools the range updated
+    pub updated: HashSet<Address>,
+}
+
+/// Progress of the live sync, carried across reconnects
+struct LiveProgress {
+    /// Last block whose logs were applied
+    block: u64,
+    /// Block each synced pool type was at when the live sync started, its pools already hold
+    /// the logs up to it
+    synced: HashMap<PoolType, u64>,
+    /// Ranges applied within the reorg depth, oldest first
+    recent: VecDeque<LiveRange>,
+    /// Last block each pool was updated at since the checkpoint
+    activity: HashMap<Address, u64>,
+    /// Last block written back to the caches
+    checkpoint: u64,
+}
+
+/// Log filters of the live sync, restricted to the tracked pools the live sync updates. Pools
+/// living in a singleton (balancer vault, v4 PoolManager) are matched on their pool id and
+/// the rest on their address, both in batches of `LIVE_FILTER_POOLS`
+pub(crate) fn live_filters(pools: &HashMap<Address, Pool>) -> Vec<Filter> {
+    let events = Filter::new().event_signature(vec![
+        DataEvents::Sync::SIGNATURE_HASH,
+        AerodromeSync::Sync::SIGNATURE_HASH,
+        DataEvents::Mint::SIGNATURE_HASH,
+        DataEvents::Burn::SIGNATURE_HASH,
+        DataEvents::Swap::SIGNATURE_HASH,
+        PancakeSwapEvents::Swap::SIGNATURE_HASH,
+        AlgebraEvents::Swap::SIGNATURE_HASH,
+        AlgebraEvents::Fee::SIGNATURE_HASH,
+        BalancerV2Event::Swap::SIGNATURE_HASH,
+        UniswapV4Events::ModifyLiquidity::SIGNATURE_HASH,
+        UniswapV4Events::Swap::SIGNATURE_HASH,
+    ]);
+    let mut addresses = Vec::new();
+    let mut pool_ids = Vec::new();
+    for pool in pools.values() {
+        match pool {
+            Pool::BalancerV2(pool) => pool_ids.push(pool.pool_id),
+            Pool::UniswapV4(pool) => pool_ids.push(pool.pool_id),
+            pool if pool.is_v2() || pool.is_v3() => addresses.push(pool.address()),
+            _ => {}
+        }
+    }
+    addresses.sort();
+    pool_ids.sort();
+
+    let mut filters: Vec<Filter> = addresses
+        .chunks(LIVE_FILTER_POOLS)
+        .map(|chunk| events.clone().address(chunk.to_vec()))
+        .collect();
+    filters.extend(
+        pool_ids
+            .chunks(LIVE_FILTER_POOLS)
+            .map(|chunk| events.clone().topic1(chunk.to_vec())),
+    );
+    filters
+}
+
+/// Drop the recent ranges a reorg replaced, newest first, given the new header at `head`.
+/// Ranges past the head are gone, the others are replaced once the canonical hash of their
+/// last block differs. A node that does not have a block proves nothing and stops the walk.
+/// Returns the first replaced block and the pools the replaced ranges updated, None if
+/// nothing was replaced
+pub(crate) async fn unwind_live_reorg<F, Fut>(
+    recent: &mut VecDeque<LiveRange>,
+    head: u64,
+    mut hash_of: F,
+) -> Result<Option<(u64, HashSet<Address>)>, PoolSyncError>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Option<B256>, PoolSyncError>>,
+{
+    let mut reorged: Option<(u64, HashSet<Address>)> = None;
+    while let Some(range) = recent.back() {
+        let replaced = range.to_block > head
+            || hash_of(range.to_block).await?.is_some_and(|hash| hash != range.hash);
+        if !replaced {
+            break;
+        }
+        let range = recent.pop_back().unwrap();
+        let (from_block, touched) = reorged.get_or_insert_with(Default::default);
+        *from_block = range.from_block;
+        touched.extend(range.updated);
+    }
+    Ok(reorged)
+}
+
+/// Collect the page of pools at `offset` from the caches in order, reading them with `load`.
+/// Caches are only read until the page is full, and caches before the page with a count in
+/// `index` are not read at all
+pub(crate) fn page_pools(
+    index: &mut PageIndex,
+    caches: impl IntoIterator<Item = (PoolType, CacheStamp)>,
+    mut load: impl FnMut(PoolType) -> Result<Vec<Pool>, PoolSyncError>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<Pool>, PoolSyncError> {
+    let mut page = Vec::new();
+    let mut skip = offset;
+    for (pool_type, stamp) in caches {
+        if page.len() >= limit {
+            break;
+        }
+        // caches before the page are skipped by their known count without reading them
+        let count = index.count(pool_type, stamp, &mut load)?;
+        if skip >= count {
+            skip -= count;
+            continue;
+        }
+        let remaining = limit - page.len();
+        let pools = index.pools(pool_type, stamp, &mut load)?;
+        page.extend(pools.iter().skip(skip).take(remaining).cloned());
+        skip = 0;
+    }
+    Ok(page)
+}
+
+/// Pool counts of the caches read so far and the pools of the last one, each kept along with
+/// the stamp of the cache file it was read from and dropped once the file changes
+#[derive(Default)]
+pub(crate) struct PageIndex {
+    counts: HashMap<PoolType, (CacheStamp, usize)>,
+    last: Option<(PoolType, CacheStamp, Arc<Vec<Pool>>)>,
+}
+
+impl PageIndex {
+    /// Number of pools in the cache, reading it only if its count is not known
+    pub(crate) fn count(
+        &mut self,
+        pool_type: PoolType,
+        stamp: CacheStamp,
+        load: impl FnMut(PoolType) -> Result<Vec<Pool>, PoolSyncError>,
+    ) -> Result<usize, PoolSyncError> {
+        match self.counts.get(&pool_type) {
+            Some((known, count)) if *known == stamp => Ok(*count),
+            _ => Ok(self.pools(pool_type, stamp, load)?.len()),
+        }
+    }
+
+    /// Pools of the cache, reading it unless it was the last one read
+    pub(crate) fn pools(
+        &mut self,
+        pool_type: PoolType,
+        stamp: CacheStamp,
+        mut load: impl FnMut(PoolType) -> Result<Vec<Pool>, PoolSyncError>,
+    ) -> Result<Arc<Vec<Pool>>, PoolSyncError> {
+        if let Some((last_type, last_stamp, pools)) = &self.last {
+            if *last_type == pool_type && *last_stamp == stamp {
+                return Ok(pools.clone());
+            }
+        }
+        let pools = Arc::new(load(pool_type)?);
+        self.counts.insert(pool_type, (stamp, pools.len()));
+        self.last = Some((pool_type, stamp, pools.clone()));
+        Ok(pools)
+    }
+}
+
+/// Timestamp of the block at `number`
+async fn block_timestamp<P, T, N>(provider: &P, number: u64) -> Result<u64, PoolSyncError>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    let block = provider
+        .get_block_by_number(number.into(), BlockTransactionsKind::Hashes)
+        .await
+        .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?
+        .ok_or_else(|| PoolSyncError::ProviderError(format!("Block {} not found", number)))?;
+    Ok(block.header().timestamp())
+}
+
+/// Binary search blocks `0..=latest` for the first one with a timestamp at or after `unix_ts`,
+/// block timestamps only ever increase
+pub(crate) async fn first_block_at<F, Fut>(
+    latest: u64,
+    unix_ts: u64,
+    mut timestamp_of: F,
+) -> Result<u64, PoolSyncError>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<u64, PoolSyncError>>,
+{
+    if timestamp_of(latest).await? < unix_ts {
+        return Err(PoolSyncError::InvalidConfig(format!(
+            "timestamp {} is after the latest block {}",
+            unix_ts, latest
+        )));
+    }
+    let (mut low, mut high) = (0, latest);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if timestamp_of(mid).await? >= unix_ts {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(low)
+}
+
+/// Hash of the block at `number`, None if the node does not have it
+async fn block_hash<P, T, N>(provider: &P, number: u64) -> Result<Option<B256>, PoolSyncError>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    let block = provider
+        .get_block_by_number(number.into(), BlockTransactionsKind::Hashes)
+        .await
+        .map_err(|e| PoolSyncError::ProviderError(e.to_string()))?;
+    Ok(block.map(|block| block.header().hash()))
+}
+
+/// Rewind the cache by `depth` blocks if the hash it stored for its last synced block is no
+/// longer the canonical one. Returns the first block of the reorged range, None if there was
+/// no reorg. A node that does not have the block yet, such as a lagging or load balanced one,
+/// proves nothing, so the stored hash is kept and compared again by the next sync
+pub(crate) fn rewind_on_reorg(
+    cache: &mut PoolCache,
+    canonical: Option<B256>,
+    depth: u64,
+    chain: Chain,
+) -> Option<u64> {
+    match (cache.last_block_hash, canonical) {
+        (Some(stored), Some(canonical)) if canonical != stored => Some(cache.rewind(depth, chain)),
+        _ => None,
+    }
+}
+
+/// Drive `sync` while yielding the pools it sends into `rx`. Pools sent before a failure are
+/// still yielded, the error comes last
+pub(crate) fn stream_synced_pools<F>(
+    sync: F,
+    rx: Receiver<Pool>,
+) -> impl Stream<Item = Result<Pool, PoolSyncError>>
+where
+    F: Future<Output = Result<u64, PoolSyncError>>,
+{
+    let state = (Some(Box::pin(sync)), rx, None);
+    futures::stream::unfold(state, |(mut sync, mut rx, mut error)| async move {
+        if let Some(running) = sync.as_mut() {
+            tokio::select! {
+                biased;
+                Some(pool) = rx.recv() => return Some((Ok(pool), (sync, rx, error))),
+                result = running => {
+                    // the sender is dropped with the finished sync, so draining rx terminates
+                    sync = None;
+                    error = result.err();
+                }
+            }
+        }
+        match rx.recv().await {
+            Some(pool) => Some((Ok(pool), (sync, rx, error))),
+            None => error.take().map(|e| (Err(e), (sync, rx, None))),
+        }
+    })
+}
+
+/// Keep the pools holding both tokens of the pair
+pub(crate) fn filter_pair_pools(pools: Vec<Pool>, token_a: Address, token_b: Address) -> Vec<Pool> {
+    pools
+        .into_iter()
+        .filter(|pool| {
+            let tokens = pool.get_tokens();
+            tokens.contains(&token_a) && tokens.contains(&token_b)
+        })
+        .collect()
+}
+
+/// Sum the reserves of both tokens over the pools holding the pair
+pub(crate) fn aggregate_pair_depth(
+    pools: &[Pool],
+    token_a: Address,
+    token_b: Address,
+) -> AggregateDepth {
+    let mut depth = AggregateDepth::default();
+    for pool in pools {
+        let tokens = pool.get_tokens();
+        if !tokens.contains(&token_a) || !tokens.contains(&token_b) {
+            continue;
+        }
+        if let (Some(reserve_a), Some(reserve_b)) =
+            (pool.token_depth(token_a), pool.token_depth(token_b))
+        {
+            depth.total_reserve_a += reserve_a;
+            depth.total_reserve_b += reserve_b;
+            depth.pool_count += 1;
+        }
+    }
+    depth
+}
+
+/// Build the token to pool addresses index
+pub(crate) fn index_pools_by_token(pools: &[Pool]) -> HashMap<Address, Vec<Address>> {
+    let mut index: HashMap<Address, Vec<Address>> = HashMap::new();
+    for pool in pools {
+        let tokens: BTreeSet<Address> = pool.get_tokens().into_iter().collect();
+        for token in tokens {
+            index.entry(token).or_default().push(pool.address());
+        }
+    }
+    index
+}
+
+/// Pick the pool with the most of the first token for every pair
+pub(crate) fn select_reference_pools(
+    pairs: &[(Address, Address)],
+    pools: Vec<Pool>,
+) -> HashMap<(Address, Address), Pool> {
+    let mut best: HashMap<(Address, Address), (U256, Pool)> = HashMap::new();
+    for pool in pools {
+        let (token0, token1) = (pool.token0_address(), pool.token1_address());
+        let pair = pairs.iter().find(|(base, quote)| {
+            (token0, token1) == (*base, *quote) || (token0, token1) == (*quote, *base)
+        });
+        let Some(&(base, quote)) = pair else {
+            continue;
+        };
+        let Some(depth) = pool.token_depth(base) else {
+            continue;
+        };
+        if best
+            .get(&(base, quote))
+            .is_none_or(|(best_depth, _)| depth > *best_depth)
+        {
+            best.insert((base, quote), (depth, pool));
+        }
+    }
+    best.into_iter()
+        .map(|(pair, (_, pool))| (pair, pool))
+        .collect()
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments)]
+
 use alloy::sol;
 
 // UNISWAP
@@ -106,6 +108,76 @@ sol!(
     "src/pools/abis/MaverickV1Factory.json"
 );
 
+// Leading state fields shared by the v2 and v3 forks, the forks append their own fields
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract PoolStateReader {
+        function getReserves() external view returns (uint256 reserve0, uint256 reserve1);
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick);
+        function globalState() external view returns (uint160 price, int24 tick, uint16 fee);
+        function liquidity() external view returns (uint128);
+    }
+);
+
+// Active tick and per bin state of maverick v2 pools, bins own a share of the reserves of their
+// tick
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract MaverickV2Pool {
+        struct State {
+            uint128 reserveA;
+            uint128 reserveB;
+            int64 lastTwaD8;
+            int64 lastLogPriceD8;
+            uint40 lastTimestamp;
+            int32 activeTick;
+            bool isLocked;
+            uint32 binCounter;
+            uint8 protocolFeeRatioD3;
+        }
+        struct TickState {
+            uint128 reserveA;
+            uint128 reserveB;
+            uint128 totalSupply;
+            uint32[4] binIdsByTick;
+        }
+        struct BinState {
+            uint128 mergeBinBalance;
+            uint128 tickBalance;
+            uint128 totalSupply;
+            uint8 kind;
+            int32 tick;
+            uint32 mergeId;
+        }
+        function getState() external view returns (State memory);
+        function getTick(int32 tick) external view returns (TickState memory tickState);
+        function getBin(uint32 binId) external view returns (BinState memory bin);
+    }
+);
+
+// Batches read only calls, deployed at the same address on every supported chain
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract Multicall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+        struct CallResult {
+            bool success;
+            bytes returnData;
+        }
+        function aggregate3(Call3[] calldata calls)
+            external
+            payable
+            returns (CallResult[] memory returnData);
+    }
+);
+
 // Curve
 sol!(
     #[derive(Debug)]
@@ -121,6 +193,65 @@ sol!(
     "src/pools/abis/TriCryptoFactory.json"
 );
 
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract StableSwapFactory {
+        event PlainPoolDeployed(address[] coins, uint256 A, uint256 fee, address deployer);
+        function get_coins(address pool) external view returns (address[]);
+        function get_decimals(address pool) external view returns (uint256[]);
+        function get_balances(address pool) external view returns (uint256[]);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract StableSwapPool {
+        function A_precise() external view returns (uint256);
+        function fee() external view returns (uint256);
+        function stored_rates() external view returns (uint256[]);
+        function get_balances() external view returns (uint256[]);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract TwoCryptoPool {
+        function balances(uint256 i) external view returns (uint256);
+        function price_scale() external view returns (uint256);
+        function D() external view returns (uint256);
+        function A() external view returns (uint256);
+        function gamma() external view returns (uint256);
+        function mid_fee() external view returns (uint256);
+        function out_fee() external view returns (uint256);
+        function fee_gamma() external view returns (uint256);
+    }
+);
+
+// Algebra (Camelot, QuickSwap). Only the leading globalState fields are shared by every
+// Algebra version, the rest are ignored when decoding
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract AlgebraFactory {
+        event Pool(address indexed token0, address indexed token1, address pool);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    contract AlgebraPool {
+        function token0() external view returns (address);
+        function token1() external view returns (address);
+        function tickSpacing() external view returns (int24);
+        function liquidity() external view returns (uint128);
+        function globalState() external view returns (uint160 price, int24 tick, uint16 fee);
+    }
+);
+
 sol!(
     #[derive(Debug)]
     #[sol(rpc)]
@@ -6,27 +6,32 @@
 
 use alloy::dyn_abi::DynSolType;
 use alloy::dyn_abi::DynSolValue;
-use alloy::primitives::{Address, Log};
+use alloy::primitives::{Address, Log, U256};
 use pool_structures::balancer_v2_structure::BalancerV2Pool;
 use pool_structures::maverick_structure::MaverickPool;
+use pool_structures::stable_swap_curve_structure::CurveStableSwapPool;
 use pool_structures::tri_crypto_curve_structure::CurveTriCryptoPool;
 use pool_structures::two_crypto_curve_structure::CurveTwoCryptoPool;
 use pool_structures::v2_structure::UniswapV2Pool;
 use pool_structures::v3_structure::UniswapV3Pool;
+use pool_structures::v4_structure::UniswapV4Pool;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 
 use crate::chain::Chain;
+use crate::errors::PoolSyncError;
 use crate::impl_pool_info;
 
-mod gen;
+pub(crate) mod gen;
 pub mod pool_builder;
 pub mod pool_fetchers;
 pub mod pool_structures;
+pub(crate) mod token_metadata;
 
-/// Enumerates the supported pool types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Enumerates the supported pool types, ordered by declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum PoolType {
     UniswapV2,
     SushiSwapV2,
@@ -34,8 +39,10 @@ pub enum PoolType {
     UniswapV3,
     SushiSwapV3,
     PancakeSwapV3,
+    UniswapV4,
     Aerodrome,
     Slipstream,
+    Velodrome,
     BaseSwapV2,
     BaseSwapV3,
     AlienBaseV2,
@@ -44,14 +51,47 @@ pub enum PoolType {
     MaverickV2,
     CurveTwoCrypto,
     CurveTriCrypto,
+    CurveStableSwap,
     BalancerV2,
     SwapBasedV2,
     SwapBasedV3,
     DackieSwapV2,
     DackieSwapV3,
+    Camelot,
 }
 
 impl PoolType {
+    /// Every pool type, in declaration order
+    pub fn all() -> &'static [PoolType] {
+        &[
+            PoolType::UniswapV2,
+            PoolType::SushiSwapV2,
+            PoolType::PancakeSwapV2,
+            PoolType::UniswapV3,
+            PoolType::SushiSwapV3,
+            PoolType::PancakeSwapV3,
+            PoolType::UniswapV4,
+            PoolType::Aerodrome,
+            PoolType::Slipstream,
+            PoolType::Velodrome,
+            PoolType::BaseSwapV2,
+            PoolType::BaseSwapV3,
+            PoolType::AlienBaseV2,
+            PoolType::AlienBaseV3,
+            PoolType::MaverickV1,
+            PoolType::MaverickV2,
+            PoolType::CurveTwoCrypto,
+            PoolType::CurveTriCrypto,
+            PoolType::CurveStableSwap,
+            PoolType::BalancerV2,
+            PoolType::SwapBasedV2,
+            PoolType::SwapBasedV3,
+            PoolType::DackieSwapV2,
+            PoolType::DackieSwapV3,
+            PoolType::Camelot,
+        ]
+    }
+
     pub fn is_v2(&self) -> bool {
         matches!(
             self,
@@ -59,6 +99,7 @@ impl PoolType {
                 | PoolType::SushiSwapV2
                 | PoolType::PancakeSwapV2
                 | PoolType::Aerodrome
+                | PoolType::Velodrome
                 | PoolType::BaseSwapV2
                 | PoolType::SwapBasedV2
                 | PoolType::DackieSwapV2
@@ -66,6 +107,17 @@ impl PoolType {
         )
     }
 
+    /// Fee in basis points that the constant product pools of a V2 type charge, None for the
+    /// solidly forks whose pools report their own fee and for types that are not V2
+    pub fn v2_fee_bps(&self) -> Option<u32> {
+        match self {
+            PoolType::PancakeSwapV2 => Some(25),
+            PoolType::Aerodrome | PoolType::Velodrome => None,
+            pool_type if pool_type.is_v2() => Some(30),
+            _ => None,
+        }
+    }
+
     pub fn is_v3(&self) -> bool {
         matches!(
             self,
@@ -77,9 +129,14 @@ impl PoolType {
                 | PoolType::AlienBaseV3
                 | PoolType::SwapBasedV3
                 | PoolType::DackieSwapV3
+                | PoolType::Camelot
         )
     }
 
+    pub fn is_v4(&self) -> bool {
+        matches!(self, PoolType::UniswapV4)
+    }
+
     pub fn is_maverick(&self) -> bool {
         matches!(self, PoolType::MaverickV1 | PoolType::MaverickV2)
     }
@@ -92,35 +149,70 @@ impl PoolType {
         matches!(self, PoolType::CurveTriCrypto)
     }
 
+    pub fn is_curve_stable(&self) -> bool {
+        matches!(self, PoolType::CurveStableSwap)
+    }
+
     pub fn is_balancer(&self) -> bool {
         matches!(self, PoolType::BalancerV2)
     }
 
-    pub fn build_pool(&self, pool_data: &[DynSolValue]) -> Pool {
+    /// Concentrated liquidity pools, the V3 and V4 tick families along with the maverick bin
+    /// pools
+    pub fn is_concentrated(&self) -> bool {
+        self.is_v3() || self.is_v4() || self.is_maverick()
+    }
+
+    /// Decode the pool data returned by the data sync contract into a pool of this type
+    pub fn build_pool(&self, pool_data: &[DynSolValue]) -> Result<Pool, PoolSyncError> {
         if self.is_v2() {
-            let pool = UniswapV2Pool::from(pool_data);
+            let pool = UniswapV2Pool::try_from(pool_data)?;
             Pool::new_v2(*self, pool)
         } else if self.is_v3() {
-            let pool = UniswapV3Pool::from(pool_data);
+            let pool = UniswapV3Pool::try_from(pool_data)?;
             Pool::new_v3(*self, pool)
         } else if self.is_maverick() {
-            let pool = MaverickPool::from(pool_data);
+            let pool = MaverickPool::try_from(pool_data)?;
             Pool::new_maverick(*self, pool)
         } else if self.is_balancer() {
-            let pool = BalancerV2Pool::from(pool_data);
+            let pool = BalancerV2Pool::try_from(pool_data)?;
             Pool::new_balancer(*self, pool)
         } else if self.is_curve_two() {
-            let pool = CurveTwoCryptoPool::from(pool_data);
+            let pool = CurveTwoCryptoPool::try_from(pool_data)?;
             Pool::new_curve_two(*self, pool)
         } else if self.is_curve_tri() {
-            let pool = CurveTriCryptoPool::from(pool_data);
+            let pool = CurveTriCryptoPool::try_from(pool_data)?;
             Pool::new_curve_tri(*self, pool)
         } else {
-            panic!("Invalid pool type");
+            Err(PoolSyncError::UnsupportedPoolType)
         }
     }
 }
 
+/// Version of the serialized pool format. Bumped whenever a change to `Pool` or the pool
+/// structures stops data written by an earlier version from deserializing correctly, such as
+/// reordering or renaming variants and fields
+pub const POOL_FORMAT_VERSION: u32 = 1;
+
+/// Versioned envelope a pool is serialized in
+#[derive(Serialize)]
+struct PoolEnvelope<'a> {
+    format_version: u32,
+    pool: &'a Pool,
+}
+
+/// Envelope read back in two steps so the version is checked before the pool is decoded
+#[derive(Deserialize)]
+struct PoolEnvelopeHeader {
+    #[serde(default)]
+    format_version: u32,
+}
+
+#[derive(Deserialize)]
+struct OwnedPoolEnvelope {
+    pool: Pool,
+}
+
 /// Represents a populated pool from any of the supported protocols
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Pool {
@@ -134,6 +226,7 @@ pub enum Pool {
 
     Aerodrome(UniswapV2Pool),
     Slipstream(UniswapV3Pool),
+    Velodrome(UniswapV2Pool),
 
     UniswapV3(UniswapV3Pool),
     SushiSwapV3(UniswapV3Pool),
@@ -142,33 +235,40 @@ pub enum Pool {
     AlienBaseV3(UniswapV3Pool),
     SwapBasedV3(UniswapV3Pool),
     DackieSwapV3(UniswapV3Pool),
+    Camelot(UniswapV3Pool),
+
+    UniswapV4(UniswapV4Pool),
 
     MaverickV1(MaverickPool),
     MaverickV2(MaverickPool),
 
     CurveTwoCrypto(CurveTwoCryptoPool),
     CurveTriCrypto(CurveTriCryptoPool),
+    CurveStableSwap(CurveStableSwapPool),
 
     BalancerV2(BalancerV2Pool),
 }
 
 impl Pool {
-    pub fn new_v2(pool_type: PoolType, pool: UniswapV2Pool) -> Self {
-        match pool_type {
+    pub fn new_v2(pool_type: PoolType, mut pool: UniswapV2Pool) -> Result<Self, PoolSyncError> {
+        pool.router_fee_bps = pool_type.v2_fee_bps();
+        let pool = match pool_type {
             PoolType::UniswapV2 => Pool::UniswapV2(pool),
             PoolType::SushiSwapV2 => Pool::SushiSwapV2(pool),
             PoolType::PancakeSwapV2 => Pool::PancakeSwapV2(pool),
             PoolType::Aerodrome => Pool::Aerodrome(pool),
+            PoolType::Velodrome => Pool::Velodrome(pool),
             PoolType::BaseSwapV2 => Pool::BaseSwapV2(pool),
             PoolType::SwapBasedV2 => Pool::SwapBasedV2(pool),
             PoolType::DackieSwapV2 => Pool::DackieSwapV2(pool),
             PoolType::AlienBaseV2 => Pool::AlienBaseV2(pool),
-            _ => panic!("Invalid pool type"),
-        }
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
     }
 
-    pub fn new_v3(pool_type: PoolType, pool: UniswapV3Pool) -> Self {
-        match pool_type {
+    pub fn new_v3(pool_type: PoolType, pool: UniswapV3Pool) -> Result<Self, PoolSyncError> {
+        let pool = match pool_type {
             PoolType::UniswapV3 => Pool::UniswapV3(pool),
             PoolType::SushiSwapV3 => Pool::SushiSwapV3(pool),
             PoolType::PancakeSwapV3 => Pool::PancakeSwapV3(pool),
@@ -176,95 +276,109 @@ impl Pool {
             PoolType::BaseSwapV3 => Pool::BaseSwapV3(pool),
             PoolType::SwapBasedV3 => Pool::SwapBasedV3(pool),
             PoolType::DackieSwapV3 => Pool::DackieSwapV3(pool),
+            PoolType::Camelot => Pool::Camelot(pool),
             PoolType::AlienBaseV3 => Pool::AlienBaseV3(pool),
-            _ => panic!("Invalid pool type"),
-        }
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
     }
 
-    pub fn new_maverick(pool_type: PoolType, pool: MaverickPool) -> Self {
-        match pool_type {
+    pub fn new_maverick(pool_type: PoolType, pool: MaverickPool) -> Result<Self, PoolSyncError> {
+        let pool = match pool_type {
             PoolType::MaverickV1 => Pool::MaverickV1(pool),
             PoolType::MaverickV2 => Pool::MaverickV2(pool),
-            _ => panic!("Invalid pool type"),
-        }
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
     }
 
-    pub fn new_curve_two(pool_type: PoolType, pool: CurveTwoCryptoPool) -> Self {
-        match pool_type {
+    pub fn new_curve_two(pool_type: PoolType, pool: CurveTwoCryptoPool) -> Result<Self, PoolSyncError> {
+        let pool = match pool_type {
             PoolType::CurveTwoCrypto => Pool::CurveTwoCrypto(pool),
-            _ => panic!("Invalid pool type"),
-        }
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
     }
 
-    pub fn new_curve_tri(pool_type: PoolType, pool: CurveTriCryptoPool) -> Self {
-        match pool_type {
+    pub fn new_curve_tri(pool_type: PoolType, pool: CurveTriCryptoPool) -> Result<Self, PoolSyncError> {
+        let pool = match pool_type {
             PoolType::CurveTriCrypto => Pool::CurveTriCrypto(pool),
-            _ => panic!("Invalid pool type"),
-        }
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
+    }
+
+    pub fn new_curve_stable(
+        pool_type: PoolType,
+        pool: CurveStableSwapPool,
+    ) -> Result<Self, PoolSyncError> {
+        let pool = match pool_type {
+            PoolType::CurveStableSwap => Pool::CurveStableSwap(pool),
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
     }
 
-    pub fn new_balancer(pool_type: PoolType, pool: BalancerV2Pool) -> Self {
-        match pool_type {
+    pub fn new_balancer(pool_type: PoolType, pool: BalancerV2Pool) -> Result<Self, PoolSyncError> {
+        let pool = match pool_type {
             PoolType::BalancerV2 => Pool::BalancerV2(pool),
-            _ => panic!("Invalid pool type"),
-        }
+            _ => return Err(PoolSyncError::UnsupportedPoolType),
+        };
+        Ok(pool)
     }
 
     pub fn is_v2(&self) -> bool {
-        match self {
-            Pool::UniswapV2(_) => true,
-            Pool::SushiSwapV2(_) => true,
-            Pool::PancakeSwapV2(_) => true,
-            Pool::Aerodrome(_) => true,
-            Pool::BaseSwapV2(_) => true,
-            Pool::AlienBaseV2(_) => true,
-            Pool::SwapBasedV2(_) => true,
-            Pool::DackieSwapV2(_) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Pool::UniswapV2(_)
+                | Pool::SushiSwapV2(_)
+                | Pool::PancakeSwapV2(_)
+                | Pool::Aerodrome(_)
+                | Pool::Velodrome(_)
+                | Pool::BaseSwapV2(_)
+                | Pool::AlienBaseV2(_)
+                | Pool::SwapBasedV2(_)
+                | Pool::DackieSwapV2(_)
+        )
     }
 
     pub fn is_v3(&self) -> bool {
-        match self {
-            Pool::UniswapV3(_) => true,
-            Pool::SushiSwapV3(_) => true,
-            Pool::PancakeSwapV3(_) => true,
-            Pool::Slipstream(_) => true,
-            Pool::BaseSwapV3(_) => true,
-            Pool::AlienBaseV3(_) => true,
-            Pool::SwapBasedV3(_) => true,
-            Pool::DackieSwapV3(_) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Pool::UniswapV3(_)
+                | Pool::SushiSwapV3(_)
+                | Pool::PancakeSwapV3(_)
+                | Pool::Slipstream(_)
+                | Pool::BaseSwapV3(_)
+                | Pool::AlienBaseV3(_)
+                | Pool::SwapBasedV3(_)
+                | Pool::DackieSwapV3(_)
+                | Pool::Camelot(_)
+        )
+    }
+
+    pub fn is_v4(&self) -> bool {
+        matches!(self, Pool::UniswapV4(_))
     }
 
     pub fn is_maverick(&self) -> bool {
-        match self {
-            Pool::MaverickV1(_) => true,
-            Pool::MaverickV2(_) => true,
-            _ => false,
-        }
+        matches!(self, Pool::MaverickV1(_) | Pool::MaverickV2(_))
     }
 
     pub fn is_curve_two(&self) -> bool {
-        match self {
-            Pool::CurveTwoCrypto(_) => true,
-            _ => false,
-        }
+        matches!(self, Pool::CurveTwoCrypto(_))
     }
 
     pub fn is_curve_tri(&self) -> bool {
-        match self {
-            Pool::CurveTriCrypto(_) => true,
-            _ => false,
-        }
+        matches!(self, Pool::CurveTriCrypto(_))
+    }
+
+    pub fn is_curve_stable(&self) -> bool {
+        matches!(self, Pool::CurveStableSwap(_))
     }
 
     pub fn is_balancer(&self) -> bool {
-        match self {
-            Pool::BalancerV2(_) => true,
-            _ => false,
-        }
+        matches!(self, Pool::BalancerV2(_))
     }
 
     pub fn get_v2(&self) -> Option<&UniswapV2Pool> {
@@ -273,6 +387,7 @@ impl Pool {
             Pool::SushiSwapV2(pool) => Some(pool),
             Pool::PancakeSwapV2(pool) => Some(pool),
             Pool::Aerodrome(pool) => Some(pool),
+            Pool::Velodrome(pool) => Some(pool),
             Pool::BaseSwapV2(pool) => Some(pool),
             Pool::SwapBasedV2(pool) => Some(pool),
             Pool::DackieSwapV2(pool) => Some(pool),
@@ -291,6 +406,14 @@ impl Pool {
             Pool::AlienBaseV3(pool) => Some(pool),
             Pool::SwapBasedV3(pool) => Some(pool),
             Pool::DackieSwapV3(pool) => Some(pool),
+            Pool::Camelot(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
+    pub fn get_v4(&self) -> Option<&UniswapV4Pool> {
+        match self {
+            Pool::UniswapV4(pool) => Some(pool),
             _ => None,
         }
     }
@@ -317,6 +440,13 @@ impl Pool {
         }
     }
 
+    pub fn get_curve_stable(&self) -> Option<&CurveStableSwapPool> {
+        match self {
+            Pool::CurveStableSwap(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
     pub fn get_balancer(&self) -> Option<&BalancerV2Pool> {
         match self {
             Pool::BalancerV2(pool) => Some(pool),
@@ -330,6 +460,7 @@ impl Pool {
             Pool::SushiSwapV2(pool) => Some(pool),
             Pool::PancakeSwapV2(pool) => Some(pool),
             Pool::Aerodrome(pool) => Some(pool),
+            Pool::Velodrome(pool) => Some(pool),
             Pool::BaseSwapV2(pool) => Some(pool),
             Pool::AlienBaseV2(pool) => Some(pool),
             Pool::SwapBasedV2(pool) => Some(pool),
@@ -348,6 +479,14 @@ impl Pool {
             Pool::AlienBaseV3(pool) => Some(pool),
             Pool::SwapBasedV3(pool) => Some(pool),
             Pool::DackieSwapV3(pool) => Some(pool),
+            Pool::Camelot(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
+    pub fn get_v4_mut(&mut self) -> Option<&mut UniswapV4Pool> {
+        match self {
+            Pool::UniswapV4(pool) => Some(pool),
             _ => None,
         }
     }
@@ -374,6 +513,13 @@ impl Pool {
         }
     }
 
+    pub fn get_curve_stable_mut(&mut self) -> Option<&mut CurveStableSwapPool> {
+        match self {
+            Pool::CurveStableSwap(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
     pub fn get_balancer_mut(&mut self) -> Option<&mut BalancerV2Pool> {
         match self {
             Pool::BalancerV2(pool) => Some(pool),
@@ -381,12 +527,224 @@ impl Pool {
         }
     }
 
+    /// Cheap copy of the pool for simulating swaps. Only the state needed for quoting is
+    /// copied (reserves, liquidity, ticks), token names are left empty. Simulations should be
+    /// run against the snapshot so the synced pool is never mutated
+    pub fn snapshot_for_sim(&self) -> Pool {
+        match self {
+            Pool::UniswapV2(pool) => Pool::UniswapV2(pool.snapshot_for_sim()),
+            Pool::SushiSwapV2(pool) => Pool::SushiSwapV2(pool.snapshot_for_sim()),
+            Pool::PancakeSwapV2(pool) => Pool::PancakeSwapV2(pool.snapshot_for_sim()),
+            Pool::BaseSwapV2(pool) => Pool::BaseSwapV2(pool.snapshot_for_sim()),
+            Pool::AlienBaseV2(pool) => Pool::AlienBaseV2(pool.snapshot_for_sim()),
+            Pool::SwapBasedV2(pool) => Pool::SwapBasedV2(pool.snapshot_for_sim()),
+            Pool::DackieSwapV2(pool) => Pool::DackieSwapV2(pool.snapshot_for_sim()),
+            Pool::Aerodrome(pool) => Pool::Aerodrome(pool.snapshot_for_sim()),
+            Pool::Velodrome(pool) => Pool::Velodrome(pool.snapshot_for_sim()),
+            Pool::Slipstream(pool) => Pool::Slipstream(pool.snapshot_for_sim()),
+            Pool::UniswapV3(pool) => Pool::UniswapV3(pool.snapshot_for_sim()),
+            Pool::SushiSwapV3(pool) => Pool::SushiSwapV3(pool.snapshot_for_sim()),
+            Pool::PancakeSwapV3(pool) => Pool::PancakeSwapV3(pool.snapshot_for_sim()),
+            Pool::BaseSwapV3(pool) => Pool::BaseSwapV3(pool.snapshot_for_sim()),
+            Pool::AlienBaseV3(pool) => Pool::AlienBaseV3(pool.snapshot_for_sim()),
+            Pool::SwapBasedV3(pool) => Pool::SwapBasedV3(pool.snapshot_for_sim()),
+            Pool::DackieSwapV3(pool) => Pool::DackieSwapV3(pool.snapshot_for_sim()),
+            Pool::Camelot(pool) => Pool::Camelot(pool.snapshot_for_sim()),
+            Pool::UniswapV4(pool) => Pool::UniswapV4(pool.snapshot_for_sim()),
+            Pool::MaverickV1(pool) => Pool::MaverickV1(pool.snapshot_for_sim()),
+            Pool::MaverickV2(pool) => Pool::MaverickV2(pool.snapshot_for_sim()),
+            Pool::CurveTwoCrypto(pool) => Pool::CurveTwoCrypto(pool.snapshot_for_sim()),
+            Pool::CurveTriCrypto(pool) => Pool::CurveTriCrypto(pool.snapshot_for_sim()),
+            Pool::CurveStableSwap(pool) => Pool::CurveStableSwap(pool.snapshot_for_sim()),
+            Pool::BalancerV2(pool) => Pool::BalancerV2(pool.snapshot_for_sim()),
+        }
+    }
+
+    /// Apply a swap to the pool state as if it executed on chain and return the output amount.
+    /// Supports V2 and V3 style pools, everything else fails with `UnsupportedPoolType`. A V3
+    /// swap that can not be filled in full fails and leaves the pool untouched
+    pub fn apply_swap(
+        &mut self,
+        amount_in: U256,
+        token_in: Address,
+    ) -> Result<U256, PoolSyncError> {
+        if let Some(pool) = self.get_v2_mut() {
+            Ok(pool.apply_swap(amount_in, token_in))
+        } else if let Some(pool) = self.get_v3_mut() {
+            pool.apply_swap(amount_in, token_in)
+        } else {
+            Err(PoolSyncError::UnsupportedPoolType)
+        }
+    }
+
+    // The token swapped against `token` in a two token view of the pool
+    fn counter_token(&self, token: Address) -> Option<Address> {
+        let (token0, token1) = self.tokens();
+        if token == token0 {
+            Some(token1)
+        } else if token == token1 {
+            Some(token0)
+        } else {
+            None
+        }
+    }
+
+    /// All tokens held by the pool, including the extra tokens of multi asset pools
+    pub fn get_tokens(&self) -> Vec<Address> {
+        if let Some(pool) = self.get_balancer() {
+            pool.get_tokens()
+        } else if let Some(pool) = self.get_curve_tri() {
+            pool.get_tokens()
+        } else if let Some(pool) = self.get_curve_stable() {
+            pool.get_tokens()
+        } else {
+            vec![self.token0_address(), self.token1_address()]
+        }
+    }
+
+    /// Decimals of every token in the pool, in the same order as `get_tokens`
+    pub fn get_token_decimals(&self) -> Vec<u8> {
+        if let Some(pool) = self.get_balancer() {
+            let mut decimals = vec![pool.token0_decimals, pool.token1_decimals];
+            decimals.extend(pool.additional_token_decimals.iter());
+            decimals
+        } else if let Some(pool) = self.get_curve_tri() {
+            vec![pool.token0_decimals, pool.token1_decimals, pool.token2_decimals]
+        } else if let Some(pool) = self.get_curve_stable() {
+            pool.get_token_decimals()
+        } else {
+            vec![self.token0_decimals(), self.token1_decimals()]
+        }
+    }
+
+    /// Check the fetched token decimals against a second source and record the result. Only
+    /// tokens present in `token_decimals` are compared, the result stays None if none are
+    pub fn verify_decimals(&mut self, token_decimals: &HashMap<Address, u8>) -> Option<bool> {
+        let mut verified = None;
+        for (token, decimals) in self.get_tokens().into_iter().zip(self.get_token_decimals()) {
+            if let Some(expected) = token_decimals.get(&token) {
+                verified = Some(verified.unwrap_or(true) && *expected == decimals);
+            }
+        }
+
+        if let Some(pool) = self.get_v2_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_v3_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_v4_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_curve_two_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_curve_tri_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_curve_stable_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_balancer_mut() {
+            pool.decimals_verified = verified;
+        } else if let Some(pool) = self.get_maverick_mut() {
+            pool.decimals_verified = verified;
+        }
+        verified
+    }
+
+    /// Amount of `token` backing the pool in raw units, used to rank pools of the same pair.
+    /// V3 pools report the virtual reserve at the current price. Returns None if the pool does
+    /// not hold the token or the pool type does not track balances
+    pub fn token_depth(&self, token: Address) -> Option<U256> {
+        if let Some(pool) = self.get_v2() {
+            if token == pool.token0 {
+                Some(pool.token0_reserves)
+            } else if token == pool.token1 {
+                Some(pool.token1_reserves)
+            } else {
+                None
+            }
+        } else if let Some(pool) = self.get_v3() {
+            pool.virtual_reserve(token)
+        } else if let Some(pool) = self.get_balancer() {
+            pool.get_token_index(&token).map(|index| pool.balances[index])
+        } else if let Some(pool) = self.get_curve_stable() {
+            pool.get_token_index(&token).and_then(|index| pool.balances.get(index).copied())
+        } else {
+            None
+        }
+    }
+
+    /// Rough USD value locked in the pool, pricing the `token_depth` of every token with
+    /// `price_of`. V3 pools are valued at their virtual reserves, so concentrated positions
+    /// count for more than they hold. None if a token has no price or no tracked depth
+    pub fn tvl_usd(&self, price_of: impl Fn(Address) -> Option<f64>) -> Option<f64> {
+        self.get_tokens()
+            .into_iter()
+            .zip(self.get_token_decimals())
+            .map(|(token, decimals)| {
+                let depth = f64::from(self.token_depth(token)?) / 10f64.powi(decimals as i32);
+                Some(depth * price_of(token)?)
+            })
+            .sum()
+    }
+
     pub fn is_valid(&self) -> bool {
+        // a zero currency0 is the native token in v4, currencies are sorted so it is never token1
         self.address() != Address::ZERO
-            && self.token0_address() != Address::ZERO
+            && (self.token0_address() != Address::ZERO || self.is_v4())
             && self.token1_address() != Address::ZERO
     }
 
+    /// Spot price of `base` in the other token of the pool, adjusted for decimals. Same as
+    /// `PoolInfo::price`, None for the pool types without pricing (Maverick, Curve)
+    pub fn price_of(&self, base: Address) -> Option<f64> {
+        PoolInfo::price(self, base)
+    }
+
+    /// Whether the sync filled in everything about the pool: every token has a name and non
+    /// zero decimals, and with `with_liquidity` set V3 pools must also have ticks. Pools that
+    /// failed part way through a sync are left with the defaults and fail this check
+    pub fn is_complete(&self, with_liquidity: bool) -> bool {
+        let mut names = vec![self.token0_name(), self.token1_name()];
+        if let Some(pool) = self.get_balancer() {
+            names.extend(pool.additional_token_names.iter().cloned());
+        } else if let Some(pool) = self.get_curve_tri() {
+            names.push(pool.token2_name.clone());
+        } else if let Some(pool) = self.get_curve_stable() {
+            names.extend(pool.additional_token_names.iter().cloned());
+        }
+        if names.len() != self.get_tokens().len() || names.iter().any(|name| name.is_empty()) {
+            return false;
+        }
+        if self.get_token_decimals().contains(&0) {
+            return false;
+        }
+
+        match self.get_v3() {
+            Some(pool) if with_liquidity => !pool.ticks.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// Serialize the pool to JSON inside an envelope stamped with `POOL_FORMAT_VERSION`
+    pub fn to_serialized(&self) -> Result<Vec<u8>, PoolSyncError> {
+        let envelope = PoolEnvelope {
+            format_version: POOL_FORMAT_VERSION,
+            pool: self,
+        };
+        Ok(serde_json::to_vec(&envelope)?)
+    }
+
+    /// Deserialize a pool written by `to_serialized`. Data from another format version is
+    /// rejected with `IncompatibleFormat` instead of being decoded into the wrong shape
+    pub fn from_serialized(bytes: &[u8]) -> Result<Pool, PoolSyncError> {
+        let header: PoolEnvelopeHeader = serde_json::from_slice(bytes)?;
+        if header.format_version != POOL_FORMAT_VERSION {
+            return Err(PoolSyncError::IncompatibleFormat {
+                found: header.format_version,
+                expected: POOL_FORMAT_VERSION,
+            });
+        }
+        let envelope: OwnedPoolEnvelope = serde_json::from_slice(bytes)?;
+        Ok(envelope.pool)
+    }
+
     fn update_token0_name(pool: &mut Pool, token0: String) {
         if pool.is_v2() {
             let pool = pool.get_v2_mut().unwrap();
@@ -394,12 +752,18 @@ impl Pool {
         } else if pool.is_v3() {
             let pool = pool.get_v3_mut().unwrap();
             pool.token0_name = token0;
+        } else if pool.is_v4() {
+            let pool = pool.get_v4_mut().unwrap();
+            pool.token0_name = token0;
         } else if pool.is_curve_two() {
             let pool = pool.get_curve_two_mut().unwrap();
             pool.token0_name = token0;
         } else if pool.is_curve_tri() {
             let pool = pool.get_curve_tri_mut().unwrap();
             pool.token0_name = token0;
+        } else if pool.is_curve_stable() {
+            let pool = pool.get_curve_stable_mut().unwrap();
+            pool.token0_name = token0;
         } else if pool.is_balancer() {
             let pool = pool.get_balancer_mut().unwrap();
             pool.token0_name = token0;
@@ -416,12 +780,18 @@ impl Pool {
         } else if pool.is_v3() {
             let pool = pool.get_v3_mut().unwrap();
             pool.token1_name = token1;
+        } else if pool.is_v4() {
+            let pool = pool.get_v4_mut().unwrap();
+            pool.token1_name = token1;
         } else if pool.is_curve_two() {
             let pool = pool.get_curve_two_mut().unwrap();
             pool.token1_name = token1;
         } else if pool.is_curve_tri() {
             let pool = pool.get_curve_tri_mut().unwrap();
             pool.token1_name = token1;
+        } else if pool.is_curve_stable() {
+            let pool = pool.get_curve_stable_mut().unwrap();
+            pool.token1_name = token1;
         } else if pool.is_balancer() {
             let pool = pool.get_balancer_mut().unwrap();
             pool.token1_name = token1;
@@ -432,6 +802,27 @@ impl Pool {
     }
 }
 
+/// Bucket pools that are economically identical across forks, keyed by the sorted token pair
+/// and the fee in hundredths of a bip. Pools whose fee is not known are left out since they
+/// can not be matched
+pub fn group_equivalent_pools(pools: &[Pool]) -> HashMap<(Address, Address, u32), Vec<&Pool>> {
+    let mut groups: HashMap<(Address, Address, u32), Vec<&Pool>> = HashMap::new();
+    for pool in pools {
+        let Some(fee) = pool.fee_fraction() else {
+            continue;
+        };
+        let (token0, token1) = (pool.token0_address(), pool.token1_address());
+        let (token_a, token_b) = if token0 < token1 {
+            (token0, token1)
+        } else {
+            (token1, token0)
+        };
+        let fee = (fee * 1_000_000.0).round() as u32;
+        groups.entry((token_a, token_b, fee)).or_default().push(pool);
+    }
+    groups
+}
+
 impl fmt::Display for PoolType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -449,6 +840,7 @@ impl_pool_info!(
     PancakeSwapV3,
     Aerodrome,
     Slipstream,
+    Velodrome,
     BaseSwapV2,
     BaseSwapV3,
     AlienBaseV2,
@@ -457,11 +849,14 @@ impl_pool_info!(
     MaverickV2,
     CurveTwoCrypto,
     CurveTriCrypto,
+    CurveStableSwap,
     BalancerV2,
     SwapBasedV2,
     SwapBasedV3,
     DackieSwapV2,
-    DackieSwapV3
+    DackieSwapV3,
+    Camelot,
+    UniswapV4
 );
 
 /// Defines common functionality for fetching and decoding pool creation events
@@ -472,8 +867,9 @@ pub trait PoolFetcher: Send + Sync {
     /// Returns the type of pool this fetcher is responsible for
     fn pool_type(&self) -> PoolType;
 
-    /// Returns the factory address for the given chain
-    fn factory_address(&self, chain: Chain) -> Address;
+    /// Returns the factory address for the given chain, None if the protocol is not deployed
+    /// there
+    fn factory_address(&self, chain: Chain) -> Option<Address>;
 
     /// Returns the event signature for pool creation
     fn pair_created_signature(&self) -> &str;
@@ -483,8 +879,29 @@ pub trait PoolFetcher: Send + Sync {
 
     /// Get the DynSolType for the pool
     fn get_pool_repr(&self) -> DynSolType;
+
+    /// Registry the factory lists its pools in, None for factories without one. Discovery then
+    /// reads the list directly instead of scanning the creation logs
+    fn pool_registry(&self) -> Option<PoolRegistryFns> {
+        None
+    }
+}
+
+/// View functions of a factory that keeps an on chain list of every pool it deployed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolRegistryFns {
+    /// Signature of the getter of the number of listed pools
+    pub count: &'static str,
+    /// Signature of the getter of the pool at an index of the list
+    pub list: &'static str,
 }
 
+/// Registry of the curve factories
+pub const CURVE_POOL_REGISTRY: PoolRegistryFns = PoolRegistryFns {
+    count: "pool_count()",
+    list: "pool_list(uint256)",
+};
+
 /// Defines common methods that are used to access information about the pools
 pub trait PoolInfo {
     fn address(&self) -> Address;
@@ -497,6 +914,129 @@ pub trait PoolInfo {
     fn pool_type(&self) -> PoolType;
     fn fee(&self) -> u32;
     fn stable(&self) -> bool;
+
+    /// Swap fee in basis points. V2 forks report their fee, 30 unless the pool stores its own,
+    /// and concentrated pools their fee tier. Other pools round their fee fraction
+    fn fee_bps(&self) -> u32;
+
+    /// Whether the token decimals matched the configured second source. None if the pool was
+    /// not checked
+    fn decimals_verified(&self) -> Option<bool>;
+
+    /// Spot price of `base` denominated in the other token, None if pricing is not supported
+    fn price(&self, base: Address) -> Option<f64>;
+
+    /// Swap fee as a fraction of the input amount, None if it is not known
+    fn fee_fraction(&self) -> Option<f64>;
+
+    /// Fee inclusive price paid to buy a small amount of `base`
+    fn ask_price(&self, base: Address) -> Option<f64> {
+        Some(self.price(base)? / (1.0 - self.fee_fraction()?))
+    }
+
+    /// Fee inclusive price received when selling a small amount of `base`
+    fn bid_price(&self, base: Address) -> Option<f64> {
+        Some(self.price(base)? * (1.0 - self.fee_fraction()?))
+    }
+
+    /// Addresses of token0 and token1
+    fn tokens(&self) -> (Address, Address) {
+        (self.token0_address(), self.token1_address())
+    }
+
+    /// Decimals of token0 and token1
+    fn decimals(&self) -> (u8, u8) {
+        (self.token0_decimals(), self.token1_decimals())
+    }
+
+    /// Every token held by the pool. Only the token pair unless the pool holds more
+    fn all_tokens(&self) -> Vec<Address> {
+        let (token0, token1) = self.tokens();
+        vec![token0, token1]
+    }
+
+    /// Whether `token` is one of the tokens held by the pool
+    fn contains_token(&self, token: Address) -> bool {
+        self.all_tokens().contains(&token)
+    }
+}
+
+/// Swap quoting against the synced pool state. Pools holding more than two tokens swap between
+/// token0 and token1, use the structure methods for the other pairs. Both methods return None
+/// when the pool type has no swap math or the state can not fill the swap
+pub trait AmmSimulate {
+    /// Output amount of an exact input swap of `amount_in` of `token_in`
+    fn amount_out(&self, token_in: Address, amount_in: U256) -> Option<U256>;
+
+    /// Input amount needed to receive exactly `amount_out` of `token_out`. Curve pools have no
+    /// closed form, their input is the smallest one whose `amount_out` quote reaches the output
+    fn amount_in(&self, token_out: Address, amount_out: U256) -> Option<U256>;
+}
+
+/// Smallest input whose `quote` reaches `amount_out`, for a quote that grows with the input.
+/// The input doubles until the quote reaches the output and is then bisected. None when the
+/// quote fails before reaching it
+pub(crate) fn invert_quote(
+    amount_out: U256,
+    quote: impl Fn(U256) -> Option<U256>,
+) -> Option<U256> {
+    let mut high = U256::from(1);
+    while quote(high)? < amount_out {
+        high = high.checked_mul(U256::from(2))?;
+    }
+    // the answer is in (low, high]
+    let mut low = high / U256::from(2);
+    while high - low > U256::from(1) {
+        let mid = low + (high - low) / U256::from(2);
+        match quote(mid) {
+            Some(amount) if amount >= amount_out => high = mid,
+            _ => low = mid,
+        }
+    }
+    Some(high)
+}
+
+impl AmmSimulate for Pool {
+    fn amount_out(&self, token_in: Address, amount_in: U256) -> Option<U256> {
+        let token_out = self.counter_token(token_in)?;
+        match self {
+            Pool::UniswapV4(_) | Pool::MaverickV1(_) | Pool::MaverickV2(_) => None,
+            Pool::CurveTriCrypto(_) => None,
+            Pool::CurveTwoCrypto(pool) => {
+                let i = usize::from(token_in == pool.token1);
+                pool.get_dy(i, 1 - i, amount_in)
+            }
+            Pool::CurveStableSwap(pool) => {
+                let i = pool.get_token_index(&token_in)?;
+                pool.get_dy(i, pool.get_token_index(&token_out)?, amount_in)
+            }
+            Pool::BalancerV2(pool) => {
+                Some(pool.get_amount_out_multi(amount_in, token_in, token_out))
+                    .filter(|amount| !amount.is_zero())
+            }
+            _ => {
+                if let Some(pool) = self.get_v2() {
+                    pool.get_amount_out(amount_in, token_in)
+                } else {
+                    self.get_v3()?.simulate_swap(token_in, amount_in).ok()
+                }
+            }
+        }
+    }
+
+    fn amount_in(&self, token_out: Address, amount_out: U256) -> Option<U256> {
+        let token_in = self.counter_token(token_out)?;
+        match self {
+            Pool::CurveTwoCrypto(_) | Pool::CurveStableSwap(_) => {
+                invert_quote(amount_out, |amount_in| self.amount_out(token_in, amount_in))
+            }
+            Pool::BalancerV2(pool) => {
+                Some(pool.get_amount_in_multi(amount_out, token_in, token_out))
+                    .filter(|amount| !amount.is_zero())
+            }
+            _ => self.get_v2()?.get_amount_in(amount_out, token_out),
+        }
+    }
 }
 
 /* 
@@ -573,6 +1113,14 @@ macro_rules! impl_pool_info {
                 }
             }
 
+            fn decimals_verified(&self) -> Option<bool> {
+                match self {
+                    $(
+                        $enum_name::$variant(pool) => pool.decimals_verified,
+                    )+
+                }
+            }
+
             fn pool_type(&self) -> PoolType {
                 match self {
                     $(
@@ -582,18 +1130,68 @@ macro_rules! impl_pool_info {
             }
 
             fn fee(&self) -> u32 {
-                match self {
-                    Pool::UniswapV3(pool) | Pool::SushiSwapV3(pool) | Pool::PancakeSwapV3(pool) | Pool::Slipstream(pool) => pool.fee,
-                    _ => 0
+                // every v3 style pool, including the dynamic fee of algebra pools
+                if let Some(pool) = self.get_v3() {
+                    pool.fee
+                } else if let Some(pool) = self.get_v4() {
+                    pool.fee
+                } else {
+                    0
                 }
             }
 
             fn stable(&self) -> bool {
                 match self {
-                    Pool::Aerodrome(pool) => pool.stable.unwrap(),
+                    Pool::Aerodrome(pool) | Pool::Velodrome(pool) => pool.stable.unwrap(),
                     _=> false
                 }
             }
+
+            fn fee_bps(&self) -> u32 {
+                if let Some(pool) = self.get_v2() {
+                    pool.fee_bps()
+                } else if let Some(pool) = self.get_v3() {
+                    pool.fee_bps()
+                } else if let Some(pool) = self.get_v4() {
+                    pool.fee_bps()
+                } else {
+                    self.fee_fraction().map_or(0, |fee| (fee * 10_000.0).round() as u32)
+                }
+            }
+
+            fn price(&self, base: Address) -> Option<f64> {
+                if let Some(pool) = self.get_v2() {
+                    pool.price(base)
+                } else if let Some(pool) = self.get_v3() {
+                    pool.price(base)
+                } else if let Some(pool) = self.get_v4() {
+                    pool.price(base)
+                } else if let Some(pool) = self.get_balancer() {
+                    pool.price(base)
+                } else {
+                    None
+                }
+            }
+
+            fn fee_fraction(&self) -> Option<f64> {
+                if let Some(pool) = self.get_v2() {
+                    Some(pool.fee_fraction())
+                } else if let Some(pool) = self.get_v3() {
+                    Some(pool.fee_fraction())
+                } else if let Some(pool) = self.get_v4() {
+                    Some(pool.fee_fraction())
+                } else if let Some(pool) = self.get_balancer() {
+                    Some(pool.fee_fraction())
+                } else if let Some(pool) = self.get_curve_stable() {
+                    Some(pool.fee_fraction())
+                } else {
+                    None
+                }
+            }
+
+            fn all_tokens(&self) -> Vec<Address> {
+                self.get_tokens()
+            }
         }
     };
 }
@@ -2,13 +2,20 @@
 //    pools::{Pool, PoolType}, rpc::{DataEvents, PancakeSwap, Rpc}
 //}; //, snapshot::{v3_tick_snapshot, v3_tickbitmap_snapshot}};
 use crate::PoolInfo;
+use alloy::contract::Error as ContractError;
 use alloy::dyn_abi::DynSolType;
+use alloy::eips::BlockId;
 use alloy::network::Network;
-use alloy::primitives::{address, Address};
+use alloy::primitives::{Address, U256};
 use alloy::providers::Provider;
-use alloy::transports::Transport;
-use anyhow::Result;
+use alloy::transports::{RpcError, Transport};
+use alloy::rpc::types::Log;
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use futures::future::join_all;
+use log::warn;
 use rand::Rng;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -18,18 +25,77 @@ use super::gen::{
 };
 
 use crate::pools::gen::ERC20;
-use crate::pools::gen::{AerodromePool, AerodromeV2Factory};
-use crate::pools::{Pool, PoolType, Chain};
-
-pub const INITIAL_BACKOFF: u64 = 1000; // 1 second
-pub const MAX_RETRIES: u32 = 5;
+use crate::pools::gen::{
+    AerodromePool, AerodromeV2Factory, AlgebraPool, StableSwapFactory, StableSwapPool,
+};
+use crate::pools::token_metadata::{cached_token_metadata, decode_symbol};
+use crate::pools::pool_structures::stable_swap_curve_structure::CurveStableSwapPool;
+use crate::pools::pool_structures::v3_structure::UniswapV3Pool;
+use crate::pools::pool_structures::v4_structure::UniswapV4Pool;
+use crate::pools::{Pool, PoolType};
+use crate::rpc::RetryConfig;
+use crate::snapshot::read_two_crypto_state;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn build_pools<P, T, N>(
     provider: &Arc<P>,
     addresses: Vec<Address>,
     pool_type: PoolType,
     data: DynSolType,
-    chain: Chain,
+    factory: Address,
+    token_list: &HashMap<Address, String>,
+    token_cache: &DashMap<Address, (String, u8)>,
+    retry: RetryConfig,
+) -> Result<Vec<Pool>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    // a single pool that reverts takes the whole batch call down with it, so reverting batches
+    // are split in half until the bad pools are isolated and skipped
+    let mut batches = vec![addresses];
+    let mut pools = Vec::new();
+    while let Some(batch) = batches.pop() {
+        match populate_with_retry(
+            provider,
+            &batch,
+            pool_type,
+            &data,
+            factory,
+            token_list,
+            token_cache,
+            retry,
+        )
+        .await
+        {
+            Ok(populated) => pools.extend(populated),
+            Err(e) if is_revert(&e) && batch.len() > 1 => {
+                let (left, right) = batch.split_at(batch.len() / 2);
+                batches.push(right.to_vec());
+                batches.push(left.to_vec());
+            }
+            Err(e) if is_revert(&e) => {
+                warn!("Skipping {} pool {}, it reverts the data sync", pool_type, batch[0]);
+            }
+            Err(e) => eprintln!("Max retries reached. Error: {:?} {:?}", e, batch),
+        }
+    }
+    Ok(pools)
+}
+
+// Populate a batch, retrying transient errors with backoff. Reverts are returned right away
+// since retrying them can not succeed
+#[allow(clippy::too_many_arguments)]
+async fn populate_with_retry<P, T, N>(
+    provider: &Arc<P>,
+    addresses: &[Address],
+    pool_type: PoolType,
+    data: &DynSolType,
+    factory: Address,
+    token_list: &HashMap<Address, String>,
+    token_cache: &DashMap<Address, (String, u8)>,
+    retry: RetryConfig,
 ) -> Result<Vec<Pool>>
 where
     P: Provider<T, N> + Sync + 'static,
@@ -37,20 +103,23 @@ where
     N: Network,
 {
     let mut retry_count = 0;
-    let mut backoff = INITIAL_BACKOFF;
+    let mut backoff = retry.initial_backoff_ms;
 
     loop {
-        match populate_pool_data(provider, addresses.clone(), pool_type, data.clone(), chain).await
+        match populate_pool_data(
+            provider,
+            addresses.to_vec(),
+            pool_type,
+            data.clone(),
+            factory,
+            token_list,
+            token_cache,
+        )
+        .await
         {
-            Ok(pools) => {
-                return Ok(pools);
-            }
-            Err(e) => {
-                if retry_count >= MAX_RETRIES {
-                    eprintln!("Max retries reached. Error: {:?} {:?}", e, addresses);
-                    return Ok(Vec::new());
-                }
-
+            Ok(pools) => return Ok(pools),
+            Err(e) if is_revert(&e) || retry_count >= retry.retry_limit => return Err(e),
+            Err(_) => {
                 let jitter = rand::thread_rng().gen_range(0..=100);
                 let sleep_duration = Duration::from_millis(backoff + jitter);
                 tokio::time::sleep(sleep_duration).await;
@@ -62,12 +131,120 @@ where
     }
 }
 
+// Whether the error is the node rejecting the call because the contract reverted
+pub(crate) fn is_revert(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<ContractError>(),
+        Some(ContractError::TransportError(RpcError::ErrorResp(payload)))
+            if payload.message.to_lowercase().contains("revert")
+    )
+}
+
 async fn populate_pool_data<P, T, N>(
     provider: &Arc<P>,
     pool_addresses: Vec<Address>,
     pool_type: PoolType,
     data: DynSolType,
-    chain: Chain
+    factory: Address,
+    token_list: &HashMap<Address, String>,
+    token_cache: &DashMap<Address, (String, u8)>,
+) -> Result<Vec<Pool>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let mut pools = match pool_type {
+        PoolType::CurveStableSwap => {
+            read_stable_swap_pools(provider, &pool_addresses, factory).await?
+        }
+        PoolType::Camelot => read_algebra_pools(provider, &pool_addresses).await?,
+        PoolType::CurveTwoCrypto => {
+            let pools =
+                decode_pool_data(provider, pool_addresses, pool_type, data, factory).await?;
+            read_two_crypto_states(provider, pools).await?
+        }
+        _ => decode_pool_data(provider, pool_addresses, pool_type, data, factory).await?,
+    };
+
+    // read the symbols of every uncached token in the batch at once, tokens the multicall can
+    // not cover fall back to single calls
+    let tokens: Vec<Address> = pools
+        .iter()
+        .flat_map(|pool| pool.get_tokens())
+        .filter(|token| !token_list.contains_key(token))
+        .collect();
+    let metadata = cached_token_metadata(provider, &tokens, token_cache).await;
+
+    // fill in missing info for the pool, this is more impl specific details
+    for pool in &mut pools {
+        let token0 = pool.token0_address();
+        if let Some(name) = token_symbol(provider, token0, token_list, &metadata).await {
+            Pool::update_token0_name(pool, name);
+        }
+
+        let token1 = pool.token1_address();
+        if let Some(name) = token_symbol(provider, token1, token_list, &metadata).await {
+            Pool::update_token1_name(pool, name);
+        }
+
+        // If the pool is balancer, update names for the other tokens
+        if pool_type == PoolType::BalancerV2 {
+            let pool = pool.get_balancer_mut().unwrap();
+            for token in &pool.additional_tokens {
+                if let Some(name) = token_symbol(provider, *token, token_list, &metadata).await {
+                    pool.additional_token_names.push(name);
+                }
+            }
+        }
+
+        // stableswap pools can hold up to four coins
+        if pool_type == PoolType::CurveStableSwap {
+            let pool = pool.get_curve_stable_mut().unwrap();
+            for token in &pool.additional_tokens {
+                if let Some(name) = token_symbol(provider, *token, token_list, &metadata).await {
+                    pool.additional_token_names.push(name);
+                }
+            }
+        }
+
+        // if the pool is curve, update name for the third token
+        if pool_type == PoolType::CurveTriCrypto {
+            let pool = pool.get_curve_tri_mut().unwrap();
+            if let Some(name) = token_symbol(provider, pool.token2, token_list, &metadata).await {
+                pool.token2_name = name;
+            }
+        }
+
+        // if the pool is a solidly fork, update the fee and if it is stable or not
+        if matches!(pool_type, PoolType::Aerodrome | PoolType::Velodrome) {
+            let pool = pool.get_v2_mut().unwrap();
+            // get if it is stable or not
+            let pool_contract = AerodromePool::new(pool.address, &provider);
+            let AerodromePool::stableReturn { _0: stable } =
+                pool_contract.stable().call().await.unwrap();
+            pool.stable = Some(stable);
+
+            let factory_contract = AerodromeV2Factory::new(factory, &provider);
+            let AerodromeV2Factory::getFeeReturn { _0: fee } = factory_contract
+                .getFee(pool.address, stable)
+                .call()
+                .await
+                .unwrap();
+            pool.fee = Some(fee);
+        }
+    }
+
+    Ok(pools)
+}
+
+// Read the pool info of the batch through its data sync contract and decode it
+async fn decode_pool_data<P, T, N>(
+    provider: &Arc<P>,
+    pool_addresses: Vec<Address>,
+    pool_type: PoolType,
+    data: DynSolType,
+    factory: Address,
 ) -> Result<Vec<Pool>>
 where
     P: Provider<T, N> + Sync + 'static,
@@ -81,6 +258,7 @@ where
         | PoolType::PancakeSwapV2
         | PoolType::BaseSwapV2
         | PoolType::Aerodrome
+        | PoolType::Velodrome
         | PoolType::AlienBaseV2
         | PoolType::SwapBasedV2
         | PoolType::DackieSwapV2 => {
@@ -111,20 +289,16 @@ where
             BalancerV2DataSync::deploy_builder(provider.clone(), pool_addresses.to_vec()).await?
         }
         PoolType::CurveTwoCrypto => {
-            let factory_addr = if chain == Chain::Ethereum {
-                address!("98EE851a00abeE0d95D08cF4CA2BdCE32aeaAF7F")
-            } else {
-                address!("c9Fe0C63Af9A39402e8a5514f9c43Af0322b665F")
-            };
-            TwoCurveDataSync::deploy_builder(provider.clone(), factory_addr,  pool_addresses.to_vec()).await?
+            TwoCurveDataSync::deploy_builder(provider.clone(), factory,  pool_addresses.to_vec()).await?
         }
         PoolType::CurveTriCrypto => {
-            let factory_addr = if chain == Chain::Ethereum {
-                address!("0c0e5f2fF0ff18a3be9b835635039256dC4B4963")
-            } else {
-                address!("A5961898870943c68037F6848d2D866Ed2016bcB")
-            };
-            TriCurveDataSync::deploy_builder(provider.clone(), factory_addr, pool_addresses.to_vec()).await?
+            TriCurveDataSync::deploy_builder(provider.clone(), factory, pool_addresses.to_vec()).await?
+        }
+        PoolType::UniswapV4 => {
+            return Err(anyhow!("V4 pools are built from their Initialize logs"));
+        }
+        PoolType::CurveStableSwap | PoolType::Camelot => {
+            return Err(anyhow!("{} pools are read through the pool views", pool_type));
         }
     };
 
@@ -134,65 +308,244 @@ where
     if let Some(pool_data_arr) = decoded_data.as_array() {
         for pool_data_tuple in pool_data_arr {
             if let Some(pool_data) = pool_data_tuple.as_tuple() {
-                let pool = pool_type.build_pool(pool_data);
-                if pool.is_valid() {
-                    pools.push(pool);
+                match pool_type.build_pool(pool_data) {
+                    Ok(pool) if pool.is_valid() => pools.push(pool),
+                    Ok(_) => {}
+                    Err(e) => warn!("Skipping {} pool that failed to decode: {}", pool_type, e),
                 }
             }
         }
     }
+    Ok(pools)
+}
 
-    // fill in missing info for the pool, this is more impl specific details. fetched by the full node, okay to not batch
-    for pool in &mut pools {
-        let token0_contract = ERC20::new(pool.token0_address(), &provider);
-        if let Ok(ERC20::symbolReturn { _0: name }) = token0_contract.symbol().call().await {
-            Pool::update_token0_name(pool, name);
+// Keep the pools that were read. A pool whose views revert is skipped like a pool that fails
+// to decode, any other error fails the batch so the pools are read again
+pub(crate) fn skip_reverted<T>(
+    pool_type: PoolType,
+    results: Vec<(Address, Result<T>)>,
+) -> Result<Vec<T>> {
+    let mut pools = Vec::with_capacity(results.len());
+    for (address, result) in results {
+        match result {
+            Ok(pool) => pools.push(pool),
+            Err(e) if is_revert(&e) => {
+                warn!("Skipping {} pool {} that failed to read: {}", pool_type, address, e)
+            }
+            Err(e) => return Err(e),
         }
+    }
+    Ok(pools)
+}
 
-        let token1_contract = ERC20::new(pool.token1_address(), &provider);
-        if let Ok(ERC20::symbolReturn { _0: name }) = token1_contract.symbol().call().await {
-            Pool::update_token1_name(pool, name);
-        }
+// Read the coins and state of StableSwap plain pools from the factory and pool views. There
+// is no data sync contract for these pools, so every pool takes six calls
+async fn read_stable_swap_pools<P, T, N>(
+    provider: &Arc<P>,
+    pool_addresses: &[Address],
+    factory: Address,
+) -> Result<Vec<Pool>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let factory = StableSwapFactory::new(factory, provider);
+    let results = join_all(pool_addresses.iter().map(|address| {
+        let factory = &factory;
+        let read = async move {
+            let pool = StableSwapPool::new(*address, provider);
+            let coins = factory.get_coins(*address).call().await?._0;
+            let decimals: Vec<u8> = factory
+                .get_decimals(*address)
+                .call()
+                .await?
+                ._0
+                .into_iter()
+                .map(|decimals| u8::try_from(decimals).unwrap_or_default())
+                .collect();
+            let balances = factory.get_balances(*address).call().await?._0;
+            let a = pool.A_precise().call().await?._0;
+            let fee = pool.fee().call().await?._0;
+            // older pools do not expose their rates, plain coins scale to 18 decimals
+            let rates = match pool.stored_rates().call().await {
+                Ok(rates) => rates._0,
+                Err(_) => decimals
+                    .iter()
+                    .map(|d| U256::from(10).pow(U256::from(36 - (*d).min(36) as u64)))
+                    .collect(),
+            };
+            Ok::<_, anyhow::Error>(CurveStableSwapPool::from_state(
+                *address, &coins, &decimals, balances, rates, a, fee,
+            ))
+        };
+        async move { (*address, read.await) }
+    }))
+    .await;
+    let pools = skip_reverted(PoolType::CurveStableSwap, results)?;
 
-        // If the pool is balancer, update names for the other tokens
-        if pool_type == PoolType::BalancerV2 {
-            let pool = pool.get_balancer_mut().unwrap();
-            for token in &pool.additional_tokens {
-                let token_contract = ERC20::new(*token, &provider);
-                if let Ok(ERC20::symbolReturn { _0: name }) = token_contract.symbol().call().await {
-                    pool.additional_token_names.push(name);
-                }
-            }
-        }
+    Ok(pools
+        .into_iter()
+        .flatten()
+        .map(Pool::CurveStableSwap)
+        .filter(|pool| pool.is_valid())
+        .collect())
+}
 
-        // if the pool is curve, update name for the third token
-        if pool_type == PoolType::CurveTriCrypto {
-            let pool = pool.get_curve_tri_mut().unwrap();
-            let token_contract = ERC20::new(pool.token2, &provider);
-            if let Ok(ERC20::symbolReturn { _0: name }) = token_contract.symbol().call().await {
-                pool.token2_name = name;
-            }
-        }
+// Read the state of two coin cryptoswap pools, the data sync contract only returns their coins
+async fn read_two_crypto_states<P, T, N>(provider: &Arc<P>, pools: Vec<Pool>) -> Result<Vec<Pool>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let results = join_all(pools.into_iter().map(|mut pool| async move {
+        let address = pool.address();
+        let read = match pool.get_curve_two_mut() {
+            Some(curve) => read_two_crypto_state(provider.as_ref(), curve, BlockId::latest()).await,
+            None => Ok(()),
+        };
+        (address, read.map(|_| pool))
+    }))
+    .await;
+    skip_reverted(PoolType::CurveTwoCrypto, results)
+}
 
-        // if the pool is aerodrome, update the fee and if it is stable or not
-        if pool_type == PoolType::Aerodrome {
-            let factory = address!("420DD381b31aEf6683db6B902084cB0FFECe40Da");
-            let pool = pool.get_v2_mut().unwrap();
-            // get if it is stable or not
-            let pool_contract = AerodromePool::new(pool.address, &provider);
-            let AerodromePool::stableReturn { _0: stable } =
-                pool_contract.stable().call().await.unwrap();
-            pool.stable = Some(stable);
+// Read Algebra pools through their views. The state is the same as a V3 pool apart from the
+// dynamic fee, which is taken from the global state. Ticks are filled in by the liquidity sync.
+// Pools whose views revert are skipped
+async fn read_algebra_pools<P, T, N>(
+    provider: &Arc<P>,
+    pool_addresses: &[Address],
+) -> Result<Vec<Pool>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let results = join_all(pool_addresses.iter().map(|address| async move {
+        let read = async {
+            let pool = AlgebraPool::new(*address, provider);
+            let token0 = pool.token0().call().await?._0;
+            let token1 = pool.token1().call().await?._0;
+            let state = pool.globalState().call().await?;
+            let decimals = |token| async move {
+                ERC20::new(token, provider).decimals().call().await.map(|decimals| decimals._0)
+            };
+            Ok::<_, anyhow::Error>(Pool::Camelot(UniswapV3Pool {
+                address: *address,
+                token0,
+                token1,
+                token0_decimals: decimals(token0).await?,
+                token1_decimals: decimals(token1).await?,
+                liquidity: pool.liquidity().call().await?._0,
+                sqrt_price: U256::from(state.price),
+                fee: u32::from(state.fee),
+                tick: state.tick.as_i32(),
+                tick_spacing: pool.tickSpacing().call().await?._0.as_i32(),
+                algebra: true,
+                ..Default::default()
+            }))
+        };
+        (*address, read.await)
+    }))
+    .await;
+    let pools = skip_reverted(PoolType::Camelot, results)?;
+    Ok(pools.into_iter().filter(|pool| pool.is_valid()).collect())
+}
 
-            let factory_contract = AerodromeV2Factory::new(factory, &provider);
-            let AerodromeV2Factory::getFeeReturn { _0: fee } = factory_contract
-                .getFee(pool.address, stable)
-                .call()
-                .await
-                .unwrap();
-            pool.fee = Some(fee);
-        }
+/// Build V4 pools from their `Initialize` logs. The PoolManager only stores pools by id, so the
+/// logs are the only place the currencies, fee, and hooks of a pool can be read from
+pub async fn build_v4_pools<P, T, N>(
+    provider: &Arc<P>,
+    logs: &[Log],
+    token_list: &HashMap<Address, String>,
+    token_cache: &DashMap<Address, (String, u8)>,
+) -> Result<Vec<Pool>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let mut pools: Vec<UniswapV4Pool> =
+        logs.iter().filter_map(UniswapV4Pool::from_initialize).collect();
+
+    let tokens: Vec<Address> = pools
+        .iter()
+        .flat_map(|pool| [pool.token0, pool.token1])
+        .filter(|token| *token != Address::ZERO)
+        .collect();
+    let metadata = cached_token_metadata(provider, &tokens, token_cache).await;
+
+    for pool in &mut pools {
+        let (token0, token1) = (pool.token0, pool.token1);
+        let (name0, decimals0) = v4_currency(provider, token0, token_list, &metadata).await;
+        let (name1, decimals1) = v4_currency(provider, token1, token_list, &metadata).await;
+        pool.token0_name = name0;
+        pool.token0_decimals = decimals0;
+        pool.token1_name = name1;
+        pool.token1_decimals = decimals1;
     }
+    Ok(pools.into_iter().map(Pool::UniswapV4).collect())
+}
 
-    Ok(pools)
+// Symbol and decimals of a V4 currency, the zero address is the native token. Unknown values
+// are left empty so the pool shows up in `incomplete_pools`
+async fn v4_currency<P, T, N>(
+    provider: &Arc<P>,
+    currency: Address,
+    token_list: &HashMap<Address, String>,
+    metadata: &HashMap<Address, (String, u8)>,
+) -> (String, u8)
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    // every chain with a V4 deployment uses ether as the native token
+    if currency == Address::ZERO {
+        return ("ETH".to_string(), 18);
+    }
+    let symbol = token_symbol(provider, currency, token_list, metadata).await.unwrap_or_default();
+    let decimals = match metadata.get(&currency) {
+        Some((_, decimals)) => *decimals,
+        None => ERC20::new(currency, provider)
+            .decimals()
+            .call()
+            .await
+            .map(|decimals| decimals._0)
+            .unwrap_or_default(),
+    };
+    (symbol, decimals)
+}
+
+// Symbol of a token. The token list is checked first, then the batched metadata, then
+// `symbol()` and `name()` for proxies and nonstandard tokens that return an empty symbol. The
+// results are decoded by hand since older tokens like MKR return a bytes32 instead of a string
+pub(crate) async fn token_symbol<P, T, N>(
+    provider: &Arc<P>,
+    token: Address,
+    token_list: &HashMap<Address, String>,
+    metadata: &HashMap<Address, (String, u8)>,
+) -> Option<String>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    if let Some(symbol) = token_list.get(&token) {
+        return Some(symbol.clone());
+    }
+    if let Some((symbol, _)) = metadata.get(&token) {
+        return Some(symbol.clone());
+    }
+
+    let token_contract = ERC20::new(token, provider);
+    if let Ok(symbol) = token_contract.symbol().call_raw().await {
+        if let Some(symbol) = decode_symbol(&symbol) {
+            return Some(symbol);
+        }
+    }
+    let name = token_contract.name().call_raw().await.ok()?;
+    decode_symbol(&name)
 }
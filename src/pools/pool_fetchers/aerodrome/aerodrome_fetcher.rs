@@ -16,10 +16,10 @@ impl PoolFetcher for AerodromeFetcher {
         PoolType::Aerodrome
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("420DD381b31aEf6683db6B902084cB0FFECe40Da"),
-            _ => panic!("Aerodome not supported on this chain")
+            Chain::Base => Some(address!("420DD381b31aEf6683db6B902084cB0FFECe40Da")),
+            _ => None,
         }
     }
 
@@ -15,10 +15,10 @@ impl PoolFetcher for SlipstreamFetcher {
         PoolType::Slipstream
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("5e7BB104d84c7CB9B682AaC2F3d509f5F406809A"),
-            _ => panic!("Aerodome not supported on this chain")
+            Chain::Base => Some(address!("5e7BB104d84c7CB9B682AaC2F3d509f5F406809A")),
+            _ => None,
         }
     }
 
@@ -16,10 +16,10 @@ impl PoolFetcher for AlienBaseV2Fetcher {
         PoolType::AlienBaseV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("3E84D913803b02A4a7f027165E8cA42C14C0FdE7"),
-            _=> panic!("AlienBase not supported on this chain")
+            Chain::Base => Some(address!("3E84D913803b02A4a7f027165E8cA42C14C0FdE7")),
+            _ => None,
         }
     }
 
@@ -14,10 +14,10 @@ impl PoolFetcher for AlienBaseV3Fetcher {
         PoolType::AlienBaseV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("0Fd83557b2be93617c9C1C1B6fd549401C74558C"),
-            _ => panic!("Alienbase not supported on this chain")
+            Chain::Base => Some(address!("0Fd83557b2be93617c9C1C1B6fd549401C74558C")),
+            _ => None,
         }
     }
     
@@ -15,10 +15,12 @@ impl PoolFetcher for BalancerV2Fetcher {
         PoolType::BalancerV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("897888115Ada5773E02aA29F775430BFB5F34c51"),            
-            Chain::Base => address!("4C32a8a8fDa4E24139B51b456B42290f51d6A1c4"),
+            Chain::Ethereum => Some(address!("897888115Ada5773E02aA29F775430BFB5F34c51")),
+            Chain::Base => Some(address!("4C32a8a8fDa4E24139B51b456B42290f51d6A1c4")),
+            Chain::Arbitrum => Some(address!("c7E5ED1054A24Ef31D827E6F86caA58B3Bc168d7")),
+            _ => None,
         }
     }
 
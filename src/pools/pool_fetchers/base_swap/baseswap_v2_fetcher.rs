@@ -14,10 +14,10 @@ impl PoolFetcher for BaseSwapV2Fetcher {
         PoolType::BaseSwapV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("FDa619b6d20975be80A10332cD39b9a4b0FAa8BB"),
-            _ => panic!("BaseSwap not supported on this chain")
+            Chain::Base => Some(address!("FDa619b6d20975be80A10332cD39b9a4b0FAa8BB")),
+            _ => None,
         }
     }
 
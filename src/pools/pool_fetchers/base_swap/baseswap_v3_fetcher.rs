@@ -15,10 +15,10 @@ impl PoolFetcher for BaseSwapV3Fetcher {
         PoolType::BaseSwapV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("38015D05f4fEC8AFe15D7cc0386a126574e8077B"),
-            _ => panic!("Aerodome not supported on this chain")
+            Chain::Base => Some(address!("38015D05f4fEC8AFe15D7cc0386a126574e8077B")),
+            _ => None,
         }
     }
 
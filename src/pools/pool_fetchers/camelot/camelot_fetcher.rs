@@ -0,0 +1,38 @@
+use alloy::primitives::{address, Address};
+use alloy::sol_types::SolEvent;
+use alloy::primitives::Log;
+use alloy::dyn_abi::DynSolType;
+use crate::pools::gen::AlgebraFactory;
+use crate::pools::PoolFetcher;
+use crate::pools::PoolType;
+use crate::Chain;
+
+/// Camelot V3 pools, deployed by an Algebra factory. The pool state is read through the pool
+/// views rather than a data sync contract
+pub struct CamelotFetcher;
+
+impl PoolFetcher for CamelotFetcher {
+    fn pool_type(&self) -> PoolType {
+        PoolType::Camelot
+    }
+
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
+        match chain {
+            Chain::Arbitrum => Some(address!("1a3c9B1d2F0529D97f2afC5136Cc23e58f1FD35B")),
+            _ => None,
+        }
+    }
+
+    fn pair_created_signature(&self) -> &str {
+        AlgebraFactory::Pool::SIGNATURE
+    }
+
+    fn log_to_address(&self, log: &Log) -> Address {
+        let decoded_log = AlgebraFactory::Pool::decode_log(log, false).unwrap();
+        decoded_log.data.pool
+    }
+
+    fn get_pool_repr(&self) -> DynSolType {
+        DynSolType::Array(Box::new(DynSolType::Address))
+    }
+}
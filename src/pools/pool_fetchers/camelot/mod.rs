@@ -0,0 +1,2 @@
+pub use camelot_fetcher::CamelotFetcher;
+mod camelot_fetcher;
@@ -1,4 +1,6 @@
+pub use stable_swap::CurveStableSwapFetcher;
 pub use tri_crypto::CurveTriCryptoFetcher;
 pub use two_crypto::CurveTwoCryptoFetcher;
 pub mod two_crypto;
-pub mod tri_crypto;
\ No newline at end of file
+pub mod tri_crypto;
+pub mod stable_swap;
\ No newline at end of file
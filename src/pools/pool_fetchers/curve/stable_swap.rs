@@ -0,0 +1,44 @@
+use alloy::primitives::{address, Address};
+use alloy::sol_types::SolEvent;
+use alloy::primitives::Log;
+use alloy::dyn_abi::DynSolType;
+
+use crate::pools::gen::StableSwapFactory;
+use crate::pools::{PoolFetcher, PoolRegistryFns, CURVE_POOL_REGISTRY};
+use crate::pools::PoolType;
+use crate::Chain;
+
+/// Plain pools of the StableSwap-NG factory. The pool state is read through the factory and
+/// pool views rather than a data sync contract
+pub struct CurveStableSwapFetcher;
+
+impl PoolFetcher for CurveStableSwapFetcher {
+    fn pool_type(&self) -> PoolType {
+        PoolType::CurveStableSwap
+    }
+
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
+        match chain {
+            Chain::Ethereum => Some(address!("6A8cbed756804B16E05E741eDaBd5cB544AE21bf")),
+            _ => None,
+        }
+    }
+
+    fn pair_created_signature(&self) -> &str {
+        StableSwapFactory::PlainPoolDeployed::SIGNATURE
+    }
+
+    // the deploy event does not carry the pool address, pools are discovered from the factory
+    // pool list instead
+    fn log_to_address(&self, _log: &Log) -> Address {
+        Address::ZERO
+    }
+
+    fn get_pool_repr(&self) -> DynSolType {
+        DynSolType::Array(Box::new(DynSolType::Address))
+    }
+
+    fn pool_registry(&self) -> Option<PoolRegistryFns> {
+        Some(CURVE_POOL_REGISTRY)
+    }
+}
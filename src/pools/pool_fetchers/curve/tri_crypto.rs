@@ -4,7 +4,7 @@ use alloy::primitives::Log;
 use alloy::dyn_abi::DynSolType;
 
 use crate::pools::gen::TriCryptoFactory;
-use crate::pools::PoolFetcher;
+use crate::pools::{PoolFetcher, PoolRegistryFns, CURVE_POOL_REGISTRY};
 use crate::pools::PoolType;
 use crate::Chain;
 
@@ -15,10 +15,12 @@ impl PoolFetcher for CurveTriCryptoFetcher {
         PoolType::CurveTriCrypto
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("0c0e5f2fF0ff18a3be9b835635039256dC4B4963"),
-            Chain::Base => address!("A5961898870943c68037F6848d2D866Ed2016bcB"),
+            Chain::Ethereum => Some(address!("0c0e5f2fF0ff18a3be9b835635039256dC4B4963")),
+            Chain::Base => Some(address!("A5961898870943c68037F6848d2D866Ed2016bcB")),
+            Chain::Arbitrum => Some(address!("bC0797015fcFc47d9C1856639CaE50D0e69FbEE8")),
+            _ => None,
         }
     }
 
@@ -43,4 +45,7 @@ impl PoolFetcher for CurveTriCryptoFetcher {
         ])))
     }
 
+    fn pool_registry(&self) -> Option<PoolRegistryFns> {
+        Some(CURVE_POOL_REGISTRY)
+    }
 }
\ No newline at end of file
@@ -4,7 +4,7 @@ use alloy::primitives::Log;
 use alloy::dyn_abi::DynSolType;
 use crate::Chain;
 use crate::pools::gen::TwoCryptoFactory;
-use crate::pools::PoolFetcher;
+use crate::pools::{PoolFetcher, PoolRegistryFns, CURVE_POOL_REGISTRY};
 use crate::pools::PoolType;
 
 pub struct CurveTwoCryptoFetcher;
@@ -14,10 +14,13 @@ impl PoolFetcher for CurveTwoCryptoFetcher {
         PoolType::CurveTwoCrypto
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("98EE851a00abeE0d95D08cF4CA2BdCE32aeaAF7F"),
-            Chain::Base => address!("c9Fe0C63Af9A39402e8a5514f9c43Af0322b665F"),
+            Chain::Ethereum => Some(address!("98EE851a00abeE0d95D08cF4CA2BdCE32aeaAF7F")),
+            Chain::Base => Some(address!("c9Fe0C63Af9A39402e8a5514f9c43Af0322b665F")),
+            Chain::Arbitrum => Some(address!("98EE851a00abeE0d95D08cF4CA2BdCE32aeaAF7F")),
+            Chain::BSC => Some(address!("98EE851a00abeE0d95D08cF4CA2BdCE32aeaAF7F")),
+            _ => None,
         }
     }
 
@@ -39,4 +42,8 @@ impl PoolFetcher for CurveTwoCryptoFetcher {
             DynSolType::Uint(8),
         ])))
     }
+
+    fn pool_registry(&self) -> Option<PoolRegistryFns> {
+        Some(CURVE_POOL_REGISTRY)
+    }
 }
\ No newline at end of file
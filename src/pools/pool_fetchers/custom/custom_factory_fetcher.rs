@@ -0,0 +1,46 @@
+use alloy::primitives::Address;
+use alloy::primitives::Log;
+use alloy::dyn_abi::DynSolType;
+use std::sync::Arc;
+use crate::pools::{PoolFetcher, PoolRegistryFns};
+use crate::pools::PoolType;
+use crate::Chain;
+
+/// Wraps a protocol fetcher with a user supplied factory address. Used for custom chains
+/// where the crate does not know the deployment addresses
+pub struct CustomFactoryFetcher {
+    inner: Arc<dyn PoolFetcher>,
+    factory: Address,
+}
+
+impl CustomFactoryFetcher {
+    pub fn new(inner: Arc<dyn PoolFetcher>, factory: Address) -> Self {
+        Self { inner, factory }
+    }
+}
+
+impl PoolFetcher for CustomFactoryFetcher {
+    fn pool_type(&self) -> PoolType {
+        self.inner.pool_type()
+    }
+
+    fn factory_address(&self, _chain: Chain) -> Option<Address> {
+        Some(self.factory)
+    }
+
+    fn pair_created_signature(&self) -> &str {
+        self.inner.pair_created_signature()
+    }
+
+    fn log_to_address(&self, log: &Log) -> Address {
+        self.inner.log_to_address(log)
+    }
+
+    fn get_pool_repr(&self) -> DynSolType {
+        self.inner.get_pool_repr()
+    }
+
+    fn pool_registry(&self) -> Option<PoolRegistryFns> {
+        self.inner.pool_registry()
+    }
+}
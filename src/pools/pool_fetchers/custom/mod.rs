@@ -0,0 +1,3 @@
+pub use custom_factory_fetcher::CustomFactoryFetcher;
+
+mod custom_factory_fetcher;
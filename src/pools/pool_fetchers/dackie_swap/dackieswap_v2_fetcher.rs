@@ -14,10 +14,10 @@ impl PoolFetcher for DackieSwapV2Fetcher {
         PoolType::DackieSwapV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("591f122D1df761E616c13d265006fcbf4c6d6551"),
-            _ => panic!("DackieSwap not supported on this chain")
+            Chain::Base => Some(address!("591f122D1df761E616c13d265006fcbf4c6d6551")),
+            _ => None,
         }
     }
 
@@ -14,10 +14,10 @@ impl PoolFetcher for DackieSwapV3Fetcher {
         PoolType::DackieSwapV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("3D237AC6D2f425D2E890Cc99198818cc1FA48870"),
-            _ => panic!("DackieSwap not supported on this chain")
+            Chain::Base => Some(address!("3D237AC6D2f425D2E890Cc99198818cc1FA48870")),
+            _ => None,
         }
     }
 
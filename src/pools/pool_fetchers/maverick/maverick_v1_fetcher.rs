@@ -14,10 +14,11 @@ impl PoolFetcher for MaverickV1Fetcher {
         PoolType::MaverickV1
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("Eb6625D65a0553c9dBc64449e56abFe519bd9c9B"),
-            Chain::Base => address!("B2855783a346735e4AAe0c1eb894DEf861Fa9b45"),
+            Chain::Ethereum => Some(address!("Eb6625D65a0553c9dBc64449e56abFe519bd9c9B")),
+            Chain::Base => Some(address!("B2855783a346735e4AAe0c1eb894DEf861Fa9b45")),
+            _ => None,
         }
     }
 
@@ -31,10 +31,11 @@ impl PoolFetcher for MaverickV2Fetcher {
         PoolType::MaverickV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("0A7e848Aca42d879EF06507Fca0E7b33A0a63c1e"),
-            Chain::Base => address!("0A7e848Aca42d879EF06507Fca0E7b33A0a63c1e"),
+            Chain::Ethereum => Some(address!("0A7e848Aca42d879EF06507Fca0E7b33A0a63c1e")),
+            Chain::Base => Some(address!("0A7e848Aca42d879EF06507Fca0E7b33A0a63c1e")),
+            _ => None,
         }
     }
 
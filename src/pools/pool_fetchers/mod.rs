@@ -5,8 +5,11 @@ pub use alien_base::AlienBaseV3Fetcher;
 pub use balancer::BalancerV2Fetcher;
 pub use base_swap::BaseSwapV2Fetcher;
 pub use base_swap::BaseSwapV3Fetcher;
+pub use camelot::CamelotFetcher;
 pub use curve::CurveTwoCryptoFetcher;
 pub use curve::CurveTriCryptoFetcher;
+pub use curve::CurveStableSwapFetcher;
+pub use custom::CustomFactoryFetcher;
 pub use maverick::MaverickV1Fetcher;
 pub use maverick::MaverickV2Fetcher;
 pub use pancake_swap::PancakeSwapV2Fetcher;
@@ -15,10 +18,12 @@ pub use sushiswap::SushiSwapV2Fetcher;
 pub use sushiswap::SushiSwapV3Fetcher;
 pub use uniswap::UniswapV2Fetcher;
 pub use uniswap::UniswapV3Fetcher;
+pub use uniswap::UniswapV4Fetcher;
 pub use swap_based::SwapBasedV2Fetcher;
 pub use swap_based::SwapBasedV3Fetcher;
 pub use dackie_swap::DackieSwapV2Fetcher;
 pub use dackie_swap::DackieSwapV3Fetcher;
+pub use velodrome::VelodromeFetcher;
 
 
 mod aerodrome;
@@ -27,8 +32,11 @@ mod dackie_swap;
 mod swap_based;
 mod balancer;
 mod base_swap;
+mod camelot;
 mod curve;
+mod custom;
 mod maverick;
 mod pancake_swap;
 mod sushiswap;
-mod uniswap;
\ No newline at end of file
+mod uniswap;
+mod velodrome;
\ No newline at end of file
@@ -14,10 +14,12 @@ impl PoolFetcher for PancakeSwapV2Fetcher {
         PoolType::PancakeSwapV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("1097053Fd2ea711dad45caCcc45EfF7548fCB362"),            
-            Chain::Base => address!("02a84c1b3BBD7401a5f7fa98a384EBC70bB5749E"),
+            Chain::Ethereum => Some(address!("1097053Fd2ea711dad45caCcc45EfF7548fCB362")),
+            Chain::Base => Some(address!("02a84c1b3BBD7401a5f7fa98a384EBC70bB5749E")),
+            Chain::BSC => Some(address!("cA143Ce32Fe78f1f7019d7d551a6402fC5350c73")),
+            _ => None,
         }
     }
 
@@ -14,10 +14,13 @@ impl PoolFetcher for PancakeSwapV3Fetcher {
         PoolType::PancakeSwapV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865"),
-            Chain::Base => address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865"),
+            Chain::Ethereum => Some(address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865")),
+            Chain::Base => Some(address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865")),
+            Chain::Arbitrum => Some(address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865")),
+            Chain::BSC => Some(address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865")),
+            _ => None,
         }
     }
     
@@ -13,10 +13,13 @@ impl PoolFetcher for SushiSwapV2Fetcher {
         PoolType::SushiSwapV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("C0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac"),
-            Chain::Base => address!("71524B4f93c58fcbF659783284E38825f0622859"),
+            Chain::Ethereum => Some(address!("C0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac")),
+            Chain::Base => Some(address!("71524B4f93c58fcbF659783284E38825f0622859")),
+            Chain::Arbitrum => Some(address!("c35DADB65012eC5796536bD9864eD8773aBc74C4")),
+            Chain::BSC => Some(address!("c35DADB65012eC5796536bD9864eD8773aBc74C4")),
+            _ => None,
         }
     }
     
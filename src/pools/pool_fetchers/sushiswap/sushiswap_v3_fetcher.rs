@@ -14,10 +14,13 @@ impl PoolFetcher for SushiSwapV3Fetcher {
         PoolType::SushiSwapV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("bACEB8eC6b9355Dfc0269C18bac9d6E2Bdc29C4F"),
-            Chain::Base => address!("c35DADB65012eC5796536bD9864eD8773aBc74C4"),
+            Chain::Ethereum => Some(address!("bACEB8eC6b9355Dfc0269C18bac9d6E2Bdc29C4F")),
+            Chain::Base => Some(address!("c35DADB65012eC5796536bD9864eD8773aBc74C4")),
+            Chain::Arbitrum => Some(address!("1af415a1EbA07a4986a52B6f2e7dE7003D82231e")),
+            Chain::BSC => Some(address!("126555dd55a39328F69400d6aE4F782Bd4C34ABb")),
+            _ => None,
         }
     }
     
@@ -14,10 +14,10 @@ impl PoolFetcher for SwapBasedV2Fetcher {
         PoolType::SwapBasedV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("04C9f118d21e8B767D2e50C946f0cC9F6C367300"),
-            _ => panic!("SwapBased not supported on this chain")
+            Chain::Base => Some(address!("04C9f118d21e8B767D2e50C946f0cC9F6C367300")),
+            _ => None,
         }
     }
 
@@ -14,10 +14,10 @@ impl PoolFetcher for SwapBasedV3Fetcher {
         PoolType::SwapBasedV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Base => address!("b5620F90e803C7F957A9EF351B8DB3C746021BEa"),
-            _ => panic!("SwapBased not supported on this chain")
+            Chain::Base => Some(address!("b5620F90e803C7F957A9EF351B8DB3C746021BEa")),
+            _ => None,
         }
     }
 
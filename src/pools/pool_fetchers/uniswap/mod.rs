@@ -1,5 +1,7 @@
 pub use uniswap_v2_fetcher::UniswapV2Fetcher;
 pub use uniswap_v3_fetcher::UniswapV3Fetcher;
+pub use uniswap_v4_fetcher::UniswapV4Fetcher;
 
 mod uniswap_v2_fetcher;
 mod uniswap_v3_fetcher;
+mod uniswap_v4_fetcher;
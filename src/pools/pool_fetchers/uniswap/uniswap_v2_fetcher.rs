@@ -14,10 +14,11 @@ impl PoolFetcher for UniswapV2Fetcher {
         PoolType::UniswapV2
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f"),
-            Chain::Base => address!("8909Dc15e40173Ff4699343b6eB8132c65e18eC6"),
+            Chain::Ethereum => Some(address!("5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f")),
+            Chain::Base => Some(address!("8909Dc15e40173Ff4699343b6eB8132c65e18eC6")),
+            _ => None,
         }
     }
 
@@ -13,10 +13,14 @@ impl PoolFetcher for UniswapV3Fetcher {
         PoolType::UniswapV3
     }
 
-    fn factory_address(&self, chain: Chain) -> Address {
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
         match chain {
-            Chain::Ethereum => address!("1F98431c8aD98523631AE4a59f267346ea31F984"),
-            Chain::Base => address!("33128a8fC17869897dcE68Ed026d694621f6FDfD"),
+            Chain::Ethereum => Some(address!("1F98431c8aD98523631AE4a59f267346ea31F984")),
+            Chain::Base => Some(address!("33128a8fC17869897dcE68Ed026d694621f6FDfD")),
+            Chain::Arbitrum => Some(address!("1F98431c8aD98523631AE4a59f267346ea31F984")),
+            Chain::Optimism => Some(address!("1F98431c8aD98523631AE4a59f267346ea31F984")),
+            Chain::BSC => Some(address!("dB1d10011AD0Ff90774D0C6Bb92e5C5c8b4461F7")),
+            _ => None,
         }
     }
 
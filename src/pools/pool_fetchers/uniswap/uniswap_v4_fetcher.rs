@@ -0,0 +1,46 @@
+use alloy::primitives::{address, Address};
+use alloy::sol_types::SolEvent;
+use alloy::primitives::Log;
+use alloy::dyn_abi::DynSolType;
+use crate::events::UniswapV4Events;
+use crate::pools::PoolFetcher;
+use crate::pools::PoolType;
+use crate::Chain;
+
+/// Uniswap V4 pools are created inside the PoolManager singleton, so the PoolManager stands in
+/// for the factory and pools are discovered from its `Initialize` events
+pub struct UniswapV4Fetcher;
+
+impl PoolFetcher for UniswapV4Fetcher {
+    fn pool_type(&self) -> PoolType {
+        PoolType::UniswapV4
+    }
+
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
+        match chain {
+            Chain::Ethereum => Some(address!("000000000004444c5dc75cB358380D2e3dE08A90")),
+            _ => None,
+        }
+    }
+
+    fn pair_created_signature(&self) -> &str {
+        UniswapV4Events::Initialize::SIGNATURE
+    }
+
+    // the pool id is truncated to an address, the same way balancer pool ids are keyed
+    fn log_to_address(&self, log: &Log) -> Address {
+        let decoded_log = UniswapV4Events::Initialize::decode_log(log, false).unwrap();
+        Address::from_slice(&decoded_log.id[..20])
+    }
+
+    // pools are built from the `Initialize` data, there is no data sync contract
+    fn get_pool_repr(&self) -> DynSolType {
+        DynSolType::Array(Box::new(DynSolType::Tuple(vec![
+            DynSolType::Uint(24),
+            DynSolType::Int(24),
+            DynSolType::Address,
+            DynSolType::Uint(160),
+            DynSolType::Int(24),
+        ])))
+    }
+}
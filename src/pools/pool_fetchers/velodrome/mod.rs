@@ -0,0 +1,2 @@
+pub use velodrome_fetcher::VelodromeFetcher;
+mod velodrome_fetcher;
\ No newline at end of file
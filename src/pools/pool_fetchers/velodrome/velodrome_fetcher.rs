@@ -0,0 +1,49 @@
+use alloy::primitives::{address, Address};
+use alloy::sol_types::SolEvent;
+use alloy::primitives::Log;
+use alloy::dyn_abi::DynSolType;
+
+// Aerodrome is a fork of Velodrome V2, the factories share the same interface
+use crate::pools::gen::AerodromeV2Factory;
+use crate::pools::PoolFetcher;
+use crate::pools::PoolType;
+use crate::Chain;
+
+pub struct VelodromeFetcher;
+
+
+impl PoolFetcher for VelodromeFetcher {
+    fn pool_type(&self) -> PoolType {
+        PoolType::Velodrome
+    }
+
+    fn factory_address(&self, chain: Chain) -> Option<Address> {
+        match chain {
+            Chain::Optimism => Some(address!("F1046053aa5682b4F9a81b5481394DA16BE5FF5a")),
+            _ => None,
+        }
+    }
+
+    fn pair_created_signature(&self) -> &str {
+        AerodromeV2Factory::PoolCreated::SIGNATURE
+    }
+
+    fn log_to_address(&self, log: &Log) -> Address {
+        let decoded_log = AerodromeV2Factory::PoolCreated::decode_log(log, false).unwrap();
+        decoded_log.data.pool
+    }
+
+
+    fn get_pool_repr(&self) -> DynSolType {
+        DynSolType::Array(Box::new(DynSolType::Tuple(vec![
+            DynSolType::Address,
+            DynSolType::Address,
+            DynSolType::Address,
+            DynSolType::Uint(8),
+            DynSolType::Uint(8),
+            DynSolType::Uint(256),
+            DynSolType::Uint(256),
+        ])))
+    }
+
+}
\ No newline at end of file
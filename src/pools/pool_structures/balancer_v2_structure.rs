@@ -5,7 +5,20 @@ use alloy::sol_types::SolEvent;
 use alloy::{dyn_abi::DynSolValue, primitives::Address};
 use serde::{Deserialize, Serialize};
 
+use crate::errors::PoolSyncError;
 use crate::pools::gen::Vault;
+use crate::pools::pool_structures::log_exp_math;
+use crate::pools::pool_structures::{address_array_at, address_at, uint_array_at, uint_at};
+
+/// Fixed point one used by the Balancer math
+const ONE: u128 = 1_000_000_000_000_000_000;
+/// Largest fraction of the input balance a single swap may add
+const MAX_IN_RATIO: u128 = 300_000_000_000_000_000;
+/// Largest fraction of the output balance a single swap may take
+const MAX_OUT_RATIO: u128 = 300_000_000_000_000_000;
+/// Relative error bound of `log_exp_math::pow`, added to powers rounded up
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BalancerV2Pool {
     pub address: Address,
@@ -22,9 +35,33 @@ pub struct BalancerV2Pool {
     pub balances: Vec<U256>,
     pub weights: Vec<U256>,
     pub swap_fee: U256,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
 }
 
 impl BalancerV2Pool {
+    /// Copy of the balances and weights for swap simulation, token names are not copied
+    pub fn snapshot_for_sim(&self) -> Self {
+        Self {
+            address: self.address,
+            pool_id: self.pool_id,
+            token0: self.token0,
+            token1: self.token1,
+            token0_name: String::new(),
+            token1_name: String::new(),
+            token0_decimals: self.token0_decimals,
+            token1_decimals: self.token1_decimals,
+            additional_tokens: self.additional_tokens.clone(),
+            additional_token_names: Vec::new(),
+            additional_token_decimals: self.additional_token_decimals.clone(),
+            balances: self.balances.clone(),
+            weights: self.weights.clone(),
+            swap_fee: self.swap_fee,
+            decimals_verified: self.decimals_verified,
+        }
+    }
+
     pub fn get_tokens(&self) -> Vec<Address> {
         let mut tokens = vec![self.token0, self.token1];
         tokens.extend(self.additional_tokens.iter());
@@ -44,6 +81,157 @@ impl BalancerV2Pool {
         }
     }
 
+    pub fn get_decimals(&self, index: usize) -> Option<u8> {
+        match index {
+            0 => Some(self.token0_decimals),
+            1 => Some(self.token1_decimals),
+            _ => self.additional_token_decimals.get(index - 2).copied(),
+        }
+    }
+
+    /// Spot price of `base` denominated in the other token of a two token pool, using the
+    /// weighted pool formula (B_quote / W_quote) / (B_base / W_base)
+    pub fn price(&self, base: Address) -> Option<f64> {
+        let quote = if base == self.token0 {
+            self.token1
+        } else if base == self.token1 {
+            self.token0
+        } else {
+            return None;
+        };
+        self.spot_price(quote, base)
+    }
+
+    /// Amount of `token_in` paid for one `token_out` before fees, adjusted for decimals. Uses
+    /// the weighted pool formula (B_in / W_in) / (B_out / W_out) and works for any pair of
+    /// tokens in the pool. None if either token is not in the pool or has no balance
+    pub fn spot_price(&self, token_in: Address, token_out: Address) -> Option<f64> {
+        let in_idx = self.get_token_index(&token_in)?;
+        let out_idx = self.get_token_index(&token_out)?;
+        Some(self.weighted_balance(in_idx)? / self.weighted_balance(out_idx)?)
+    }
+
+    // Balance of the token at `index` in whole tokens divided by its weight
+    fn weighted_balance(&self, index: usize) -> Option<f64> {
+        let balance = f64::from(*self.balances.get(index)?);
+        let decimals = self.get_decimals(index)? as i32;
+        let weight = f64::from(*self.weights.get(index)?);
+        if balance == 0.0 || weight == 0.0 {
+            return None;
+        }
+        Some(balance / 10f64.powi(decimals) / weight)
+    }
+
+    /// Swap fee as a fraction of the input amount, the fee is stored with 18 decimals
+    pub fn fee_fraction(&self) -> f64 {
+        f64::from(self.swap_fee) / 1e18
+    }
+
+    /// Output amount of an exact input swap from `token_in` to `token_out` using the weighted
+    /// math Bo * (1 - (Bi / (Bi + Ai)) ^ (Wi / Wo)), with the fee taken from the input first.
+    /// Amounts are scaled to 18 decimals and rounded like the pool, so the result matches the
+    /// vault. Works for any pair of tokens in the pool. Returns zero for unknown tokens or when
+    /// the input is above the vault's 30% max in ratio
+    pub fn get_amount_out_multi(
+        &self,
+        amount_in: U256,
+        token_in: Address,
+        token_out: Address,
+    ) -> U256 {
+        self.weighted_amount_out(amount_in, token_in, token_out).unwrap_or_default()
+    }
+
+    fn weighted_amount_out(
+        &self,
+        amount_in: U256,
+        token_in: Address,
+        token_out: Address,
+    ) -> Option<U256> {
+        let (balance_in, balance_out, weight_in, weight_out, scale_in, scale_out) =
+            self.swap_state(token_in, token_out)?;
+
+        // the fee is taken before scaling and rounded up in favor of the pool
+        let amount_in = amount_in.checked_sub(mul_up(amount_in, self.swap_fee)?)?;
+        let amount_in = amount_in.checked_mul(scale_in)?;
+        if amount_in > mul_down(balance_in, U256::from(MAX_IN_RATIO))? {
+            return None;
+        }
+
+        let base = div_up(balance_in, balance_in.checked_add(amount_in)?)?;
+        let exponent = div_down(weight_in, weight_out)?;
+        let power = pow_up(base, exponent)?;
+        let amount_out = mul_down(balance_out, complement(power))?;
+        Some(amount_out / scale_out)
+    }
+
+    /// Input amount of an exact output swap from `token_in` to `token_out` using the weighted
+    /// math Bi * ((Bo / (Bo - Ao)) ^ (Wo / Wi) - 1), grossed up for the fee. Rounded like the
+    /// pool, see `get_amount_out_multi`. Returns zero for unknown tokens or when the output is
+    /// above the vault's 30% max out ratio
+    pub fn get_amount_in_multi(
+        &self,
+        amount_out: U256,
+        token_in: Address,
+        token_out: Address,
+    ) -> U256 {
+        self.weighted_amount_in(amount_out, token_in, token_out).unwrap_or_default()
+    }
+
+    fn weighted_amount_in(
+        &self,
+        amount_out: U256,
+        token_in: Address,
+        token_out: Address,
+    ) -> Option<U256> {
+        let (balance_in, balance_out, weight_in, weight_out, scale_in, scale_out) =
+            self.swap_state(token_in, token_out)?;
+        let amount_out = amount_out.checked_mul(scale_out)?;
+        if amount_out > mul_down(balance_out, U256::from(MAX_OUT_RATIO))? {
+            return None;
+        }
+
+        let base = div_up(balance_out, balance_out.checked_sub(amount_out)?)?;
+        let exponent = div_up(weight_out, weight_in)?;
+        let power = pow_up(base, exponent)?;
+        let amount_in = mul_up(balance_in, power.checked_sub(U256::from(ONE))?)?;
+
+        // the input is scaled back rounding up, then grossed up for the fee
+        let amount_in = amount_in.div_ceil(scale_in);
+        let fee_complement = complement(self.swap_fee);
+        if fee_complement.is_zero() {
+            return None;
+        }
+        div_up(amount_in, fee_complement)
+    }
+
+    // Balances scaled to 18 decimals, weights and decimal scaling factors of the two tokens of
+    // a swap. None for unknown or equal tokens, empty balances or weights and more than 18
+    // decimals
+    fn swap_state(
+        &self,
+        token_in: Address,
+        token_out: Address,
+    ) -> Option<(U256, U256, U256, U256, U256, U256)> {
+        let in_idx = self.get_token_index(&token_in)?;
+        let out_idx = self.get_token_index(&token_out)?;
+        if in_idx == out_idx {
+            return None;
+        }
+        let scale = |index: usize| {
+            let decimals = self.get_decimals(index)?;
+            (decimals <= 18).then(|| U256::from(10).pow(U256::from(18 - decimals)))
+        };
+        let (scale_in, scale_out) = (scale(in_idx)?, scale(out_idx)?);
+        let balance_in = self.balances.get(in_idx)?.checked_mul(scale_in)?;
+        let balance_out = self.balances.get(out_idx)?.checked_mul(scale_out)?;
+        let (weight_in, weight_out) = (*self.weights.get(in_idx)?, *self.weights.get(out_idx)?);
+        let empty = [balance_in, balance_out, weight_in, weight_out].iter().any(U256::is_zero);
+        if empty {
+            return None;
+        }
+        Some((balance_in, balance_out, weight_in, weight_out, scale_in, scale_out))
+    }
+
     pub fn get_balance(&self, token: &Address) -> U256 {
         let index = self.get_token_index(token);
         if let Some(index) = index {
@@ -54,6 +242,48 @@ impl BalancerV2Pool {
     }
 }
 
+// 18 decimal fixed point helpers of the Balancer FixedPoint library, None on overflow
+fn mul_down(a: U256, b: U256) -> Option<U256> {
+    Some(a.checked_mul(b)? / U256::from(ONE))
+}
+
+fn mul_up(a: U256, b: U256) -> Option<U256> {
+    Some(a.checked_mul(b)?.div_ceil(U256::from(ONE)))
+}
+
+fn div_down(a: U256, b: U256) -> Option<U256> {
+    a.checked_mul(U256::from(ONE))?.checked_div(b)
+}
+
+fn div_up(a: U256, b: U256) -> Option<U256> {
+    if b.is_zero() {
+        return None;
+    }
+    Some(a.checked_mul(U256::from(ONE))?.div_ceil(b))
+}
+
+fn complement(x: U256) -> U256 {
+    U256::from(ONE).saturating_sub(x)
+}
+
+// x ^ y rounded up. Exponents of one, two and four are exact, other powers add the error bound
+// of the logarithm based power
+fn pow_up(x: U256, y: U256) -> Option<U256> {
+    let one = U256::from(ONE);
+    if y == one {
+        Some(x)
+    } else if y == one * U256::from(2) {
+        mul_up(x, x)
+    } else if y == one * U256::from(4) {
+        let square = mul_up(x, x)?;
+        mul_up(square, square)
+    } else {
+        let raw = log_exp_math::pow(x, y)?;
+        let max_error = mul_up(raw, U256::from(MAX_POW_RELATIVE_ERROR))? + U256::from(1);
+        raw.checked_add(max_error)
+    }
+}
+
 pub fn process_balance_data(pool: &mut BalancerV2Pool, log: Log) {
     let event = Vault::Swap::decode_log(log.as_ref(), true).unwrap();
 
@@ -66,41 +296,28 @@ pub fn process_balance_data(pool: &mut BalancerV2Pool, log: Log) {
         pool.balances[log_token_out_idx].saturating_sub(event.amountOut);
 }
 
-impl From<&[DynSolValue]> for BalancerV2Pool {
-    fn from(data: &[DynSolValue]) -> Self {
-        let pool_address = data[0].as_address().unwrap();
-        let pool_id = FixedBytes::from_slice(data[1].as_fixed_bytes().unwrap().0);
-        let token0 = data[2].as_address().unwrap();
-        let token1 = data[3].as_address().unwrap();
-        let token0_decimals = data[4].as_uint().unwrap().0.to::<u8>();
-        let token1_decimals = data[5].as_uint().unwrap().0.to::<u8>();
-        let additional_tokens: Vec<Address> = data[6]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|v| v.as_address().unwrap())
-            .collect();
-        let additional_token_decimals: Vec<u8> = data[7]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|v| v.as_uint().unwrap().0.to::<u8>())
-            .collect();
-        let balances: Vec<U256> = data[8]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|v| v.as_uint().unwrap().0)
-            .collect();
-        let weights: Vec<U256> = data[9]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|v| v.as_uint().unwrap().0)
-            .collect();
-        let swap_fee = data[10].as_uint().unwrap().0;
+impl TryFrom<&[DynSolValue]> for BalancerV2Pool {
+    type Error = PoolSyncError;
 
-        Self {
+    fn try_from(data: &[DynSolValue]) -> Result<Self, Self::Error> {
+        let pool_address = address_at(data, 0)?;
+        let pool_id = data
+            .get(1)
+            .and_then(|value| value.as_fixed_bytes())
+            .filter(|(bytes, size)| *size == 32 && bytes.len() == 32)
+            .map(|(bytes, _)| FixedBytes::from_slice(bytes))
+            .ok_or_else(|| PoolSyncError::DecodeError("field 1 is not a pool id".to_string()))?;
+        let token0 = address_at(data, 2)?;
+        let token1 = address_at(data, 3)?;
+        let token0_decimals = uint_at(data, 4)?;
+        let token1_decimals = uint_at(data, 5)?;
+        let additional_tokens: Vec<Address> = address_array_at(data, 6)?;
+        let additional_token_decimals: Vec<u8> = uint_array_at(data, 7)?;
+        let balances: Vec<U256> = uint_array_at(data, 8)?;
+        let weights: Vec<U256> = uint_array_at(data, 9)?;
+        let swap_fee = uint_at(data, 10)?;
+
+        Ok(Self {
             address: pool_address,
             pool_id,
             token0,
@@ -115,7 +332,8 @@ impl From<&[DynSolValue]> for BalancerV2Pool {
             balances,
             weights,
             swap_fee,
-        }
+            decimals_verified: None,
+        })
     }
 }
 
@@ -0,0 +1,191 @@
+//! Port of the LogExpMath library of the Balancer v2 vault. Powers of 18 decimal fixed point
+//! numbers are computed as exp(ln(x) * y) with the same series, constants and rounding as the
+//! pools, so weighted pool quotes match the chain.
+
+use alloy::primitives::{I256, U256};
+
+const ONE_18: i128 = 1_000_000_000_000_000_000;
+const ONE_20: i128 = 100_000_000_000_000_000_000;
+
+const MAX_NATURAL_EXPONENT: i128 = 130 * ONE_18;
+const MIN_NATURAL_EXPONENT: i128 = -41 * ONE_18;
+const LN_36_LOWER_BOUND: i128 = ONE_18 - 100_000_000_000_000_000;
+const LN_36_UPPER_BOUND: i128 = ONE_18 + 100_000_000_000_000_000;
+
+// 18 decimal constants, x0 = 2^7 and x1 = 2^6 with their exponentials a0 and a1 without
+// decimals. a0 does not fit an i128 and is built in `a0`
+const X0: i128 = 128_000_000_000_000_000_000;
+const A0: &str = "38877084059945950922200000000000000000000000000000000000";
+const X1: i128 = 64_000_000_000_000_000_000;
+const A1: i128 = 6_235_149_080_811_616_882_910_000_000;
+
+// 20 decimal constants, xn = 2^(7 - n) and an = e^xn
+const X2: i128 = 3_200_000_000_000_000_000_000;
+const A2: i128 = 7_896_296_018_268_069_516_100_000_000_000_000;
+const X3: i128 = 1_600_000_000_000_000_000_000;
+const A3: i128 = 888_611_052_050_787_263_676_000_000;
+const X4: i128 = 800_000_000_000_000_000_000;
+const A4: i128 = 298_095_798_704_172_827_474_000;
+const X5: i128 = 400_000_000_000_000_000_000;
+const A5: i128 = 5_459_815_003_314_423_907_810;
+const X6: i128 = 200_000_000_000_000_000_000;
+const A6: i128 = 738_905_609_893_065_022_723;
+const X7: i128 = 100_000_000_000_000_000_000;
+const A7: i128 = 271_828_182_845_904_523_536;
+const X8: i128 = 50_000_000_000_000_000_000;
+const A8: i128 = 164_872_127_070_012_814_685;
+const X9: i128 = 25_000_000_000_000_000_000;
+const A9: i128 = 128_402_541_668_774_148_407;
+const X10: i128 = 12_500_000_000_000_000_000;
+const A10: i128 = 113_314_845_306_682_631_683;
+const X11: i128 = 6_250_000_000_000_000_000;
+const A11: i128 = 106_449_445_891_785_942_956;
+
+fn int(value: i128) -> I256 {
+    I256::try_from(value).unwrap()
+}
+
+fn a0() -> I256 {
+    I256::from_dec_str(A0).unwrap()
+}
+
+/// x ^ y for 18 decimal fixed point x and y. None where the vault reverts, for an x or y out of
+/// bounds or a result out of the range of `exp`
+pub(crate) fn pow(x: U256, y: U256) -> Option<U256> {
+    if y.is_zero() {
+        return Some(U256::from(ONE_18));
+    }
+    if x.is_zero() {
+        return Some(U256::ZERO);
+    }
+    // x must fit an int256 and y * 1e20 must not overflow one
+    if x.bit(255) || y >= (U256::from(1) << 254) / U256::from(ONE_20) {
+        return None;
+    }
+    let x = I256::from_raw(x);
+    let y = I256::from_raw(y);
+    let one_18 = int(ONE_18);
+
+    let mut logx_times_y = if int(LN_36_LOWER_BOUND) < x && x < int(LN_36_UPPER_BOUND) {
+        // close to one the 36 decimal logarithm keeps the precision
+        let ln_36_x = ln_36(x);
+        (ln_36_x / one_18) * y + ((ln_36_x % one_18) * y) / one_18
+    } else {
+        ln(x) * y
+    };
+    logx_times_y /= one_18;
+
+    if logx_times_y < int(MIN_NATURAL_EXPONENT) || logx_times_y > int(MAX_NATURAL_EXPONENT) {
+        return None;
+    }
+    Some(exp(logx_times_y).into_raw())
+}
+
+/// e ^ x for an 18 decimal x within the natural exponent bounds
+fn exp(mut x: I256) -> I256 {
+    let (one_18, one_20) = (int(ONE_18), int(ONE_20));
+    if x.is_negative() {
+        return (one_18 * one_18) / exp(-x);
+    }
+
+    // the largest powers of two are taken out without decimals so the series stays in range
+    let first_an = if x >= int(X0) {
+        x -= int(X0);
+        a0()
+    } else if x >= int(X1) {
+        x -= int(X1);
+        int(A1)
+    } else {
+        int(1)
+    };
+
+    // the rest is computed with 20 decimals
+    x *= int(100);
+    let mut product = one_20;
+    for (xn, an) in [(X2, A2), (X3, A3), (X4, A4), (X5, A5), (X6, A6), (X7, A7), (X8, A8), (X9, A9)]
+    {
+        if x >= int(xn) {
+            x -= int(xn);
+            product = (product * int(an)) / one_20;
+        }
+    }
+
+    // taylor series of the remaining x, which is below 2^-3
+    let mut series_sum = one_20;
+    let mut term = x;
+    series_sum += term;
+    for n in 2..=12 {
+        term = ((term * x) / one_20) / int(n);
+        series_sum += term;
+    }
+
+    (((product * series_sum) / one_20) * first_an) / int(100)
+}
+
+/// Natural logarithm of an 18 decimal a
+fn ln(mut a: I256) -> I256 {
+    let (one_18, one_20) = (int(ONE_18), int(ONE_20));
+    if a < one_18 {
+        return -ln((one_18 * one_18) / a);
+    }
+
+    let mut sum = I256::ZERO;
+    if a >= a0() * one_18 {
+        a /= a0();
+        sum += int(X0);
+    }
+    if a >= int(A1) * one_18 {
+        a /= int(A1);
+        sum += int(X1);
+    }
+
+    // the rest is computed with 20 decimals
+    sum *= int(100);
+    a *= int(100);
+    for (xn, an) in [
+        (X2, A2),
+        (X3, A3),
+        (X4, A4),
+        (X5, A5),
+        (X6, A6),
+        (X7, A7),
+        (X8, A8),
+        (X9, A9),
+        (X10, A10),
+        (X11, A11),
+    ] {
+        if a >= int(an) {
+            a = (a * one_20) / int(an);
+            sum += int(xn);
+        }
+    }
+
+    // ln(a) = 2 * atanh(z) with z = (a - 1) / (a + 1), a is now close to one
+    let z = ((a - one_20) * one_20) / (a + one_20);
+    let z_squared = (z * z) / one_20;
+    let mut num = z;
+    let mut series_sum = num;
+    for n in [3, 5, 7, 9, 11] {
+        num = (num * z_squared) / one_20;
+        series_sum += num / int(n);
+    }
+    series_sum *= int(2);
+
+    (sum + series_sum) / int(100)
+}
+
+/// Natural logarithm of an 18 decimal x close to one, with 36 decimals
+fn ln_36(x: I256) -> I256 {
+    let one_36 = int(ONE_18) * int(ONE_18);
+    let x = x * int(ONE_18);
+
+    let z = ((x - one_36) * one_36) / (x + one_36);
+    let z_squared = (z * z) / one_36;
+    let mut num = z;
+    let mut series_sum = num;
+    for n in [3, 5, 7, 9, 11, 13, 15] {
+        num = (num * z_squared) / one_36;
+        series_sum += num / int(n);
+    }
+    series_sum * int(2)
+}
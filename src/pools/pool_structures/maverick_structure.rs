@@ -1,5 +1,13 @@
+use alloy::primitives::U256;
+use alloy::rpc::types::Log;
+use alloy::sol_types::SolEvent;
 use alloy::{dyn_abi::DynSolValue, primitives::Address};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::errors::PoolSyncError;
+use crate::events::{MaverickV1Events, MaverickV2Events};
+use crate::pools::pool_structures::{address_at, uint_at};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MaverickPool {
@@ -10,17 +18,179 @@ pub struct MaverickPool {
     pub token1_name: String,
     pub token0_decimals: u8,
     pub token1_decimals: u8,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
+    /// Bins holding liquidity keyed by bin id
+    #[serde(default)]
+    pub bins: HashMap<u128, Bin>,
+    /// Active tick after the last swap
+    #[serde(default)]
+    pub active_tick: i32,
+}
+
+/// Liquidity held by a single maverick bin
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Bin {
+    pub reserve_a: U256,
+    pub reserve_b: U256,
+    pub lower_tick: i32,
+    /// Movement mode of the bin: static, right, left or both
+    pub kind: u8,
 }
 
-impl From<&[DynSolValue]> for MaverickPool {
-    fn from(data: &[DynSolValue]) -> Self {
+impl MaverickPool {
+    /// Copy of the pool for swap simulation without the token names
+    pub fn snapshot_for_sim(&self) -> Self {
         Self {
-            address: data[0].as_address().unwrap(),
-            token0: data[1].as_address().unwrap(),
-            token1: data[2].as_address().unwrap(),
-            token0_decimals: data[3].as_uint().unwrap().0.to::<u8>(),
-            token1_decimals: data[4].as_uint().unwrap().0.to::<u8>(),
+            address: self.address,
+            token0: self.token0,
+            token1: self.token1,
+            token0_decimals: self.token0_decimals,
+            token1_decimals: self.token1_decimals,
+            bins: self.bins.clone(),
+            active_tick: self.active_tick,
             ..Default::default()
         }
     }
 }
+
+/// Apply a maverick liquidity or swap log to the pool. V1 liquidity logs carry the change of
+/// every touched bin, bins left without reserves are removed, and swaps are applied with
+/// `apply_bin_swap`. V2 logs only carry the bins that liquidity was added to, their reserves are
+/// read from the pool once the logs are applied
+pub fn process_bin_data(pool: &mut MaverickPool, log: Log) {
+    let Some(event_sig) = log.topic0() else {
+        return;
+    };
+
+    if *event_sig == MaverickV1Events::AddLiquidity::SIGNATURE_HASH {
+        let event = MaverickV1Events::AddLiquidity::decode_log(log.as_ref(), true).unwrap();
+        for delta in &event.binDeltas {
+            let bin = pool.bins.entry(delta.binId).or_default();
+            bin.reserve_a += U256::from(delta.deltaA);
+            bin.reserve_b += U256::from(delta.deltaB);
+            bin.lower_tick = delta.lowerTick;
+            bin.kind = delta.kind;
+        }
+    } else if *event_sig == MaverickV1Events::RemoveLiquidity::SIGNATURE_HASH {
+        let event = MaverickV1Events::RemoveLiquidity::decode_log(log.as_ref(), true).unwrap();
+        for delta in &event.binDeltas {
+            let Some(bin) = pool.bins.get_mut(&delta.binId) else {
+                continue;
+            };
+            bin.reserve_a = bin.reserve_a.saturating_sub(U256::from(delta.deltaA));
+            bin.reserve_b = bin.reserve_b.saturating_sub(U256::from(delta.deltaB));
+            if bin.reserve_a.is_zero() && bin.reserve_b.is_zero() {
+                pool.bins.remove(&delta.binId);
+            }
+        }
+    } else if *event_sig == MaverickV1Events::Swap::SIGNATURE_HASH {
+        let event = MaverickV1Events::Swap::decode_log(log.as_ref(), true).unwrap();
+        apply_bin_swap(
+            pool,
+            event.tokenAIn,
+            event.amountIn,
+            event.amountOut,
+            event.activeTick,
+        );
+    } else if *event_sig == MaverickV2Events::PoolAddLiquidity::SIGNATURE_HASH {
+        let event = MaverickV2Events::PoolAddLiquidity::decode_log(log.as_ref(), true).unwrap();
+        for (bin_id, tick) in event.binIds.iter().zip(&event.params.ticks) {
+            let bin = pool.bins.entry(u128::from(*bin_id)).or_default();
+            bin.lower_tick = *tick;
+            bin.kind = event.params.kind;
+        }
+    }
+}
+
+/// Move a v1 swap through the bins. The price moves from the old active tick to `active_tick`,
+/// so the output is taken from the bins holding the output token on the way there, from the old
+/// active tick towards the new one, and the input is credited to those bins in proportion to
+/// what they paid out. The pool totals move by exactly the swapped amounts, the split across the
+/// bins crossed in one swap and the movement of non static bins are approximated
+pub fn apply_bin_swap(
+    pool: &mut MaverickPool,
+    token_a_in: bool,
+    amount_in: U256,
+    amount_out: U256,
+    active_tick: i32,
+) {
+    // bins past the old active tick hold none of the output token, so every bin between the
+    // new active tick and the far end that still holds it was crossed by the swap
+    let mut path: Vec<(i32, u128)> = pool
+        .bins
+        .iter()
+        .filter(|(_, bin)| {
+            if token_a_in {
+                bin.lower_tick >= active_tick && !bin.reserve_b.is_zero()
+            } else {
+                bin.lower_tick <= active_tick && !bin.reserve_a.is_zero()
+            }
+        })
+        .map(|(id, bin)| (bin.lower_tick, *id))
+        .collect();
+    path.sort_unstable();
+    if token_a_in {
+        // token a in pushes the price down
+        path.reverse();
+    }
+
+    let mut remaining = amount_out;
+    let mut paid = Vec::new();
+    for (_, id) in path {
+        if remaining.is_zero() {
+            break;
+        }
+        let bin = pool.bins.get_mut(&id).unwrap();
+        let reserve_out = if token_a_in { &mut bin.reserve_b } else { &mut bin.reserve_a };
+        let taken = remaining.min(*reserve_out);
+        *reserve_out -= taken;
+        remaining -= taken;
+        paid.push((id, taken));
+    }
+
+    // a swap filled by no known bin credits the bins at the new active tick
+    if paid.is_empty() {
+        paid = pool
+            .bins
+            .iter()
+            .filter(|(_, bin)| bin.lower_tick == active_tick)
+            .map(|(id, _)| (*id, U256::from(1)))
+            .collect();
+        paid.sort_unstable();
+    }
+    let total_paid = paid.iter().fold(U256::ZERO, |total, (_, taken)| total + taken);
+    let mut credited = U256::ZERO;
+    for (i, (id, taken)) in paid.iter().enumerate() {
+        // the last bin takes the rounding remainder so the totals stay exact
+        let share = if i + 1 == paid.len() {
+            amount_in - credited
+        } else {
+            amount_in * taken / total_paid
+        };
+        credited += share;
+        let bin = pool.bins.get_mut(id).unwrap();
+        if token_a_in {
+            bin.reserve_a += share;
+        } else {
+            bin.reserve_b += share;
+        }
+    }
+    pool.active_tick = active_tick;
+}
+
+impl TryFrom<&[DynSolValue]> for MaverickPool {
+    type Error = PoolSyncError;
+
+    fn try_from(data: &[DynSolValue]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            address: address_at(data, 0)?,
+            token0: address_at(data, 1)?,
+            token1: address_at(data, 2)?,
+            token0_decimals: uint_at(data, 3)?,
+            token1_decimals: uint_at(data, 4)?,
+            ..Default::default()
+        })
+    }
+}
@@ -1,7 +1,68 @@
+use alloy::dyn_abi::DynSolValue;
+use alloy::primitives::{Address, I256, U256};
+
+use crate::errors::PoolSyncError;
+
 pub mod balancer_v2_structure;
+mod log_exp_math;
 pub mod maverick_structure;
+pub mod stable_swap_curve_structure;
 pub mod tri_crypto_curve_structure;
 pub mod two_crypto_curve_structure;
 pub mod v2_structure;
 pub mod v3_structure;
 
+pub mod v4_structure;
+
+// Field `index` of the pool data returned by a data sync contract
+fn field(data: &[DynSolValue], index: usize) -> Result<&DynSolValue, PoolSyncError> {
+    data.get(index)
+        .ok_or_else(|| PoolSyncError::DecodeError(format!("missing field {}", index)))
+}
+
+fn mismatch(index: usize, expected: &str) -> PoolSyncError {
+    PoolSyncError::DecodeError(format!("field {} is not {}", index, expected))
+}
+
+/// Address at `index` of the pool data
+pub(crate) fn address_at(data: &[DynSolValue], index: usize) -> Result<Address, PoolSyncError> {
+    field(data, index)?.as_address().ok_or_else(|| mismatch(index, "an address"))
+}
+
+/// Unsigned integer at `index` of the pool data, converted to `T`. Values that do not fit are
+/// an error instead of being truncated
+pub(crate) fn uint_at<T: TryFrom<U256>>(
+    data: &[DynSolValue],
+    index: usize,
+) -> Result<T, PoolSyncError> {
+    let (value, _) = field(data, index)?.as_uint().ok_or_else(|| mismatch(index, "a uint"))?;
+    T::try_from(value).map_err(|_| mismatch(index, "in range"))
+}
+
+/// Signed integer at `index` of the pool data
+pub(crate) fn int_at(data: &[DynSolValue], index: usize) -> Result<I256, PoolSyncError> {
+    let (value, _) = field(data, index)?.as_int().ok_or_else(|| mismatch(index, "an int"))?;
+    Ok(value)
+}
+
+/// Addresses in the array at `index` of the pool data
+pub(crate) fn address_array_at(
+    data: &[DynSolValue],
+    index: usize,
+) -> Result<Vec<Address>, PoolSyncError> {
+    let values = array_at(data, index)?;
+    (0..values.len()).map(|i| address_at(values, i)).collect()
+}
+
+/// Unsigned integers in the array at `index` of the pool data, converted to `T`
+pub(crate) fn uint_array_at<T: TryFrom<U256>>(
+    data: &[DynSolValue],
+    index: usize,
+) -> Result<Vec<T>, PoolSyncError> {
+    let values = array_at(data, index)?;
+    (0..values.len()).map(|i| uint_at(values, i)).collect()
+}
+
+fn array_at(data: &[DynSolValue], index: usize) -> Result<&[DynSolValue], PoolSyncError> {
+    field(data, index)?.as_array().ok_or_else(|| mismatch(index, "an array"))
+}
@@ -0,0 +1,196 @@
+use alloy::primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+
+/// Fixed point one the balances are scaled to before the invariant math
+const PRECISION: u128 = 1_000_000_000_000_000_000;
+/// Scale of the stored A parameter, `A_precise()` is A * 100
+const A_PRECISION: u64 = 100;
+/// Fees are stored with 10 decimals
+const FEE_DENOMINATOR: u64 = 10_000_000_000;
+/// Plain pools hold between two and four coins
+const MAX_COINS: usize = 4;
+
+/// A Curve StableSwap plain pool. Coins past the first two are kept in the `additional_`
+/// fields, `balances` and `rates` hold an entry for every coin in pool order
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CurveStableSwapPool {
+    pub address: Address,
+    pub token0: Address,
+    pub token1: Address,
+    pub token0_name: String,
+    pub token1_name: String,
+    pub token0_decimals: u8,
+    pub token1_decimals: u8,
+    pub additional_tokens: Vec<Address>,
+    pub additional_token_names: Vec<String>,
+    pub additional_token_decimals: Vec<u8>,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
+    /// Raw balance of every coin
+    pub balances: Vec<U256>,
+    /// Rate of every coin with 18 decimals, scales a raw balance to 18 decimals. Plain coins use
+    /// 10^(36 - decimals), oracle and rebasing coins carry their current rate
+    pub rates: Vec<U256>,
+    /// Amplification as returned by `A_precise()`, A * 100
+    pub a: U256,
+    /// Swap fee with 10 decimals
+    pub fee: U256,
+}
+
+impl CurveStableSwapPool {
+    /// Build the pool from the coins and state read from the chain, None unless there are two
+    /// to four coins and every list has an entry per coin
+    pub fn from_state(
+        address: Address,
+        coins: &[Address],
+        decimals: &[u8],
+        balances: Vec<U256>,
+        rates: Vec<U256>,
+        a: U256,
+        fee: U256,
+    ) -> Option<Self> {
+        let n = coins.len();
+        if !(2..=MAX_COINS).contains(&n)
+            || decimals.len() != n
+            || balances.len() != n
+            || rates.len() != n
+        {
+            return None;
+        }
+        Some(Self {
+            address,
+            token0: coins[0],
+            token1: coins[1],
+            token0_decimals: decimals[0],
+            token1_decimals: decimals[1],
+            additional_tokens: coins[2..].to_vec(),
+            additional_token_decimals: decimals[2..].to_vec(),
+            balances,
+            rates,
+            a,
+            fee,
+            ..Default::default()
+        })
+    }
+
+    /// Copy of the pool for swap simulation without the token names
+    pub fn snapshot_for_sim(&self) -> Self {
+        Self {
+            token0_name: String::new(),
+            token1_name: String::new(),
+            additional_token_names: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    pub fn get_tokens(&self) -> Vec<Address> {
+        let mut tokens = vec![self.token0, self.token1];
+        tokens.extend(self.additional_tokens.iter());
+        tokens
+    }
+
+    pub fn get_token_decimals(&self) -> Vec<u8> {
+        let mut decimals = vec![self.token0_decimals, self.token1_decimals];
+        decimals.extend(self.additional_token_decimals.iter());
+        decimals
+    }
+
+    pub fn get_token_index(&self, token: &Address) -> Option<usize> {
+        self.get_tokens().iter().position(|t| t == token)
+    }
+
+    /// Swap fee as a fraction of the output amount
+    pub fn fee_fraction(&self) -> f64 {
+        f64::from(self.fee) / FEE_DENOMINATOR as f64
+    }
+
+    /// Output amount of swapping `dx` of coin `i` for coin `j`, net of the pool fee. Follows
+    /// the pool's `get_dy` with the stored rates, so quotes taken while A is ramping can be
+    /// slightly off. None for invalid indices or a pool without state
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Option<U256> {
+        let n = self.balances.len();
+        if i >= n || j >= n || i == j || dx.is_zero() || self.rates.len() != n {
+            return None;
+        }
+        if self.a.is_zero() {
+            return None;
+        }
+        let one = U256::from(PRECISION);
+        let xp = self
+            .balances
+            .iter()
+            .zip(&self.rates)
+            .map(|(balance, rate)| Some(balance.checked_mul(*rate)? / one))
+            .collect::<Option<Vec<U256>>>()?;
+
+        let x = xp[i].checked_add(dx.checked_mul(self.rates[i])? / one)?;
+        let y = self.get_y(i, j, x, &xp)?;
+        let dy = xp[j].checked_sub(y)?.checked_sub(U256::from(1))?;
+        let fee = self.fee * dy / U256::from(FEE_DENOMINATOR);
+        (dy - fee).checked_mul(one)?.checked_div(self.rates[j])
+    }
+
+    /// StableSwap invariant of the balances `xp`, all scaled to 18 decimals
+    fn get_d(&self, xp: &[U256]) -> Option<U256> {
+        let n = U256::from(xp.len());
+        let a_precision = U256::from(A_PRECISION);
+        let s = xp.iter().try_fold(U256::ZERO, |sum, x| sum.checked_add(*x))?;
+        if s.is_zero() {
+            return Some(U256::ZERO);
+        }
+
+        let ann = self.a * n;
+        let mut d = s;
+        for _ in 0..255 {
+            let mut d_p = d;
+            for x in xp {
+                d_p = d_p.checked_mul(d)?.checked_div(x.checked_mul(n)?)?;
+            }
+            let d_prev = d;
+            let numerator = (ann * s / a_precision + d_p * n).checked_mul(d)?;
+            let denominator = ann.checked_sub(a_precision)? * d / a_precision
+                + (n + U256::from(1)) * d_p;
+            d = numerator.checked_div(denominator)?;
+            if d.abs_diff(d_prev) <= U256::from(1) {
+                return Some(d);
+            }
+        }
+        None
+    }
+
+    /// Balance of coin `j` that keeps the invariant when coin `i` is set to `x`
+    fn get_y(&self, i: usize, j: usize, x: U256, xp: &[U256]) -> Option<U256> {
+        let n = U256::from(xp.len());
+        let a_precision = U256::from(A_PRECISION);
+        let d = self.get_d(xp)?;
+        let ann = self.a * n;
+
+        let mut c = d;
+        let mut s = U256::ZERO;
+        for (k, balance) in xp.iter().enumerate() {
+            let x_k = if k == i {
+                x
+            } else if k != j {
+                *balance
+            } else {
+                continue;
+            };
+            s += x_k;
+            c = c.checked_mul(d)?.checked_div(x_k.checked_mul(n)?)?;
+        }
+        c = c.checked_mul(d)?.checked_mul(a_precision)?.checked_div(ann * n)?;
+        let b = s + d * a_precision / ann;
+
+        let mut y = d;
+        for _ in 0..255 {
+            let y_prev = y;
+            y = (y.checked_mul(y)?.checked_add(c)?)
+                .checked_div((U256::from(2) * y + b).checked_sub(d)?)?;
+            if y.abs_diff(y_prev) <= U256::from(1) {
+                return Some(y);
+            }
+        }
+        None
+    }
+}
@@ -2,6 +2,9 @@ use alloy::dyn_abi::DynSolValue;
 use alloy::primitives::Address;
 use serde::{Deserialize, Serialize};
 
+use crate::errors::PoolSyncError;
+use crate::pools::pool_structures::{address_at, uint_at};
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CurveTriCryptoPool {
     pub address: Address,
@@ -14,9 +17,26 @@ pub struct CurveTriCryptoPool {
     pub token0_decimals: u8,
     pub token1_decimals: u8,
     pub token2_decimals: u8,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
 }
 
 impl CurveTriCryptoPool {
+    /// Copy of the pool for swap simulation without the token names
+    pub fn snapshot_for_sim(&self) -> Self {
+        Self {
+            address: self.address,
+            token0: self.token0,
+            token1: self.token1,
+            token2: self.token2,
+            token0_decimals: self.token0_decimals,
+            token1_decimals: self.token1_decimals,
+            token2_decimals: self.token2_decimals,
+            ..Default::default()
+        }
+    }
+
     pub fn get_tokens(&self) -> Vec<Address> {
         let tokens = vec![self.token0, self.token1, self.token2];
         tokens
@@ -33,17 +53,19 @@ impl CurveTriCryptoPool {
     }
 }
 
-impl From<&[DynSolValue]> for CurveTriCryptoPool {
-    fn from(data: &[DynSolValue]) -> Self {
-        let pool_address = data[0].as_address().unwrap();
-        let token0 = data[1].as_address().unwrap();
-        let token1 = data[2].as_address().unwrap();
-        let token2 = data[3].as_address().unwrap();
-        let token0_decimals = data[4].as_uint().unwrap().0.to::<u8>();
-        let token1_decimals = data[5].as_uint().unwrap().0.to::<u8>();
-        let token2_decimals = data[6].as_uint().unwrap().0.to::<u8>();
+impl TryFrom<&[DynSolValue]> for CurveTriCryptoPool {
+    type Error = PoolSyncError;
 
-        Self {
+    fn try_from(data: &[DynSolValue]) -> Result<Self, Self::Error> {
+        let pool_address = address_at(data, 0)?;
+        let token0 = address_at(data, 1)?;
+        let token1 = address_at(data, 2)?;
+        let token2 = address_at(data, 3)?;
+        let token0_decimals = uint_at(data, 4)?;
+        let token1_decimals = uint_at(data, 5)?;
+        let token2_decimals = uint_at(data, 6)?;
+
+        Ok(Self {
             address: pool_address,
             token0,
             token1,
@@ -52,7 +74,7 @@ impl From<&[DynSolValue]> for CurveTriCryptoPool {
             token1_decimals,
             token2_decimals,
             ..Default::default()
-        }
+        })
     }
 }
 
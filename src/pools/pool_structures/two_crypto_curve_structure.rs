@@ -1,7 +1,17 @@
 use alloy::dyn_abi::DynSolValue;
-use alloy::primitives::Address;
+use alloy::primitives::{Address, U256};
 use serde::{Deserialize, Serialize};
 
+use crate::errors::PoolSyncError;
+use crate::pools::pool_structures::{address_at, uint_at};
+
+/// Fixed point one used by the cryptoswap math
+const PRECISION: u128 = 1_000_000_000_000_000_000;
+/// Scale of the stored A parameter
+const A_MULTIPLIER: u64 = 10_000;
+/// Fees are stored with 10 decimals
+const FEE_DENOMINATOR: u64 = 10_000_000_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CurveTwoCryptoPool {
     pub address: Address,
@@ -11,18 +21,193 @@ pub struct CurveTwoCryptoPool {
     pub token1_name: String,
     pub token0_decimals: u8,
     pub token1_decimals: u8,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
+    /// Raw token balances held by the pool
+    #[serde(default)]
+    pub balances: [U256; 2],
+    /// Price of token1 in token0 with 18 decimals
+    #[serde(default)]
+    pub price_scale: U256,
+    /// Invariant of the pool
+    #[serde(default)]
+    pub d: U256,
+    /// Amplification as returned by `A()`, A * N^N * 10000
+    #[serde(default)]
+    pub a: U256,
+    #[serde(default)]
+    pub gamma: U256,
+    /// Fee charged when the pool is balanced, 10 decimals
+    #[serde(default)]
+    pub mid_fee: U256,
+    /// Fee charged when the pool is imbalanced, 10 decimals
+    #[serde(default)]
+    pub out_fee: U256,
+    /// How fast the fee moves from `mid_fee` to `out_fee`
+    #[serde(default)]
+    pub fee_gamma: U256,
 }
 
-impl From<&[DynSolValue]> for CurveTwoCryptoPool {
-    fn from(data: &[DynSolValue]) -> Self {
+impl CurveTwoCryptoPool {
+    /// Copy of the pool for swap simulation without the token names
+    pub fn snapshot_for_sim(&self) -> Self {
         Self {
-            address: data[0].as_address().unwrap(),
-            token0: data[1].as_address().unwrap(),
-            token1: data[2].as_address().unwrap(),
-            token0_decimals: data[3].as_uint().unwrap().0.to::<u8>(),
-            token1_decimals: data[4].as_uint().unwrap().0.to::<u8>(),
+            address: self.address,
+            token0: self.token0,
+            token1: self.token1,
+            token0_decimals: self.token0_decimals,
+            token1_decimals: self.token1_decimals,
+            decimals_verified: self.decimals_verified,
+            balances: self.balances,
+            price_scale: self.price_scale,
+            d: self.d,
+            a: self.a,
+            gamma: self.gamma,
+            mid_fee: self.mid_fee,
+            out_fee: self.out_fee,
+            fee_gamma: self.fee_gamma,
             ..Default::default()
         }
     }
+
+    /// Output amount of swapping `dx` of coin `i` for coin `j`, net of the pool fee. Follows
+    /// the pool's `get_dy` using the stored invariant, so quotes taken while A and gamma are
+    /// ramping can be slightly off. Returns None for invalid indices, a pool without state, or
+    /// a swap the math overflows on
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Option<U256> {
+        if i > 1 || j > 1 || i == j || dx.is_zero() {
+            return None;
+        }
+        if self.d.is_zero()
+            || self.a.is_zero()
+            || self.gamma.is_zero()
+            || self.price_scale.is_zero()
+        {
+            return None;
+        }
+        let one = U256::from(PRECISION);
+        let precisions = [
+            U256::from(10).pow(U256::from(18u8.checked_sub(self.token0_decimals)?)),
+            U256::from(10).pow(U256::from(18u8.checked_sub(self.token1_decimals)?)),
+        ];
+
+        let mut balances = self.balances;
+        balances[i] = balances[i].checked_add(dx)?;
+        let mut xp = [
+            balances[0].checked_mul(precisions[0])?,
+            balances[1]
+                .checked_mul(self.price_scale)?
+                .checked_mul(precisions[1])?
+                / one,
+        ];
+
+        let y = self.newton_y(xp, j)?;
+        let mut dy = xp[j].checked_sub(y)?.checked_sub(U256::from(1))?;
+        xp[j] = y;
+        if j > 0 {
+            dy = dy.checked_mul(one)? / self.price_scale;
+        }
+        dy /= precisions[j];
+
+        let fee = self.fee(xp)?;
+        dy.checked_sub(fee.checked_mul(dy)? / U256::from(FEE_DENOMINATOR))
+    }
+
+    /// Dynamic fee for the balances `xp`, moves from `mid_fee` towards `out_fee` as the pool
+    /// gets imbalanced
+    fn fee(&self, xp: [U256; 2]) -> Option<U256> {
+        let one = U256::from(PRECISION);
+        let sum = xp[0].checked_add(xp[1])?;
+        let balance = (U256::from(4).checked_mul(one)?.checked_mul(xp[0])? / sum)
+            .checked_mul(xp[1])?
+            .checked_div(sum)?;
+        let f = self
+            .fee_gamma
+            .checked_mul(one)?
+            .checked_div(self.fee_gamma.checked_add(one)?.checked_sub(balance)?)?;
+        let fee = self
+            .mid_fee
+            .checked_mul(f)?
+            .checked_add(self.out_fee.checked_mul(one.checked_sub(f)?)?)?;
+        Some(fee / one)
+    }
+
+    /// Newton's method for the balance of coin `i` that keeps the invariant at `d` given the
+    /// other balance in `xp`, both scaled to 18 decimals and priced in coin 0
+    fn newton_y(&self, xp: [U256; 2], i: usize) -> Option<U256> {
+        let one = U256::from(PRECISION);
+        let n = U256::from(2);
+        let (d, gamma) = (self.d, self.gamma);
+        let x_j = xp[1 - i];
+        let mut y = d.checked_mul(d)?.checked_div(x_j.checked_mul(n * n)?)?;
+        let k0_i = (one * n).checked_mul(x_j)? / d;
+        let convergence_limit = (x_j / U256::from(10).pow(U256::from(14)))
+            .max(d / U256::from(10).pow(U256::from(14)))
+            .max(U256::from(100));
+
+        for _ in 0..255 {
+            let y_prev = y;
+            let k0 = k0_i.checked_mul(y)?.checked_mul(n)? / d;
+            let s = x_j.checked_add(y)?;
+
+            let g1k0 = gamma.checked_add(one)?;
+            let g1k0 = if g1k0 > k0 {
+                g1k0 - k0 + U256::from(1)
+            } else {
+                k0 - g1k0 + U256::from(1)
+            };
+            let mul1 = (one.checked_mul(d)? / gamma).checked_mul(g1k0)? / gamma;
+            let mul1 = mul1
+                .checked_mul(g1k0)?
+                .checked_mul(U256::from(A_MULTIPLIER))?
+                .checked_div(self.a)?;
+            let mul2 = one.checked_add(U256::from(2).checked_mul(one)?.checked_mul(k0)? / g1k0)?;
+
+            let yfprime = one
+                .checked_mul(y)?
+                .checked_add(s.checked_mul(mul2)?)?
+                .checked_add(mul1)?;
+            let dyfprime = d.checked_mul(mul2)?;
+            if yfprime < dyfprime {
+                y = y_prev / U256::from(2);
+                continue;
+            }
+            let yfprime = yfprime - dyfprime;
+            let fprime = yfprime.checked_div(y)?;
+
+            let mut y_minus = mul1.checked_div(fprime)?;
+            let y_plus = yfprime
+                .checked_add(one.checked_mul(d)?)?
+                .checked_div(fprime)?
+                .checked_add(y_minus.checked_mul(one)?.checked_div(k0)?)?;
+            y_minus = y_minus.checked_add(one.checked_mul(s)? / fprime)?;
+            y = if y_plus < y_minus {
+                y_prev / U256::from(2)
+            } else {
+                y_plus - y_minus
+            };
+
+            let diff = if y > y_prev { y - y_prev } else { y_prev - y };
+            if diff < convergence_limit.max(y / U256::from(10).pow(U256::from(14))) {
+                return Some(y);
+            }
+        }
+        None
+    }
 }
 
+impl TryFrom<&[DynSolValue]> for CurveTwoCryptoPool {
+    type Error = PoolSyncError;
+
+    fn try_from(data: &[DynSolValue]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            address: address_at(data, 0)?,
+            token0: address_at(data, 1)?,
+            token1: address_at(data, 2)?,
+            token0_decimals: uint_at(data, 3)?,
+            token1_decimals: uint_at(data, 4)?,
+            ..Default::default()
+        })
+    }
+}
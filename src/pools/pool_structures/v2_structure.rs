@@ -1,5 +1,7 @@
 
 use crate::events::{AerodromeSync, DataEvents};
+use crate::errors::PoolSyncError;
+use crate::pools::pool_structures::{address_at, uint_at};
 use crate::pools::PoolType;
 use alloy::dyn_abi::DynSolValue;
 use alloy::primitives::{Address, U256};
@@ -20,10 +22,244 @@ pub struct UniswapV2Pool {
     pub token1_reserves: U256,
     pub stable: Option<bool>,
     pub fee: Option<U256>,
+    /// Fee in basis points of the constant product math for pools that do not report a fee,
+    /// set from the pool type. None falls back to the uniswap 0.3%
+    #[serde(default)]
+    pub router_fee_bps: Option<u32>,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
+}
+
+impl UniswapV2Pool {
+    /// Copy of the reserve state for swap simulation, token names are not copied
+    pub fn snapshot_for_sim(&self) -> Self {
+        Self {
+            address: self.address,
+            token0: self.token0,
+            token1: self.token1,
+            token0_name: String::new(),
+            token1_name: String::new(),
+            token0_decimals: self.token0_decimals,
+            token1_decimals: self.token1_decimals,
+            token0_reserves: self.token0_reserves,
+            token1_reserves: self.token1_reserves,
+            stable: self.stable,
+            fee: self.fee,
+            router_fee_bps: self.router_fee_bps,
+            decimals_verified: self.decimals_verified,
+        }
+    }
+
+    /// Spot price of `base` denominated in the other token of the pool, adjusted for decimals
+    pub fn price(&self, base: Address) -> Option<f64> {
+        let reserve0 = f64::from(self.token0_reserves) / 10f64.powi(self.token0_decimals as i32);
+        let reserve1 = f64::from(self.token1_reserves) / 10f64.powi(self.token1_decimals as i32);
+        if reserve0 == 0.0 || reserve1 == 0.0 {
+            return None;
+        }
+
+        if base == self.token0 {
+            Some(reserve1 / reserve0)
+        } else if base == self.token1 {
+            Some(reserve0 / reserve1)
+        } else {
+            None
+        }
+    }
+
+    /// Impermanent loss of a position opened at `entry_reserves` (token0, token1) compared to
+    /// holding the tokens, as a positive fraction of the held value. Follows the constant
+    /// product curve and ignores earned fees. Returns 0 if either side has no reserves
+    pub fn impermanent_loss(&self, entry_reserves: (U256, U256)) -> f64 {
+        let (entry0, entry1) = (f64::from(entry_reserves.0), f64::from(entry_reserves.1));
+        let (current0, current1) = (
+            f64::from(self.token0_reserves),
+            f64::from(self.token1_reserves),
+        );
+        if entry0 == 0.0 || entry1 == 0.0 || current0 == 0.0 || current1 == 0.0 {
+            return 0.0;
+        }
+
+        // decimals cancel out in the ratio of the two prices
+        let ratio = (current1 / current0) / (entry1 / entry0);
+        1.0 - 2.0 * ratio.sqrt() / (1.0 + ratio)
+    }
+
+    /// Swap fee as a fraction of the input amount. Pools that report a fee (aerodrome) store it
+    /// in basis points, everything else uses the fee of its pool type
+    pub fn fee_fraction(&self) -> f64 {
+        self.fee_bps() as f64 / 10_000.0
+    }
+
+    /// Swap fee in basis points, the stored fee for pools that report one and the fee of the
+    /// pool type otherwise
+    pub fn fee_bps(&self) -> u32 {
+        self.fee
+            .map_or(self.router_fee_bps.unwrap_or(30), |fee| fee.saturating_to())
+    }
+
+    /// Output amount of a swap, None if `token_in` is not in the pool or the pool has no
+    /// reserves. Pools without a fee follow the uniswap router math with the fee of their pool
+    /// type. Pools that report a fee (aerodrome) take it off the input first like the pool
+    /// contract, and stable pools quote on the x3y + y3x curve
+    pub fn get_amount_out(&self, amount_in: U256, token_in: Address) -> Option<U256> {
+        let (reserve_in, reserve_out) = if token_in == self.token0 {
+            (self.token0_reserves, self.token1_reserves)
+        } else if token_in == self.token1 {
+            (self.token1_reserves, self.token0_reserves)
+        } else {
+            return None;
+        };
+        if reserve_in.is_zero() || reserve_out.is_zero() {
+            return None;
+        }
+
+        let Some(fee) = self.fee else {
+            let fee_factor = U256::from(10_000).checked_sub(U256::from(self.fee_bps()))?;
+            let amount_in_with_fee = amount_in.checked_mul(fee_factor)?;
+            let numerator = amount_in_with_fee.checked_mul(reserve_out)?;
+            let denominator = reserve_in
+                .checked_mul(U256::from(10_000))?
+                .checked_add(amount_in_with_fee)?;
+            return Some(numerator / denominator);
+        };
+
+        let amount_in = amount_in - amount_in.checked_mul(fee)? / U256::from(10_000);
+        if self.stable == Some(true) {
+            return self.stable_amount_out(amount_in, token_in == self.token0);
+        }
+        let numerator = amount_in.checked_mul(reserve_out)?;
+        Some(numerator / reserve_in.checked_add(amount_in)?)
+    }
+
+    /// Input amount needed to receive `amount_out` of `token_out`, rounded up in favor of the
+    /// pool. None if `token_out` is not in the pool, the output drains the reserve, or the pool
+    /// is a stable pool since the x3y + y3x curve has no closed form inverse
+    pub fn get_amount_in(&self, amount_out: U256, token_out: Address) -> Option<U256> {
+        let (reserve_in, reserve_out) = if token_out == self.token1 {
+            (self.token0_reserves, self.token1_reserves)
+        } else if token_out == self.token0 {
+            (self.token1_reserves, self.token0_reserves)
+        } else {
+            return None;
+        };
+        if reserve_in.is_zero() || amount_out >= reserve_out || self.stable == Some(true) {
+            return None;
+        }
+
+        let numerator = reserve_in.checked_mul(amount_out)?;
+        let denominator = reserve_out - amount_out;
+        let Some(fee) = self.fee else {
+            let fee_factor = U256::from(10_000)
+                .checked_sub(U256::from(self.fee_bps()))
+                .filter(|factor| !factor.is_zero())?;
+            let numerator = numerator.checked_mul(U256::from(10_000))?;
+            return Some(numerator / denominator.checked_mul(fee_factor)? + U256::from(1));
+        };
+
+        // gross the input up so the pool still receives enough once its fee is taken off
+        let amount_in = numerator / denominator + U256::from(1);
+        let fee_denominator = U256::from(10_000).checked_sub(fee).filter(|d| !d.is_zero())?;
+        Some(amount_in.checked_mul(U256::from(10_000))?.div_ceil(fee_denominator))
+    }
+
+    /// Output of the solidly stable curve for a fee adjusted input, balances are scaled to 18
+    /// decimals before solving for the new output reserve
+    fn stable_amount_out(&self, amount_in: U256, zero_for_one: bool) -> Option<U256> {
+        let one = U256::from(10).pow(U256::from(18));
+        let unit0 = U256::from(10).pow(U256::from(self.token0_decimals));
+        let unit1 = U256::from(10).pow(U256::from(self.token1_decimals));
+
+        let xy = self.stable_k(self.token0_reserves, self.token1_reserves)?;
+        let reserve0 = self.token0_reserves.checked_mul(one)? / unit0;
+        let reserve1 = self.token1_reserves.checked_mul(one)? / unit1;
+        let (reserve_in, reserve_out, unit_in, unit_out) = if zero_for_one {
+            (reserve0, reserve1, unit0, unit1)
+        } else {
+            (reserve1, reserve0, unit1, unit0)
+        };
+        let amount_in = amount_in.checked_mul(one)? / unit_in;
+        let y = self.stable_get_y(amount_in.checked_add(reserve_in)?, xy, reserve_out)?;
+        Some(reserve_out.checked_sub(y)?.checked_mul(unit_out)? / one)
+    }
+
+    /// Invariant of the stable curve over raw reserves
+    fn stable_k(&self, x: U256, y: U256) -> Option<U256> {
+        let one = U256::from(10).pow(U256::from(18));
+        let x = x.checked_mul(one)? / U256::from(10).pow(U256::from(self.token0_decimals));
+        let y = y.checked_mul(one)? / U256::from(10).pow(U256::from(self.token1_decimals));
+        stable_f(x, y)
+    }
+
+    /// Newton's method for the output reserve that keeps the invariant at `xy`, mirroring the
+    /// pool contract including its rounding
+    fn stable_get_y(&self, x0: U256, xy: U256, mut y: U256) -> Option<U256> {
+        let one = U256::from(10).pow(U256::from(18));
+        for _ in 0..255 {
+            let k = stable_f(x0, y)?;
+            if k < xy {
+                let mut dy = (xy - k).checked_mul(one)? / stable_d(x0, y)?;
+                if dy.is_zero() {
+                    if k == xy {
+                        return Some(y);
+                    }
+                    if self.stable_k(x0, y + U256::from(1))? > xy {
+                        return Some(y + U256::from(1));
+                    }
+                    dy = U256::from(1);
+                }
+                y = y.checked_add(dy)?;
+            } else {
+                let mut dy = (k - xy).checked_mul(one)? / stable_d(x0, y)?;
+                if dy.is_zero() {
+                    if k == xy || stable_f(x0, y.checked_sub(U256::from(1))?)? < xy {
+                        return Some(y);
+                    }
+                    dy = U256::from(1);
+                }
+                y = y.checked_sub(dy)?;
+            }
+        }
+        None
+    }
+
+    /// Execute a swap against the reserves as if it happened on chain and return the output
+    /// amount. The reserves are left untouched if the swap can not be computed
+    pub fn apply_swap(&mut self, amount_in: U256, token_in: Address) -> U256 {
+        let Some(amount_out) = self.get_amount_out(amount_in, token_in) else {
+            return U256::ZERO;
+        };
+
+        if token_in == self.token0 {
+            self.token0_reserves += amount_in;
+            self.token1_reserves -= amount_out;
+        } else {
+            self.token1_reserves += amount_in;
+            self.token0_reserves -= amount_out;
+        }
+        amount_out
+    }
+}
+
+// x3y + y3x over 18 decimal balances
+fn stable_f(x0: U256, y: U256) -> Option<U256> {
+    let one = U256::from(10).pow(U256::from(18));
+    let a = x0.checked_mul(y)? / one;
+    let b = (x0.checked_mul(x0)? / one).checked_add(y.checked_mul(y)? / one)?;
+    Some(a.checked_mul(b)? / one)
+}
+
+// derivative of the stable invariant with respect to y
+fn stable_d(x0: U256, y: U256) -> Option<U256> {
+    let one = U256::from(10).pow(U256::from(18));
+    let a = U256::from(3).checked_mul(x0)?.checked_mul(y.checked_mul(y)? / one)? / one;
+    let b = (x0.checked_mul(x0)? / one).checked_mul(x0)? / one;
+    a.checked_add(b)
 }
 
 pub fn process_sync_data(pool: &mut UniswapV2Pool, log: Log, pool_type: PoolType) {
-    let (reserve0, reserve1) = if pool_type == PoolType::Aerodrome {
+    let (reserve0, reserve1) = if matches!(pool_type, PoolType::Aerodrome | PoolType::Velodrome) {
         let sync_event =  AerodromeSync::Sync::decode_log(log.as_ref(), true).unwrap();
         (sync_event.reserve0, sync_event.reserve1)
     } else {
@@ -34,18 +270,20 @@ pub fn process_sync_data(pool: &mut UniswapV2Pool, log: Log, pool_type: PoolType
     pool.token1_reserves = reserve1;
 }
 
-impl From<&[DynSolValue]> for UniswapV2Pool {
-    fn from(data: &[DynSolValue]) -> Self {
-        Self {
-            address: data[0].as_address().unwrap(),
-            token0: data[1].as_address().unwrap(),
-            token1: data[2].as_address().unwrap(),
-            token0_decimals: data[3].as_uint().unwrap().0.to::<u8>(),
-            token1_decimals: data[4].as_uint().unwrap().0.to::<u8>(),
-            token0_reserves: data[5].as_uint().unwrap().0.to::<U256>(),
-            token1_reserves: data[6].as_uint().unwrap().0.to::<U256>(),
+impl TryFrom<&[DynSolValue]> for UniswapV2Pool {
+    type Error = PoolSyncError;
+
+    fn try_from(data: &[DynSolValue]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            address: address_at(data, 0)?,
+            token0: address_at(data, 1)?,
+            token1: address_at(data, 2)?,
+            token0_decimals: uint_at(data, 3)?,
+            token1_decimals: uint_at(data, 4)?,
+            token0_reserves: uint_at(data, 5)?,
+            token1_reserves: uint_at(data, 6)?,
             ..Default::default()
-        }
+        })
     }
 }
 
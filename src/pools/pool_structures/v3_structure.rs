@@ -1,11 +1,17 @@
 use alloy::dyn_abi::DynSolValue;
-use alloy::primitives::{Address, U256};
+use alloy::primitives::{Address, I256, U256};
 use alloy::rpc::types::Log;
 use alloy::sol_types::SolEvent;
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use uniswap_v3_math::error::UniswapV3MathError;
+use uniswap_v3_math::tick_math::{MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK};
+use uniswap_v3_math::{full_math, liquidity_math, swap_math, tick_bitmap, tick_math};
 
-use crate::events::{DataEvents, PancakeSwapEvents};
+use crate::errors::PoolSyncError;
+use crate::events::{AlgebraEvents, DataEvents, PancakeSwapEvents};
+use crate::pools::pool_structures::{address_at, int_at, uint_at};
 use crate::pools::PoolType;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -24,6 +30,320 @@ pub struct UniswapV3Pool {
     pub tick_spacing: i32,
     pub tick_bitmap: HashMap<i16, U256>,
     pub ticks: HashMap<i32, TickInfo>,
+    /// Set when ticks far from the current tick were evicted to bound memory
+    #[serde(default)]
+    pub ticks_truncated: bool,
+    /// Token0 prices after the most recent swaps, oldest first
+    #[serde(default)]
+    pub price_history: VecDeque<f64>,
+    /// Number of swap prices to keep, no history is recorded when zero
+    #[serde(skip)]
+    pub price_history_depth: usize,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
+    /// Algebra pool, `fee` is dynamic and follows the swap and fee events
+    #[serde(default)]
+    pub algebra: bool,
+}
+
+impl UniswapV3Pool {
+    /// Copy of the price, liquidity, and tick state for swap simulation. Token names are not
+    /// copied, the tick maps are
+    pub fn snapshot_for_sim(&self) -> Self {
+        Self {
+            address: self.address,
+            token0: self.token0,
+            token1: self.token1,
+            token0_name: String::new(),
+            token1_name: String::new(),
+            token0_decimals: self.token0_decimals,
+            token1_decimals: self.token1_decimals,
+            liquidity: self.liquidity,
+            sqrt_price: self.sqrt_price,
+            fee: self.fee,
+            tick: self.tick,
+            tick_spacing: self.tick_spacing,
+            tick_bitmap: self.tick_bitmap.clone(),
+            ticks: self.ticks.clone(),
+            ticks_truncated: self.ticks_truncated,
+            price_history: VecDeque::new(),
+            price_history_depth: 0,
+            decimals_verified: self.decimals_verified,
+            algebra: self.algebra,
+        }
+    }
+
+    /// Spot price of `base` denominated in the other token of the pool, derived from the
+    /// sqrt price and adjusted for decimals
+    pub fn price(&self, base: Address) -> Option<f64> {
+        if self.sqrt_price.is_zero() {
+            return None;
+        }
+
+        let sqrt_price = f64::from(self.sqrt_price) / 2f64.powi(96);
+        let decimal_shift = self.token0_decimals as i32 - self.token1_decimals as i32;
+        let price0 = sqrt_price * sqrt_price * 10f64.powi(decimal_shift);
+
+        if base == self.token0 {
+            Some(price0)
+        } else if base == self.token1 {
+            Some(1.0 / price0)
+        } else {
+            None
+        }
+    }
+
+    /// Virtual reserve of `token` implied by the active liquidity and current price. This is the
+    /// balance a V2 pool would need to offer the same depth at the current price
+    pub fn virtual_reserve(&self, token: Address) -> Option<U256> {
+        if self.sqrt_price.is_zero() {
+            return None;
+        }
+        let liquidity = U256::from(self.liquidity);
+        let q96 = U256::from(1) << 96;
+        if token == self.token0 {
+            full_math::mul_div(liquidity, q96, self.sqrt_price).ok()
+        } else if token == self.token1 {
+            full_math::mul_div(liquidity, self.sqrt_price, q96).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Prices of token0 after each of the most recent swaps, oldest first
+    pub fn recent_prices(&self) -> &VecDeque<f64> {
+        &self.price_history
+    }
+
+    /// Set how many swap prices to keep, dropping the oldest ones that no longer fit
+    pub fn set_price_history_depth(&mut self, depth: usize) {
+        self.price_history_depth = depth;
+        let excess = self.price_history.len().saturating_sub(depth);
+        self.price_history.drain(..excess);
+    }
+
+    /// Append the current price to the history if it is enabled
+    pub(crate) fn record_price(&mut self) {
+        if self.price_history_depth == 0 {
+            return;
+        }
+        if let Some(price) = self.price(self.token0) {
+            if self.price_history.len() == self.price_history_depth {
+                self.price_history.pop_front();
+            }
+            self.price_history.push_back(price);
+        }
+    }
+
+    /// Swap fee as a fraction of the input amount, the fee is stored in hundredths of a bip
+    pub fn fee_fraction(&self) -> f64 {
+        self.fee as f64 / 1_000_000.0
+    }
+
+    /// Fee tier in basis points, tiers below a basis point round down to zero
+    pub fn fee_bps(&self) -> u32 {
+        self.fee / 100
+    }
+
+    /// Walk the tick bitmap for an exact input swap, crossing initialized ticks until the input
+    /// is consumed or the price limit is reached. The pool is not modified
+    fn compute_swap(
+        &self,
+        token_in: Address,
+        amount_in: U256,
+    ) -> Result<SwapResult, UniswapV3MathError> {
+        let zero_for_one = token_in == self.token0;
+        let sqrt_price_limit = if zero_for_one {
+            MIN_SQRT_RATIO + U256::from(1)
+        } else {
+            MAX_SQRT_RATIO - U256::from(1)
+        };
+
+        let mut state = SwapResult {
+            amount_remaining: I256::from_raw(amount_in),
+            amount_out: U256::ZERO,
+            sqrt_price: self.sqrt_price,
+            tick: self.tick,
+            liquidity: self.liquidity,
+        };
+
+        while !state.amount_remaining.is_zero() && state.sqrt_price != sqrt_price_limit {
+            let sqrt_price_start = state.sqrt_price;
+            let (tick_next, initialized) = tick_bitmap::next_initialized_tick_within_one_word(
+                &self.tick_bitmap,
+                state.tick,
+                self.tick_spacing,
+                zero_for_one,
+            )?;
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next = tick_math::get_sqrt_ratio_at_tick(tick_next)?;
+
+            let sqrt_price_target = if zero_for_one {
+                sqrt_price_next.max(sqrt_price_limit)
+            } else {
+                sqrt_price_next.min(sqrt_price_limit)
+            };
+
+            let (sqrt_price, step_in, step_out, step_fee) = swap_math::compute_swap_step(
+                state.sqrt_price,
+                sqrt_price_target,
+                state.liquidity,
+                state.amount_remaining,
+                self.fee,
+            )?;
+            state.sqrt_price = sqrt_price;
+            state.amount_remaining -= I256::from_raw(step_in + step_fee);
+            state.amount_out += step_out;
+
+            if state.sqrt_price == sqrt_price_next {
+                // crossed into the next tick range, update the active liquidity
+                if initialized {
+                    let liquidity_net = self
+                        .ticks
+                        .get(&tick_next)
+                        .map(|info| info.liquidity_net)
+                        .unwrap_or(0);
+                    let liquidity_net = if zero_for_one {
+                        -liquidity_net
+                    } else {
+                        liquidity_net
+                    };
+                    state.liquidity = liquidity_math::add_delta(state.liquidity, liquidity_net)?;
+                }
+                state.tick = if zero_for_one { tick_next - 1 } else { tick_next };
+            } else if state.sqrt_price != sqrt_price_start {
+                state.tick = tick_math::get_tick_at_sqrt_ratio(state.sqrt_price)?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Output amount of an exact input swap of `amount_in` of `token_in`, crossing initialized
+    /// ticks as needed. Fails with `InsufficientLiquidity` if the price reaches the end of the
+    /// tick range before the input is consumed. The pool is not modified
+    pub fn simulate_swap(&self, token_in: Address, amount_in: U256) -> Result<U256, PoolSyncError> {
+        Ok(self.filled_swap(token_in, amount_in)?.amount_out)
+    }
+
+    /// Execute a swap against the pool as if it happened on chain, moving the price, tick, and
+    /// active liquidity. Returns the output amount. Fails like `simulate_swap` and leaves the
+    /// pool untouched if the swap can not be filled in full
+    pub fn apply_swap(
+        &mut self,
+        amount_in: U256,
+        token_in: Address,
+    ) -> Result<U256, PoolSyncError> {
+        let result = self.filled_swap(token_in, amount_in)?;
+        self.sqrt_price = result.sqrt_price;
+        self.tick = result.tick;
+        self.liquidity = result.liquidity;
+        Ok(result.amount_out)
+    }
+
+    // Swap of the whole input, a swap that runs out of liquidity part way is an error rather
+    // than a partial fill
+    fn filled_swap(&self, token_in: Address, amount_in: U256) -> Result<SwapResult, PoolSyncError> {
+        if token_in != self.token0 && token_in != self.token1 {
+            return Err(PoolSyncError::SwapError(format!(
+                "{} is not a token of pool {}",
+                token_in, self.address
+            )));
+        }
+
+        let result = self
+            .compute_swap(token_in, amount_in)
+            .map_err(|e| PoolSyncError::SwapError(e.to_string()))?;
+        if !result.amount_remaining.is_zero() {
+            return Err(PoolSyncError::InsufficientLiquidity);
+        }
+        Ok(result)
+    }
+
+    /// Keep only the `max_ticks` initialized ticks nearest the current tick, evicting the rest
+    /// from the tick map and bitmap. Swaps that stay within the kept window quote exactly,
+    /// larger swaps will not see the liquidity of the evicted ticks
+    pub fn truncate_ticks(&mut self, max_ticks: usize) {
+        if self.ticks.len() <= max_ticks {
+            return;
+        }
+
+        let mut ticks: Vec<i32> = self.ticks.keys().copied().collect();
+        ticks.sort_by_key(|tick| ((*tick as i64 - self.tick as i64).abs(), *tick));
+        for tick in ticks.into_iter().skip(max_ticks) {
+            if let Some(info) = self.ticks.remove(&tick) {
+                if info.liquidity_gross != 0 {
+                    flip_tick(self, tick, self.tick_spacing);
+                }
+            }
+        }
+        self.ticks_truncated = true;
+    }
+
+    /// Ticks where the bitmap and the tick map disagree, sorted. This covers ticks holding
+    /// liquidity that are not flagged in the bitmap and flagged bits with no liquidity behind
+    /// them. An empty result means the bitmap is consistent
+    pub fn validate_bitmap(&self) -> Vec<i32> {
+        let flagged: HashSet<i32> = self
+            .tick_bitmap
+            .iter()
+            .flat_map(|(word_pos, word)| {
+                (0..256usize)
+                    .filter(move |bit| word.bit(*bit))
+                    .map(move |bit| ((*word_pos as i32) * 256 + bit as i32) * self.tick_spacing)
+            })
+            .collect();
+        let initialized: HashSet<i32> = self
+            .ticks
+            .iter()
+            .filter(|(_, info)| info.liquidity_gross != 0)
+            .map(|(tick, _)| *tick)
+            .collect();
+
+        let mut mismatched: Vec<i32> = flagged
+            .symmetric_difference(&initialized)
+            .copied()
+            .collect();
+        mismatched.sort();
+        mismatched
+    }
+
+    /// Rebuild the bitmap from the ticks that hold liquidity
+    pub fn repair_bitmap(&mut self) {
+        self.tick_bitmap.clear();
+        let initialized: Vec<i32> = self
+            .ticks
+            .iter()
+            .filter(|(_, info)| info.liquidity_gross != 0)
+            .map(|(tick, _)| *tick)
+            .collect();
+        for tick in initialized {
+            flip_tick(self, tick, self.tick_spacing);
+        }
+    }
+
+    /// Recompute the active liquidity from the tick map by summing the net liquidity of every
+    /// initialized tick at or below the current tick. This should match `liquidity` and is
+    /// useful for validating the tick replay
+    pub fn liquidity_from_ticks(&self) -> u128 {
+        let liquidity: i128 = self
+            .ticks
+            .iter()
+            .filter(|(tick, info)| info.initialized && **tick <= self.tick)
+            .map(|(_, info)| info.liquidity_net)
+            .sum();
+        liquidity.max(0) as u128
+    }
+}
+
+/// Pool state at the end of a simulated swap
+struct SwapResult {
+    amount_remaining: I256,
+    amount_out: U256,
+    sqrt_price: U256,
+    tick: i32,
+    liquidity: u128,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -49,9 +369,37 @@ pub fn process_tick_data(
         || *event_sig == PancakeSwapEvents::Swap::SIGNATURE_HASH
     {
         process_swap(pool, log, pool_type);
+    } else if *event_sig == AlgebraEvents::Swap::SIGNATURE_HASH {
+        process_algebra_swap(pool, log);
+    } else if *event_sig == AlgebraEvents::Fee::SIGNATURE_HASH && pool.algebra {
+        match AlgebraEvents::Fee::decode_log(log.as_ref(), true) {
+            Ok(event) => pool.fee = u32::from(event.fee),
+            Err(_) => warn!("Failed to decode Algebra fee event of pool {}", pool.address),
+        }
     }
 }
 
+/// Whether a mint or burn log touches a position overlapping `window` ticks on either side of
+/// the current tick. Any other log is always considered near the price
+pub fn position_near_price(pool: &UniswapV3Pool, log: &Log, window: i32) -> bool {
+    let (tick_lower, tick_upper) = match log.topic0() {
+        Some(sig) if *sig == DataEvents::Mint::SIGNATURE_HASH => {
+            match DataEvents::Mint::decode_log(log.as_ref(), true) {
+                Ok(mint) => (mint.tickLower.as_i32(), mint.tickUpper.as_i32()),
+                Err(_) => return true,
+            }
+        }
+        Some(sig) if *sig == DataEvents::Burn::SIGNATURE_HASH => {
+            match DataEvents::Burn::decode_log(log.as_ref(), true) {
+                Ok(burn) => (burn.tickLower.as_i32(), burn.tickUpper.as_i32()),
+                Err(_) => return true,
+            }
+        }
+        _ => return true,
+    };
+    tick_upper >= pool.tick.saturating_sub(window) && tick_lower <= pool.tick.saturating_add(window)
+}
+
 fn process_burn(pool: &mut UniswapV3Pool, log: Log, is_initial_sync: bool) {
     let burn_event = DataEvents::Burn::decode_log(log.as_ref(), true).unwrap();
     modify_position(
@@ -86,6 +434,24 @@ fn process_swap(pool: &mut UniswapV3Pool, log: Log, pool_type: PoolType) {
         pool.sqrt_price = U256::from(swap_event.sqrtPriceX96);
         pool.liquidity = swap_event.liquidity;
     }
+    pool.record_price();
+}
+
+// Algebra swaps report the fee they were charged when a plugin overrides the base fee, a zero
+// override leaves the last known fee in place
+fn process_algebra_swap(pool: &mut UniswapV3Pool, log: Log) {
+    let Ok(swap_event) = AlgebraEvents::Swap::decode_log(log.as_ref(), true) else {
+        warn!("Failed to decode Algebra swap event of pool {}", pool.address);
+        return;
+    };
+    pool.tick = swap_event.tick.as_i32();
+    pool.sqrt_price = U256::from(swap_event.price);
+    pool.liquidity = swap_event.liquidity;
+    let fee = swap_event.overrideFee.to::<u32>();
+    if fee != 0 {
+        pool.fee = fee;
+    }
+    pool.record_price();
 }
 
 /// Modifies a positions liquidity in the pool.
@@ -98,6 +464,14 @@ pub fn modify_position(
 ) {
     //We are only using this function when a mint or burn event is emitted,
     //therefore we do not need to checkTicks as that has happened before the event is emitted
+    //the bounds are still checked so a bad log can not corrupt the bitmap or the liquidity
+    if !tick_in_range(tick_lower) || !tick_in_range(tick_upper) {
+        warn!(
+            "Skipping position [{}, {}] of pool {}, tick out of range",
+            tick_lower, tick_upper, pool.address
+        );
+        return;
+    }
     update_position(pool, tick_lower, tick_upper, liquidity_delta);
 
     // if it is the initial sync, ignore since liq is populated via contract
@@ -122,6 +496,15 @@ pub fn update_position(
     let mut flipped_lower = false;
     let mut flipped_upper = false;
 
+    // skip the whole position so the lower and upper ticks stay consistent
+    if !tick_in_range(tick_lower) || !tick_in_range(tick_upper) {
+        warn!(
+            "Skipping position [{}, {}] of pool {}, tick out of range",
+            tick_lower, tick_upper, pool.address
+        );
+        return;
+    }
+
     if liquidity_delta != 0 {
         flipped_lower = update_tick(pool, tick_lower, liquidity_delta, false);
         flipped_upper = update_tick(pool, tick_upper, liquidity_delta, true);
@@ -150,6 +533,13 @@ pub fn update_tick(
     liquidity_delta: i128,
     upper: bool,
 ) -> bool {
+    if !tick_in_range(tick) {
+        warn!("Skipping tick {} of pool {}, out of range", tick, pool.address);
+        return false;
+    }
+
+    // an evicted tick comes back with partial liquidity, so removals may exceed what is stored
+    let truncated = pool.ticks_truncated;
     let info = match pool.ticks.get_mut(&tick) {
         Some(info) => info,
         None => {
@@ -162,7 +552,9 @@ pub fn update_tick(
 
     let liquidity_gross_before = info.liquidity_gross;
 
-    let liquidity_gross_after = if liquidity_delta < 0 {
+    let liquidity_gross_after = if liquidity_delta < 0 && truncated {
+        liquidity_gross_before.saturating_sub((-liquidity_delta) as u128)
+    } else if liquidity_delta < 0 {
         liquidity_gross_before - ((-liquidity_delta) as u128)
     } else {
         liquidity_gross_before + (liquidity_delta as u128)
@@ -187,7 +579,19 @@ pub fn update_tick(
     flipped
 }
 
+fn tick_in_range(tick: i32) -> bool {
+    (MIN_TICK..=MAX_TICK).contains(&tick)
+}
+
 pub fn flip_tick(pool: &mut UniswapV3Pool, tick: i32, tick_spacing: i32) {
+    // a tick off the spacing grid would land on the wrong word position
+    if !tick_in_range(tick) || tick_spacing <= 0 || tick % tick_spacing != 0 {
+        warn!(
+            "Not flipping tick {} of pool {} with spacing {}",
+            tick, pool.address, tick_spacing
+        );
+        return;
+    }
     let (word_pos, bit_pos) = uniswap_v3_math::tick_bitmap::position(tick / tick_spacing);
     let mask = U256::from(1) << bit_pos;
 
@@ -198,20 +602,22 @@ pub fn flip_tick(pool: &mut UniswapV3Pool, tick: i32, tick_spacing: i32) {
     }
 }
 
-impl From<&[DynSolValue]> for UniswapV3Pool {
-    fn from(data: &[DynSolValue]) -> Self {
-        Self {
-            address: data[0].as_address().unwrap(),
-            token0: data[1].as_address().unwrap(),
-            token0_decimals: data[2].as_uint().unwrap().0.to::<u8>(),
-            token1: data[3].as_address().unwrap(),
-            token1_decimals: data[4].as_uint().unwrap().0.to::<u8>(),
-            liquidity: data[5].as_uint().unwrap().0.to::<u128>(),
-            sqrt_price: data[6].as_uint().unwrap().0,
-            tick: data[7].as_int().unwrap().0.as_i32(),
-            tick_spacing: data[8].as_int().unwrap().0.as_i32(),
-            fee: data[9].as_uint().unwrap().0.to::<u32>(),
+impl TryFrom<&[DynSolValue]> for UniswapV3Pool {
+    type Error = PoolSyncError;
+
+    fn try_from(data: &[DynSolValue]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            address: address_at(data, 0)?,
+            token0: address_at(data, 1)?,
+            token0_decimals: uint_at(data, 2)?,
+            token1: address_at(data, 3)?,
+            token1_decimals: uint_at(data, 4)?,
+            liquidity: uint_at(data, 5)?,
+            sqrt_price: uint_at(data, 6)?,
+            tick: int_at(data, 7)?.as_i32(),
+            tick_spacing: int_at(data, 8)?.as_i32(),
+            fee: uint_at(data, 9)?,
             ..Default::default()
-        }
+        })
     }
 }
@@ -0,0 +1,158 @@
+use alloy::primitives::{Address, B256, U256};
+use alloy::rpc::types::Log;
+use alloy::sol_types::SolEvent;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uniswap_v3_math::tick_math::{MAX_TICK, MIN_TICK};
+
+use crate::events::UniswapV4Events;
+use crate::pools::pool_structures::v3_structure::TickInfo;
+
+/// Fee of a pool key whose hook sets the fee per swap
+pub const DYNAMIC_FEE_FLAG: u32 = 0x800000;
+
+/// A Uniswap V4 pool. V4 pools live inside the PoolManager singleton and are identified by
+/// their `pool_id` instead of a contract address, `address` holds the first 20 bytes of the id
+/// so the pool can be keyed like every other pool. A zero currency is the native token
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UniswapV4Pool {
+    pub address: Address,
+    pub pool_id: B256,
+    pub token0: Address,
+    pub token1: Address,
+    pub token0_name: String,
+    pub token1_name: String,
+    pub token0_decimals: u8,
+    pub token1_decimals: u8,
+    /// Fee of the last swap in hundredths of a bip. Starts at the pool key fee, or zero for
+    /// dynamic fee pools until their first swap
+    pub fee: u32,
+    pub dynamic_fee: bool,
+    pub tick_spacing: i32,
+    pub hooks: Address,
+    pub liquidity: u128,
+    pub sqrt_price: U256,
+    pub tick: i32,
+    pub ticks: HashMap<i32, TickInfo>,
+    /// Whether the token decimals match the configured `token_decimals`, None if unchecked
+    #[serde(default)]
+    pub decimals_verified: Option<bool>,
+}
+
+impl UniswapV4Pool {
+    /// Build the pool from its `Initialize` event. Token names and decimals are left empty,
+    /// None if the log is not an `Initialize` event
+    pub fn from_initialize(log: &Log) -> Option<Self> {
+        let event = UniswapV4Events::Initialize::decode_log(log.as_ref(), true).ok()?;
+        let dynamic_fee = event.fee.to::<u32>() == DYNAMIC_FEE_FLAG;
+        Some(Self {
+            address: Address::from_slice(&event.id[..20]),
+            pool_id: event.id,
+            token0: event.currency0,
+            token1: event.currency1,
+            fee: if dynamic_fee { 0 } else { event.fee.to() },
+            dynamic_fee,
+            tick_spacing: event.tickSpacing.as_i32(),
+            hooks: event.hooks,
+            sqrt_price: U256::from(event.sqrtPriceX96),
+            tick: event.tick.as_i32(),
+            ..Default::default()
+        })
+    }
+
+    /// Copy of the price, liquidity, and tick state for swap simulation, token names are not
+    /// copied
+    pub fn snapshot_for_sim(&self) -> Self {
+        Self {
+            token0_name: String::new(),
+            token1_name: String::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Spot price of `base` denominated in the other token of the pool, derived from the
+    /// sqrt price and adjusted for decimals
+    pub fn price(&self, base: Address) -> Option<f64> {
+        if self.sqrt_price.is_zero() {
+            return None;
+        }
+
+        let sqrt_price = f64::from(self.sqrt_price) / 2f64.powi(96);
+        let decimal_shift = self.token0_decimals as i32 - self.token1_decimals as i32;
+        let price0 = sqrt_price * sqrt_price * 10f64.powi(decimal_shift);
+
+        if base == self.token0 {
+            Some(price0)
+        } else if base == self.token1 {
+            Some(1.0 / price0)
+        } else {
+            None
+        }
+    }
+
+    /// Swap fee as a fraction of the input amount
+    pub fn fee_fraction(&self) -> f64 {
+        self.fee as f64 / 1_000_000.0
+    }
+
+    /// Fee tier in basis points, tiers below a basis point round down to zero
+    pub fn fee_bps(&self) -> u32 {
+        self.fee / 100
+    }
+
+    /// Add `liquidity_delta` to the position between the ticks, moving the active liquidity
+    /// when the current tick is inside the position
+    pub fn modify_liquidity(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: i128) {
+        let in_range = |tick: i32| (MIN_TICK..=MAX_TICK).contains(&tick);
+        if !in_range(tick_lower) || !in_range(tick_upper) || liquidity_delta == 0 {
+            return;
+        }
+
+        for (tick, upper) in [(tick_lower, false), (tick_upper, true)] {
+            let info = self.ticks.entry(tick).or_default();
+            info.liquidity_gross = info.liquidity_gross.saturating_add_signed(liquidity_delta);
+            info.liquidity_net += if upper { -liquidity_delta } else { liquidity_delta };
+            info.initialized = info.liquidity_gross != 0;
+            if !info.initialized {
+                self.ticks.remove(&tick);
+            }
+        }
+
+        if self.tick >= tick_lower && self.tick < tick_upper {
+            self.liquidity = self.liquidity.saturating_add_signed(liquidity_delta);
+        }
+    }
+}
+
+/// Apply a `ModifyLiquidity` or `Swap` event of the PoolManager to the pool
+pub fn process_v4_data(pool: &mut UniswapV4Pool, log: Log) {
+    let Some(event_sig) = log.topic0() else {
+        return;
+    };
+
+    if *event_sig == UniswapV4Events::ModifyLiquidity::SIGNATURE_HASH {
+        let Ok(event) = UniswapV4Events::ModifyLiquidity::decode_log(log.as_ref(), true) else {
+            warn!("Failed to decode V4 liquidity event of pool {}", pool.pool_id);
+            return;
+        };
+        let Ok(liquidity_delta) = i128::try_from(event.liquidityDelta) else {
+            warn!("Liquidity delta out of range for pool {}", pool.pool_id);
+            return;
+        };
+        pool.modify_liquidity(
+            event.tickLower.as_i32(),
+            event.tickUpper.as_i32(),
+            liquidity_delta,
+        );
+    } else if *event_sig == UniswapV4Events::Swap::SIGNATURE_HASH {
+        let Ok(event) = UniswapV4Events::Swap::decode_log(log.as_ref(), true) else {
+            warn!("Failed to decode V4 swap event of pool {}", pool.pool_id);
+            return;
+        };
+        pool.sqrt_price = U256::from(event.sqrtPriceX96);
+        pool.liquidity = event.liquidity;
+        pool.tick = event.tick.as_i32();
+        pool.fee = event.fee.to();
+    }
+}
@@ -0,0 +1,137 @@
+//! Token Metadata
+//!
+//! Reads ERC20 symbols and decimals in batches through Multicall3, so the tokens of a whole
+//! batch of pools are covered by a few calls instead of one or two calls per token.
+
+use alloy::network::Network;
+use alloy::primitives::{address, Address};
+use alloy::providers::Provider;
+use alloy::sol_types::{SolCall, SolValue};
+use alloy::transports::Transport;
+use anyhow::Result;
+use dashmap::DashMap;
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::pools::gen::{Multicall3, ERC20};
+
+/// Multicall3 address, the same on every chain it is deployed on
+const MULTICALL3: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+/// Tokens covered by a single multicall, every token takes three calls
+const TOKENS_PER_CALL: usize = 200;
+
+/// Symbol and decimals of the tokens. Duplicates are only read once and the name stands in
+/// for an empty symbol. Tokens without a readable symbol or decimals are left out of the map
+pub(crate) async fn fetch_token_metadata<P, T, N>(
+    provider: &Arc<P>,
+    tokens: &[Address],
+) -> Result<HashMap<Address, (String, u8)>>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let mut seen = HashSet::new();
+    let tokens: Vec<Address> = tokens.iter().copied().filter(|token| seen.insert(*token)).collect();
+
+    let multicall = Multicall3::new(MULTICALL3, provider);
+    let mut metadata = HashMap::new();
+    for chunk in tokens.chunks(TOKENS_PER_CALL) {
+        let calls = chunk
+            .iter()
+            .flat_map(|token| {
+                [
+                    ERC20::symbolCall {}.abi_encode(),
+                    ERC20::nameCall {}.abi_encode(),
+                    ERC20::decimalsCall {}.abi_encode(),
+                ]
+                .map(|call_data| Multicall3::Call3 {
+                    target: *token,
+                    allowFailure: true,
+                    callData: call_data.into(),
+                })
+            })
+            .collect();
+        let Multicall3::aggregate3Return { returnData: results } =
+            multicall.aggregate3(calls).call().await?;
+
+        for (token, results) in chunk.iter().zip(results.chunks(3)) {
+            let [symbol, name, decimals] = results else {
+                continue;
+            };
+            let text = |result: &Multicall3::CallResult| {
+                result.success.then(|| decode_symbol(&result.returnData)).flatten()
+            };
+            let decimals = decimals
+                .success
+                .then(|| ERC20::decimalsCall::abi_decode_returns(&decimals.returnData, false).ok())
+                .flatten();
+            if let (Some(symbol), Some(decimals)) = (text(symbol).or_else(|| text(name)), decimals)
+            {
+                metadata.insert(*token, (symbol, decimals._0));
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+/// Symbol and decimals of the tokens, reading only the ones missing from `cache` and adding
+/// them to it. Popular tokens show up in most batches of a sync, so after the first batches
+/// they are served from the cache. Tokens that could not be read are left out of the map
+pub(crate) async fn cached_token_metadata<P, T, N>(
+    provider: &Arc<P>,
+    tokens: &[Address],
+    cache: &DashMap<Address, (String, u8)>,
+) -> HashMap<Address, (String, u8)>
+where
+    P: Provider<T, N> + Sync + 'static,
+    T: Transport + Sync + Clone,
+    N: Network,
+{
+    let (mut metadata, missing) = split_cached(tokens, cache);
+    if missing.is_empty() {
+        return metadata;
+    }
+    match fetch_token_metadata(provider, &missing).await {
+        Ok(fetched) => {
+            for (token, entry) in fetched {
+                cache.insert(token, entry.clone());
+                metadata.insert(token, entry);
+            }
+        }
+        Err(e) => warn!("Failed to batch token metadata, reading tokens one by one: {}", e),
+    }
+    metadata
+}
+
+/// Split the tokens into the cached metadata and the tokens that still have to be read
+pub(crate) fn split_cached(
+    tokens: &[Address],
+    cache: &DashMap<Address, (String, u8)>,
+) -> (HashMap<Address, (String, u8)>, Vec<Address>) {
+    let mut cached = HashMap::new();
+    let mut missing = Vec::new();
+    for token in tokens {
+        match cache.get(token) {
+            Some(entry) => {
+                cached.insert(*token, entry.clone());
+            }
+            None => missing.push(*token),
+        }
+    }
+    (cached, missing)
+}
+
+/// Decode the result of `symbol()` or `name()`. Most tokens return a string, but some older
+/// ones such as MKR return a zero padded bytes32. Empty values are None
+pub(crate) fn decode_symbol(data: &[u8]) -> Option<String> {
+    // an abi encoded string takes at least two words, so a single word is a bytes32
+    let symbol = if data.len() == 32 {
+        let end = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+        String::from_utf8(data[..end].to_vec()).ok()?
+    } else {
+        String::abi_decode(data, false).ok()?
+    };
+    (!symbol.is_empty()).then_some(symbol)
+}
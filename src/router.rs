@@ -0,0 +1,102 @@
+//! Swap Routing
+//!
+//! Finds the best path for a swap across a set of synced pools. Every hop is quoted with
+//! `AmmSimulate` against the pool state as synced, so a path never moves the pools it quotes.
+//! Pools holding more than two tokens route between their token0 and token1.
+
+use alloy::primitives::{Address, U256};
+use std::collections::HashMap;
+
+use crate::pools::{AmmSimulate, Pool, PoolInfo};
+
+/// Routes swaps over a token adjacency index of the pools
+pub struct Router {
+    pools: Vec<Pool>,
+    /// Token to the indices of the pools that swap it
+    adjacency: HashMap<Address, Vec<usize>>,
+}
+
+impl Router {
+    pub fn new(pools: Vec<Pool>) -> Self {
+        let mut adjacency: HashMap<Address, Vec<usize>> = HashMap::new();
+        for (index, pool) in pools.iter().enumerate() {
+            let (token0, token1) = pool.tokens();
+            if token0 == token1 {
+                continue;
+            }
+            adjacency.entry(token0).or_default().push(index);
+            adjacency.entry(token1).or_default().push(index);
+        }
+        Self { pools, adjacency }
+    }
+
+    /// The pools the router quotes against
+    pub fn pools(&self) -> &[Pool] {
+        &self.pools
+    }
+
+    /// Path with the largest output for swapping `amount_in` of `token_in` to `token_out`
+    /// using at most `max_hops` pools. Returns the addresses of the pools in swap order and
+    /// the output amount, None if no path within `max_hops` can fill the swap
+    pub fn best_amount_out(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        max_hops: usize,
+    ) -> Option<(Vec<Address>, U256)> {
+        if token_in == token_out {
+            return None;
+        }
+        let mut best = None;
+        let mut path = Vec::new();
+        let mut visited = vec![token_in];
+        self.search(token_in, token_out, amount_in, max_hops, &mut path, &mut visited, &mut best);
+        best
+    }
+
+    // Depth first search over the pools of `token`, keeping the best complete path. Tokens
+    // already on the path are not revisited so a route never cycles
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        token: Address,
+        token_out: Address,
+        amount: U256,
+        hops_left: usize,
+        path: &mut Vec<Address>,
+        visited: &mut Vec<Address>,
+        best: &mut Option<(Vec<Address>, U256)>,
+    ) {
+        if hops_left == 0 {
+            return;
+        }
+        let Some(pools) = self.adjacency.get(&token) else {
+            return;
+        };
+        for &index in pools {
+            let pool = &self.pools[index];
+            let (token0, token1) = pool.tokens();
+            let next = if token == token0 { token1 } else { token0 };
+            if visited.contains(&next) {
+                continue;
+            }
+            let Some(amount_out) = pool.amount_out(token, amount).filter(|out| !out.is_zero())
+            else {
+                continue;
+            };
+
+            path.push(pool.address());
+            if next == token_out {
+                if best.as_ref().is_none_or(|(_, best_out)| amount_out > *best_out) {
+                    *best = Some((path.clone(), amount_out));
+                }
+            } else {
+                visited.push(next);
+                self.search(next, token_out, amount_out, hops_left - 1, path, visited, best);
+                visited.pop();
+            }
+            path.pop();
+        }
+    }
+}
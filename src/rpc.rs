@@ -1,32 +1,52 @@
-use alloy::network::Network;
-use alloy::primitives::Address;
+use alloy::eips::BlockId;
+use alloy::network::{Network, TransactionBuilder};
+use alloy::primitives::{keccak256, Address, Bytes, B256, U256};
 use alloy::providers::Provider;
 use alloy::rpc::types::{Filter, Log};
-use alloy::sol_types::SolEvent;
+use alloy::sol_types::{SolEvent, SolValue};
 use alloy::transports::Transport;
 use anyhow::anyhow;
 use anyhow::Result;
 use futures::StreamExt;
+use dashmap::DashMap;
 use indicatif::ProgressBar;
-use log::info;
+use log::{info, warn};
 use rand::Rng;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::{Mutex, Semaphore};
 use tokio::time::{interval, Duration};
 
 use crate::events::*;
+use crate::log_cache::LogCache;
 use crate::pools::pool_builder;
 use crate::pools::pool_structures::balancer_v2_structure::process_balance_data;
+use crate::pools::pool_structures::maverick_structure::process_bin_data;
 use crate::pools::pool_structures::v2_structure::process_sync_data;
-use crate::pools::pool_structures::v3_structure::process_tick_data;
+use crate::pools::pool_structures::v3_structure::{position_near_price, process_tick_data};
+use crate::pools::pool_structures::v4_structure::process_v4_data;
 use crate::pools::PoolFetcher;
 use crate::util::create_progress_bar;
 use crate::{Chain, Pool, PoolInfo, PoolType};
 
-// Retry constants
-const MAX_RETRIES: u32 = 5;
-const INITIAL_BACKOFF: u64 = 1000; // 1 second
+/// Retry behavior of failed rpc requests. The backoff doubles after every retry and a small
+/// random jitter is added to each wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Number of retries before a request is given up on
+    pub retry_limit: usize,
+    /// Wait before the first retry in milliseconds
+    pub initial_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            retry_limit: 5,
+            initial_backoff_ms: 1000,
+        }
+    }
+}
 
 // Define event configurations
 #[derive(Debug)]
@@ -37,9 +57,19 @@ struct EventConfig {
     requires_initial_sync: bool,
 }
 
+/// Restriction of a log request to a set of pools
+#[derive(Debug, PartialEq)]
+pub(crate) enum LogScope {
+    /// Logs emitted by the pool contracts
+    Emitters(Vec<Address>),
+    /// Logs of a singleton with the pool id as the first topic
+    PoolIds(Vec<B256>),
+}
+
 pub struct Rpc;
 impl Rpc {
     // Fetch all pool addresses for the protocol
+    #[allow(clippy::too_many_arguments)]
     pub async fn fetch_pool_addrs<P, T, N>(
         start_block: u64,
         end_block: u64,
@@ -47,18 +77,25 @@ impl Rpc {
         fetcher: Arc<dyn PoolFetcher>,
         chain: Chain,
         rate_limit: u64,
+        log_cache: Option<Arc<LogCache>>,
+        retry: RetryConfig,
+        address_batch_size: Option<u64>,
     ) -> Result<Vec<Address>>
     where
         P: Provider<T, N> + 'static,
         T: Transport + Clone + 'static,
         N: Network,
     {
+        let factory = fetcher
+            .factory_address(chain.clone())
+            .ok_or_else(|| anyhow!("{} is not supported on {}", fetcher.pool_type(), chain))?;
+
         // fetch all of the logs
         let filter = Filter::new()
-            .address(fetcher.factory_address(chain))
+            .address(factory)
             .event(fetcher.pair_created_signature());
 
-        let step_size: u64 = 10000;
+        let step_size = address_batch_size.unwrap_or(10000);
         let num_tasks = end_block / step_size;
         let pb_info = format!(
             "{} Address Sync. Block range {}-{}",
@@ -72,11 +109,13 @@ impl Rpc {
         let logs = Rpc::fetch_event_logs(
             start_block,
             end_block,
-            10000,
+            step_size,
             provider,
             rate_limit,
             progress_bar,
             filter,
+            log_cache,
+            retry,
         )
         .await?;
 
@@ -88,26 +127,118 @@ impl Rpc {
         anyhow::Ok(addresses)
     }
 
+    // List the pools a factory registered from `start_index` on, reading its registry at
+    // `block`. Returns the addresses along with the registry size to continue from next time.
+    // A registry smaller than `start_index` was reorged and is listed again from the start
+    pub async fn enumerate_pool_addrs<P, T, N>(
+        provider: Arc<P>,
+        fetcher: Arc<dyn PoolFetcher>,
+        chain: Chain,
+        rate_limit: u64,
+        start_index: u64,
+        block: u64,
+    ) -> Result<(Vec<Address>, u64)>
+    where
+        P: Provider<T, N> + 'static,
+        T: Transport + Clone + 'static,
+        N: Network,
+    {
+        let registry = fetcher
+            .pool_registry()
+            .ok_or_else(|| anyhow!("{} has no pool registry", fetcher.pool_type()))?;
+        let factory = fetcher
+            .factory_address(chain.clone())
+            .ok_or_else(|| anyhow!("{} is not supported on {}", fetcher.pool_type(), chain))?;
+        let count: u64 = Rpc::call_registry(
+            provider.clone(),
+            factory,
+            registry_call(registry.count, None),
+            block,
+        )
+        .await?
+        .to();
+        let start_index = if start_index > count { 0 } else { start_index };
+
+        let progress_bar = create_progress_bar(
+            count - start_index,
+            format!(
+                "{} Address Sync. Registry of {} pools from {}",
+                fetcher.pool_type(),
+                count,
+                start_index
+            ),
+        );
+        let mut stream = futures::stream::iter((start_index..count).map(|index| {
+            let provider = provider.clone();
+            let pb = progress_bar.clone();
+            async move {
+                let call = registry_call(registry.list, Some(index));
+                let word = Rpc::call_registry(provider, factory, call, block).await?;
+                pb.inc(1);
+                anyhow::Ok(Address::from_word(word.into()))
+            }
+        }))
+        .buffered(rate_limit.max(1) as usize);
+
+        let mut addresses = Vec::with_capacity((count - start_index) as usize);
+        while let Some(address) = stream.next().await {
+            addresses.push(address?);
+        }
+        progress_bar.finish_and_clear();
+        Ok((addresses, count))
+    }
+
+    // Call a registry getter at `block` and return the word it returned
+    async fn call_registry<P, T, N>(
+        provider: Arc<P>,
+        factory: Address,
+        input: Bytes,
+        block: u64,
+    ) -> Result<U256>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        let tx = N::TransactionRequest::default()
+            .with_to(factory)
+            .with_input(input);
+        let output = provider.call(&tx).block(BlockId::number(block)).await?;
+        Ok(U256::abi_decode(&output, false)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn populate_pools<P, T, N>(
         pool_addrs: Vec<Address>,
         provider: Arc<P>,
         pool: PoolType,
         fetcher: Arc<dyn PoolFetcher>,
         rate_limit: u64,
-        chain: Chain
+        chain: Chain,
+        concurrency: usize,
+        token_list: Arc<HashMap<Address, String>>,
+        token_cache: Arc<DashMap<Address, (String, u8)>>,
+        info_batch_size: Option<usize>,
+        retry: RetryConfig,
     ) -> Result<Vec<Pool>>
     where
         P: Provider<T, N> + 'static,
         T: Transport + Clone + 'static,
         N: Network,
     {
+        let factory = fetcher
+            .factory_address(chain.clone())
+            .ok_or_else(|| anyhow!("{} is not supported on {}", pool, chain))?;
+
         // data batch size for contract calls
-        let batch_size = if pool.is_balancer() { 10 } else { 50 };
+        let batch_size = info_batch_size
+            .unwrap_or(if pool.is_balancer() { 10 } else { 50 })
+            .max(1);
 
         // informational and rate limiting initialization
-        let total_tasks = (pool_addrs.len() + batch_size - 1) / batch_size; // Ceiling division
+        let total_tasks = pool_addrs.len().div_ceil(batch_size);
         let progress_bar = create_progress_bar(total_tasks as u64, format!("{} data sync", pool));
-        let semaphore = Arc::new(Semaphore::new(rate_limit as usize));
+        let semaphore = Arc::new(Semaphore::new(concurrency));
         let interval = Arc::new(tokio::sync::Mutex::new(interval(Duration::from_secs_f64(
             1.0 / rate_limit as f64,
         ))));
@@ -125,12 +256,14 @@ impl Rpc {
             let fetcher = fetcher.clone();
             let interval = interval.clone();
             let data = fetcher.get_pool_repr();
+            let token_list = token_list.clone();
+            let token_cache = token_cache.clone();
 
             async move {
                 let _permit = sem.acquire().await.unwrap();
                 interval.lock().await.tick().await;
                 let mut retry_count = 0;
-                let mut backoff = 1000; // Initial backoff of 1 second
+                let mut backoff = retry.initial_backoff_ms;
                 loop {
                     // try building pools from this set of addresses
                     match pool_builder::build_pools(
@@ -138,7 +271,10 @@ impl Rpc {
                         chunk.clone(),
                         pool,
                         data.clone(),
-                        chain
+                        factory,
+                        &token_list,
+                        &token_cache,
+                        retry,
                     )
                     .await
                     {
@@ -148,7 +284,7 @@ impl Rpc {
                             return anyhow::Ok::<Vec<Pool>>(populated_pools);
                         }
                         Err(e) => {
-                            if retry_count >= MAX_RETRIES {
+                            if retry_count >= retry.retry_limit {
                                 info!("Failed to populate pools data: {}", e);
                                 drop(provider);
                                 return Ok(Vec::new());
@@ -159,12 +295,20 @@ impl Rpc {
                             retry_count += 1;
                             backoff *= 2; // Exponential backoff
                         }
-                        _ => continue,
+                        // an empty result is retried, but give up if the batch has no valid pools
+                        _ => {
+                            if retry_count >= retry.retry_limit {
+                                pb.inc(1);
+                                drop(provider);
+                                return Ok(Vec::new());
+                            }
+                            retry_count += 1;
+                        }
                     }
                 }
             }
         }))
-        .buffer_unordered(rate_limit as usize);
+        .buffered(concurrency); // Keep chunk order so results are reproducible
 
         let mut all_pools = Vec::new();
 
@@ -178,6 +322,70 @@ impl Rpc {
         Ok(all_pools)
     }
 
+    // Build the V4 pools among `pool_addrs` from the `Initialize` logs in the block range. V4
+    // pools have no contract to read their key from, so the range must cover their creation
+    #[allow(clippy::too_many_arguments)]
+    pub async fn populate_v4_pools<P, T, N>(
+        start_block: u64,
+        end_block: u64,
+        pool_addrs: Vec<Address>,
+        provider: Arc<P>,
+        fetcher: Arc<dyn PoolFetcher>,
+        chain: Chain,
+        rate_limit: u64,
+        log_cache: Option<Arc<LogCache>>,
+        token_list: Arc<HashMap<Address, String>>,
+        token_cache: Arc<DashMap<Address, (String, u8)>>,
+        retry: RetryConfig,
+        address_batch_size: Option<u64>,
+    ) -> Result<Vec<Pool>>
+    where
+        P: Provider<T, N> + Sync + 'static,
+        T: Transport + Sync + Clone + 'static,
+        N: Network,
+    {
+        let pool_manager = fetcher
+            .factory_address(chain.clone())
+            .ok_or_else(|| anyhow!("{} is not supported on {}", fetcher.pool_type(), chain))?;
+        let filter = Filter::new()
+            .address(pool_manager)
+            .event(fetcher.pair_created_signature());
+
+        let step_size = address_batch_size.unwrap_or(10000);
+        let num_tasks = end_block.saturating_sub(start_block) / step_size;
+        let pb_info = format!(
+            "{} data sync. Block range {}-{}",
+            fetcher.pool_type(),
+            start_block,
+            end_block
+        );
+        let progress_bar = Arc::new(create_progress_bar(num_tasks, pb_info));
+        let logs = Rpc::fetch_event_logs(
+            start_block,
+            end_block,
+            step_size,
+            provider.clone(),
+            rate_limit,
+            progress_bar,
+            filter,
+            log_cache,
+            retry,
+        )
+        .await?;
+
+        let wanted: HashSet<Address> = pool_addrs.into_iter().collect();
+        let logs: Vec<Log> = Rpc::order_logs(logs)
+            .into_values()
+            .flatten()
+            .filter(|log| wanted.contains(&fetcher.log_to_address(&log.inner)))
+            .collect();
+        pool_builder::build_v4_pools(&provider, &logs, &token_list, &token_cache).await
+    }
+
+    // Apply the liquidity events in the range to the pools. Returns the last block with an event
+    // for each pool that had activity. With `scoped` set only the logs of the given pools are
+    // requested, otherwise every log of the pool type is fetched and the unknown ones dropped
+    #[allow(clippy::too_many_arguments)]
     pub async fn populate_liquidity<P, T, N>(
         start_block: u64,
         end_block: u64,
@@ -186,15 +394,23 @@ impl Rpc {
         pool_type: PoolType,
         rate_limit: u64,
         is_initial_sync: bool,
-    ) -> anyhow::Result<()>
+        log_cache: Option<Arc<LogCache>>,
+        tick_window: Option<i32>,
+        liquidity_step: Option<u64>,
+        retry: RetryConfig,
+        scoped: bool,
+    ) -> anyhow::Result<HashMap<Address, u64>>
     where
         P: Provider<T, N> + Sync + 'static,
         T: Transport + Sync + Clone,
         N: Network,
     {
+        let mut last_activity = HashMap::new();
         if pools.is_empty() {
-            return anyhow::Ok(());
+            return anyhow::Ok(last_activity);
         }
+        // pools whose state is read at the end block instead of being replayed from the logs
+        let mut touched = HashSet::new();
 
         let address_to_index: HashMap<Address, usize> = pools
             .iter()
@@ -206,10 +422,14 @@ impl Rpc {
         let mut current_block = start_block;
 
         // get the configuration for this sync and config we should sync
-        let config = Rpc::get_event_config(pool_type, is_initial_sync);
+        let mut config = Rpc::get_event_config(pool_type, is_initial_sync);
         if is_initial_sync && config.requires_initial_sync {
-            return anyhow::Ok(());
+            return anyhow::Ok(last_activity);
         }
+        if let Some(step) = liquidity_step {
+            config.step_size = step.max(1);
+        }
+        let scope = scoped.then(|| Rpc::pool_log_scope(pools));
 
         // construct the progress bar
         let num_tasks = (end_block - start_block) / config.step_size;
@@ -225,11 +445,14 @@ impl Rpc {
 
             let logs = Rpc::fetch_logs_for_config(
                 &config,
+                scope.as_ref(),
                 current_block,
                 batch_end,
                 provider.clone(),
                 progress_bar.clone(),
                 rate_limit,
+                log_cache.clone(),
+                retry,
             )
             .await?;
 
@@ -242,28 +465,39 @@ impl Rpc {
                 create_progress_bar(logs.len().try_into().unwrap(), processing_pb_info);
 
             // Process logs immediately after fetching
-            let mut ordered_logs: BTreeMap<u64, Vec<Log>> = BTreeMap::new();
-            for log in logs {
-                if let Some(block_number) = log.block_number {
-                    ordered_logs.entry(block_number).or_default().push(log);
-                }
-            }
+            let ordered_logs = Rpc::order_logs(logs);
 
             // Process logs in order
             for (_, log_group) in ordered_logs {
                 for log in log_group {
-                    let address = log.address();
+                    let address = Rpc::log_pool_address(&log);
                     if let Some(&index) = address_to_index.get(&address) {
+                        if let Some(block) = log.block_number {
+                            last_activity.insert(address, block);
+                        }
                         if let Some(pool) = pools.get_mut(index) {
                             if pool_type.is_v3() {
-                                process_tick_data(
-                                    pool.get_v3_mut().unwrap(),
-                                    log,
-                                    pool_type,
-                                    is_initial_sync,
-                                );
+                                let pool = pool.get_v3_mut().unwrap();
+                                let skip = is_initial_sync
+                                    && tick_window
+                                        .is_some_and(|window| !position_near_price(pool, &log, window));
+                                if skip {
+                                    // later burns of the skipped positions must not underflow
+                                    pool.ticks_truncated = true;
+                                } else {
+                                    process_tick_data(pool, log, pool_type, is_initial_sync);
+                                }
+                            } else if pool_type.is_v4() {
+                                process_v4_data(pool.get_v4_mut().unwrap(), log);
+                            } else if pool_type.is_maverick() {
+                                process_bin_data(pool.get_maverick_mut().unwrap(), log);
+                                if pool_type == PoolType::MaverickV2 {
+                                    touched.insert(index);
+                                }
                             } else if pool_type.is_balancer() {
                                 process_balance_data(pool.get_balancer_mut().unwrap(), log);
+                            } else if pool_type.is_curve_stable() || pool_type.is_curve_two() {
+                                touched.insert(index);
                             } else {
                                 process_sync_data(pool.get_v2_mut().unwrap(), log, pool_type);
                             }
@@ -276,9 +510,64 @@ impl Rpc {
             processing_progress_bar.finish_and_clear();
             current_block = batch_end + 1;
         }
-        anyhow::Ok(())
+
+        // stableswap events leave out the admin fee, cryptoswap events the repegged price scale
+        // and invariant and maverick v2 events the bin reserves, the pool views have the exact
+        // state
+        futures::stream::iter(
+            pools
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| touched.contains(index))
+                .map(|(_, pool)| pool.refresh_state(provider.clone(), end_block)),
+        )
+        .buffer_unordered(rate_limit.max(1) as usize)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+        anyhow::Ok(last_activity)
+    }
+
+    // Fetch all of the liquidity events for the pool type in the block range without
+    // applying them to any pools
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fetch_pool_events<P, T, N>(
+        start_block: u64,
+        end_block: u64,
+        provider: Arc<P>,
+        pool_type: PoolType,
+        rate_limit: u64,
+        log_cache: Option<Arc<LogCache>>,
+        retry: RetryConfig,
+    ) -> Result<Vec<Log>>
+    where
+        P: Provider<T, N> + 'static,
+        T: Transport + Clone + 'static,
+        N: Network,
+    {
+        let config = Rpc::get_event_config(pool_type, false);
+        let num_tasks = end_block.saturating_sub(start_block) / config.step_size;
+        let pb_info = format!(
+            "{} Activity scan. Block range {}-{}",
+            pool_type, start_block, end_block
+        );
+        let progress_bar = Arc::new(create_progress_bar(num_tasks, pb_info));
+        Rpc::fetch_logs_for_config(
+            &config,
+            None,
+            start_block,
+            end_block,
+            provider,
+            progress_bar,
+            rate_limit,
+            log_cache,
+            retry,
+        )
+        .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn fetch_event_logs<T, N, P>(
         start_block: u64,
         end_block: u64,
@@ -287,6 +576,8 @@ impl Rpc {
         rate_limit: u64,
         progress_bar: Arc<ProgressBar>,
         filter: Filter,
+        log_cache: Option<Arc<LogCache>>,
+        retry: RetryConfig,
     ) -> anyhow::Result<Vec<Log>>
     where
         T: Transport + Clone,
@@ -310,15 +601,28 @@ impl Rpc {
                 let pb = progress_bar.clone();
                 let interval = interval.clone();
                 let filter = filter.clone();
+                let log_cache = log_cache.clone();
 
                 async move {
+                    let filter = filter.from_block(from_block).to_block(to_block);
+
+                    // cached ranges do not count against the rate limit
+                    if let Some(logs) = log_cache.as_ref().and_then(|cache| cache.get(&filter)) {
+                        pb.inc(1);
+                        return Ok(logs);
+                    }
+
                     let _permit = sem.acquire().await.unwrap();
                     interval.lock().await.tick().await;
 
-                    let filter = filter.from_block(from_block).to_block(to_block);
-                    let logs = Rpc::get_logs_with_retry(provider, &filter).await;
-                    if logs.is_ok() {
+                    let logs = Rpc::get_logs_with_retry(provider, &filter, retry).await;
+                    if let Ok(logs) = &logs {
                         pb.inc(1);
+                        if let Some(cache) = &log_cache {
+                            if let Err(e) = cache.insert(&filter, logs) {
+                                warn!("Failed to write log cache entry: {}", e);
+                            }
+                        }
                     }
                     logs
                 }
@@ -341,20 +645,29 @@ impl Rpc {
     // Given a config and a range, fetch all the logs for it
     // This is a top level call which will delegate to individual fetching
     // functions to get the logs and to ensure retries on failure
+    #[allow(clippy::too_many_arguments)]
     async fn fetch_logs_for_config<P, T, N>(
         config: &EventConfig,
+        scope: Option<&LogScope>,
         start_block: u64,
         end_block: u64,
         provider: Arc<P>,
         progress_bar: Arc<ProgressBar>,
         rate_limit: u64,
+        log_cache: Option<Arc<LogCache>>,
+        retry: RetryConfig,
     ) -> Result<Vec<Log>>
     where
         P: Provider<T, N> + 'static,
         T: Transport + Clone + 'static,
         N: Network,
     {
-        let filter = Filter::new().events(config.events.iter().copied());
+        let mut filter = Filter::new().events(config.events.iter().copied());
+        match scope {
+            Some(LogScope::Emitters(addresses)) => filter = filter.address(addresses.clone()),
+            Some(LogScope::PoolIds(pool_ids)) => filter = filter.topic1(pool_ids.clone()),
+            None => {}
+        }
         Rpc::fetch_event_logs(
             start_block,
             end_block,
@@ -363,6 +676,8 @@ impl Rpc {
             rate_limit,
             progress_bar,
             filter,
+            log_cache,
+            retry,
         )
         .await
     }
@@ -371,6 +686,7 @@ impl Rpc {
     async fn get_logs_with_retry<P, T, N>(
         provider: Arc<P>,
         filter: &Filter,
+        retry: RetryConfig,
     ) -> anyhow::Result<Vec<Log>>
     where
         P: Provider<T, N> + 'static,
@@ -378,7 +694,7 @@ impl Rpc {
         N: Network,
     {
         let mut retry_count = 0;
-        let mut backoff = INITIAL_BACKOFF;
+        let mut backoff = retry.initial_backoff_ms;
 
         loop {
             match provider.get_logs(filter).await {
@@ -386,7 +702,7 @@ impl Rpc {
                     return anyhow::Ok(logs);
                 }
                 Err(e) => {
-                    if retry_count >= MAX_RETRIES {
+                    if retry_count >= retry.retry_limit {
                         return Err(anyhow!(e));
                     }
                     let jitter = rand::thread_rng().gen_range(0..=100);
@@ -401,6 +717,19 @@ impl Rpc {
 
     fn get_event_config(pool_type: PoolType, is_initial_sync: bool) -> EventConfig {
         match pool_type {
+            // algebra pools share the V3 tick events and add their own swap and fee events
+            PoolType::Camelot if !is_initial_sync => EventConfig {
+                events: &[
+                    DataEvents::Mint::SIGNATURE,
+                    DataEvents::Burn::SIGNATURE,
+                    DataEvents::Swap::SIGNATURE,
+                    AlgebraEvents::Swap::SIGNATURE,
+                    AlgebraEvents::Fee::SIGNATURE,
+                ],
+                step_size: 50,
+                description: "Full sync",
+                requires_initial_sync: true,
+            },
             pt if pt.is_v3() => {
                 if is_initial_sync {
                     EventConfig {
@@ -422,6 +751,70 @@ impl Rpc {
                     }
                 }
             }
+            // pools are built at their initialization, so every event is replayed from there
+            pt if pt.is_v4() => EventConfig {
+                events: &[
+                    UniswapV4Events::ModifyLiquidity::SIGNATURE,
+                    UniswapV4Events::Swap::SIGNATURE,
+                ],
+                step_size: if is_initial_sync { 1500 } else { 250 },
+                description: "Tick sync",
+                requires_initial_sync: false,
+            },
+            PoolType::MaverickV1 => EventConfig {
+                events: &[
+                    MaverickV1Events::AddLiquidity::SIGNATURE,
+                    MaverickV1Events::RemoveLiquidity::SIGNATURE,
+                    MaverickV1Events::Swap::SIGNATURE,
+                ],
+                step_size: if is_initial_sync { 1500 } else { 250 },
+                description: "Bin sync",
+                requires_initial_sync: false, // bins are only known from the logs
+            },
+            // the logs name the bins, their reserves are read from the touched pools, see
+            // `populate_liquidity`
+            PoolType::MaverickV2 => EventConfig {
+                events: &[
+                    MaverickV2Events::PoolAddLiquidity::SIGNATURE,
+                    MaverickV2Events::PoolRemoveLiquidity::SIGNATURE,
+                    MaverickV2Events::PoolSwap::SIGNATURE,
+                ],
+                step_size: if is_initial_sync { 1500 } else { 250 },
+                description: "Bin sync",
+                requires_initial_sync: false,
+            },
+            // the touched pools have their balances read again, see `populate_liquidity`
+            pt if pt.is_curve_stable() => EventConfig {
+                events: &[
+                    CurveStableSwapEvents::TokenExchange::SIGNATURE,
+                    CurveStableSwapEvents::AddLiquidity::SIGNATURE,
+                    CurveStableSwapEvents::RemoveLiquidity::SIGNATURE,
+                    CurveStableSwapEvents::RemoveLiquidityOne::SIGNATURE,
+                    CurveStableSwapEvents::RemoveLiquidityImbalance::SIGNATURE,
+                    CurveStableSwapEvents::RampA::SIGNATURE,
+                    CurveStableSwapEvents::StopRampA::SIGNATURE,
+                    CurveStableSwapEvents::ApplyNewFee::SIGNATURE,
+                ],
+                step_size: 250,
+                description: "Balance sync",
+                requires_initial_sync: true,
+            },
+            // the touched pools have their state read again, see `populate_liquidity`
+            pt if pt.is_curve_two() => EventConfig {
+                events: &[
+                    CurveTwoCryptoEvents::TokenExchange::SIGNATURE,
+                    CurveTwoCryptoEvents::AddLiquidity::SIGNATURE,
+                    CurveTwoCryptoEvents::RemoveLiquidity::SIGNATURE,
+                    CurveTwoCryptoEvents::RemoveLiquidityOne::SIGNATURE,
+                    CurveTwoCryptoEvents::ClaimAdminFee::SIGNATURE,
+                    CurveTwoCryptoEvents::NewParameters::SIGNATURE,
+                    CurveTwoCryptoEvents::RampAgamma::SIGNATURE,
+                    CurveTwoCryptoEvents::StopRampA::SIGNATURE,
+                ],
+                step_size: 250,
+                description: "State sync",
+                requires_initial_sync: true,
+            },
             pt if pt.is_balancer() => EventConfig {
                 events: &[BalancerV2Event::Swap::SIGNATURE],
                 step_size: 5000,
@@ -437,6 +830,55 @@ impl Rpc {
         }
     }
 
+    // Narrow the liquidity logs to the pools. Pools living in a singleton (balancer vault, v4
+    // PoolManager) share its address and are told apart by the pool id in the first topic
+    pub(crate) fn pool_log_scope(pools: &[Pool]) -> LogScope {
+        let pool_ids: Vec<B256> = pools
+            .iter()
+            .filter_map(|pool| match pool {
+                Pool::BalancerV2(pool) => Some(pool.pool_id),
+                Pool::UniswapV4(pool) => Some(pool.pool_id),
+                _ => None,
+            })
+            .collect();
+        if pool_ids.is_empty() {
+            LogScope::Emitters(pools.iter().map(|pool| pool.address()).collect())
+        } else {
+            LogScope::PoolIds(pool_ids)
+        }
+    }
+
+    // Address of the pool a liquidity log belongs to. Balancer swaps are emitted by the vault
+    // and V4 events by the PoolManager, the pool address is the first 20 bytes of the pool id
+    pub fn log_pool_address(log: &Log) -> Address {
+        let singleton_events = [
+            BalancerV2Event::Swap::SIGNATURE_HASH,
+            UniswapV4Events::ModifyLiquidity::SIGNATURE_HASH,
+            UniswapV4Events::Swap::SIGNATURE_HASH,
+        ];
+        if log.topic0().is_some_and(|topic| singleton_events.contains(topic)) {
+            if let Some(pool_id) = log.topics().get(1) {
+                return Address::from_slice(&pool_id[..20]);
+            }
+        }
+        log.address()
+    }
+
+    // Group logs by block and sort each block by log index. Logs are fetched concurrently so
+    // they must be put back into on chain order before they are applied to the pools
+    pub fn order_logs(logs: Vec<Log>) -> BTreeMap<u64, Vec<Log>> {
+        let mut ordered_logs: BTreeMap<u64, Vec<Log>> = BTreeMap::new();
+        for log in logs {
+            if let Some(block_number) = log.block_number {
+                ordered_logs.entry(block_number).or_default().push(log);
+            }
+        }
+        for log_group in ordered_logs.values_mut() {
+            log_group.sort_by_key(|log| log.log_index);
+        }
+        ordered_logs
+    }
+
     // Generate a range of blocks of step size distance
     pub fn get_block_range(step_size: u64, start_block: u64, end_block: u64) -> Vec<(u64, u64)> {
         if start_block == end_block {
@@ -462,3 +904,13 @@ impl Rpc {
         block_ranges
     }
 }
+
+// Calldata of a registry getter, the selector of the signature followed by the index argument
+// of the list getter
+pub(crate) fn registry_call(signature: &str, index: Option<u64>) -> Bytes {
+    let mut input = keccak256(signature)[..4].to_vec();
+    if let Some(index) = index {
+        input.extend(U256::from(index).abi_encode());
+    }
+    input.into()
+}
@@ -0,0 +1,293 @@
+//! Pool State Snapshots
+//!
+//! Reads the reserves of v2 style pools, the slot0 or global state of v3 style pools, and the
+//! vault balances of balancer pools straight from the chain. Nothing is discovered or cached,
+//! this is meant for callers that only need the spot state of a set of pools.
+
+use alloy::contract::Error as ContractError;
+use alloy::eips::BlockId;
+use alloy::network::Network;
+use alloy::primitives::{address, Address, Bytes, U256};
+use alloy::providers::Provider;
+use alloy::sol_types::SolCall;
+use alloy::transports::Transport;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Arc;
+
+use crate::errors::PoolSyncError;
+use crate::pools::gen::{MaverickV2Pool, PoolStateReader, StableSwapPool, TwoCryptoPool, Vault};
+use crate::pools::pool_builder::is_revert;
+use crate::pools::pool_structures::balancer_v2_structure::BalancerV2Pool;
+use crate::pools::pool_structures::maverick_structure::MaverickPool;
+use crate::pools::pool_structures::two_crypto_curve_structure::CurveTwoCryptoPool;
+use crate::{Pool, PoolInfo, PoolType};
+
+/// The balancer v2 vault, deployed at the same address on every chain
+const BALANCER_VAULT: Address = address!("BA12222222228d8Ba445958a75a0704d566BF2C8");
+
+/// On chain state of a pool at a given block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoolState {
+    /// Constant product pool reserves
+    V2 {
+        address: Address,
+        reserve0: U256,
+        reserve1: U256,
+    },
+    /// Concentrated liquidity pool price, tick and active liquidity
+    V3 {
+        address: Address,
+        sqrt_price: U256,
+        tick: i32,
+        liquidity: u128,
+    },
+    /// The address exposes neither a v2 nor a v3 interface
+    Unknown { address: Address },
+}
+
+impl PoolState {
+    pub fn address(&self) -> Address {
+        match self {
+            PoolState::V2 { address, .. }
+            | PoolState::V3 { address, .. }
+            | PoolState::Unknown { address } => *address,
+        }
+    }
+}
+
+/// Read the state of the pool at `address` as of `block`. V3 and Algebra are tried first since
+/// v2 pools have neither slot0 nor a global state
+pub(crate) async fn snapshot_pool<P, T, N>(
+    provider: Arc<P>,
+    address: Address,
+    block: BlockId,
+) -> Result<PoolState>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    let reader = PoolStateReader::new(address, provider);
+
+    let slot0 = reader.slot0().block(block).call_raw().await;
+    if let Some(slot0) = decode_leading::<PoolStateReader::slot0Call>(slot0)? {
+        let PoolStateReader::liquidityReturn { _0: liquidity } =
+            reader.liquidity().block(block).call().await?;
+        return Ok(PoolState::V3 {
+            address,
+            sqrt_price: U256::from(slot0.sqrtPriceX96),
+            tick: slot0.tick.as_i32(),
+            liquidity,
+        });
+    }
+
+    // algebra pools keep the price and tick in their global state instead
+    let state = reader.globalState().block(block).call_raw().await;
+    if let Some(state) = decode_leading::<PoolStateReader::globalStateCall>(state)? {
+        let PoolStateReader::liquidityReturn { _0: liquidity } =
+            reader.liquidity().block(block).call().await?;
+        return Ok(PoolState::V3 {
+            address,
+            sqrt_price: U256::from(state.price),
+            tick: state.tick.as_i32(),
+            liquidity,
+        });
+    }
+
+    let reserves = reader.getReserves().block(block).call_raw().await;
+    if let Some(reserves) = decode_leading::<PoolStateReader::getReservesCall>(reserves)? {
+        return Ok(PoolState::V2 {
+            address,
+            reserve0: reserves.reserve0,
+            reserve1: reserves.reserve1,
+        });
+    }
+
+    Ok(PoolState::Unknown { address })
+}
+
+/// Decode the leading words of a raw call result. Reverts and undecodable data mean the pool
+/// does not implement the call, any other error is passed on
+pub(crate) fn decode_leading<C: SolCall>(
+    result: Result<Bytes, ContractError>,
+) -> Result<Option<C::Return>> {
+    let data = match result {
+        Ok(data) => data,
+        Err(e) => {
+            let e = anyhow::Error::from(e);
+            return if is_revert(&e) { Ok(None) } else { Err(e) };
+        }
+    };
+    // decoding without validation ignores the extra fields the forks return
+    Ok(C::abi_decode_returns(&data, false).ok())
+}
+
+impl Pool {
+    /// Bring the pool up to date at `block` by reading its state from the chain instead of
+    /// replaying events. Updates the reserves of v2 pools, the price, tick and active liquidity
+    /// of v3 pools, the balances of balancer pools, the balances, rates, A and fee of
+    /// StableSwap pools, the balances, price scale, invariant and parameters of two coin
+    /// cryptoswap pools and the active tick and known bins of maverick v2 pools. V3 ticks are
+    /// not refreshed, and pool types without an on chain state read (tricrypto, maverick v1, v4)
+    /// are left as they are
+    pub async fn refresh_state<P, T, N>(
+        &mut self,
+        provider: Arc<P>,
+        block: u64,
+    ) -> Result<(), PoolSyncError>
+    where
+        P: Provider<T, N>,
+        T: Transport + Clone,
+        N: Network,
+    {
+        refresh_pool(provider, self, BlockId::number(block))
+            .await
+            .map_err(|e| PoolSyncError::ProviderError(e.to_string()))
+    }
+}
+
+// Read the spot state of the pool at `block` and apply it
+async fn refresh_pool<P, T, N>(provider: Arc<P>, pool: &mut Pool, block: BlockId) -> Result<()>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    if let Some(balancer) = pool.get_balancer_mut() {
+        let vault = Vault::new(BALANCER_VAULT, provider);
+        let Vault::getPoolTokensReturn {
+            tokens, balances, ..
+        } = vault
+            .getPoolTokens(balancer.pool_id)
+            .block(block)
+            .call()
+            .await?;
+        apply_vault_balances(balancer, &tokens, &balances);
+    } else if let Some(curve) = pool.get_curve_stable_mut() {
+        let reader = StableSwapPool::new(curve.address, provider);
+        curve.balances = reader.get_balances().block(block).call().await?._0;
+        curve.a = reader.A_precise().block(block).call().await?._0;
+        curve.fee = reader.fee().block(block).call().await?._0;
+        // older pools do not expose their rates, plain coin rates never change
+        if let Ok(rates) = reader.stored_rates().block(block).call().await {
+            curve.rates = rates._0;
+        }
+    } else if let Some(curve) = pool.get_curve_two_mut() {
+        read_two_crypto_state(provider.as_ref(), curve, block).await?;
+    } else if pool.pool_type() == PoolType::MaverickV2 {
+        read_maverick_v2_state(provider.as_ref(), pool.get_maverick_mut().unwrap(), block).await?;
+    } else if pool.is_v2() || pool.is_v3() {
+        let state = snapshot_pool(provider, pool.address(), block).await?;
+        apply_pool_state(pool, &state);
+    }
+    Ok(())
+}
+
+/// Read the balances, price scale, invariant and curve parameters of a two coin cryptoswap pool
+pub(crate) async fn read_two_crypto_state<P, T, N>(
+    provider: &P,
+    pool: &mut CurveTwoCryptoPool,
+    block: BlockId,
+) -> Result<()>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    let reader = TwoCryptoPool::new(pool.address, provider);
+    for (i, balance) in pool.balances.iter_mut().enumerate() {
+        *balance = reader.balances(U256::from(i)).block(block).call().await?._0;
+    }
+    pool.price_scale = reader.price_scale().block(block).call().await?._0;
+    pool.d = reader.D().block(block).call().await?._0;
+    pool.a = reader.A().block(block).call().await?._0;
+    pool.gamma = reader.gamma().block(block).call().await?._0;
+    pool.mid_fee = reader.mid_fee().block(block).call().await?._0;
+    pool.out_fee = reader.out_fee().block(block).call().await?._0;
+    pool.fee_gamma = reader.fee_gamma().block(block).call().await?._0;
+    Ok(())
+}
+
+/// Read the active tick and the reserves of the known bins of a maverick v2 pool. A bin owns
+/// the share of its tick reserves its balance is of the tick supply, bins left without reserves,
+/// such as merged ones, are removed
+pub(crate) async fn read_maverick_v2_state<P, T, N>(
+    provider: &P,
+    pool: &mut MaverickPool,
+    block: BlockId,
+) -> Result<()>
+where
+    P: Provider<T, N>,
+    T: Transport + Clone,
+    N: Network,
+{
+    let reader = MaverickV2Pool::new(pool.address, provider);
+    pool.active_tick = reader.getState().block(block).call().await?._0.activeTick;
+    let mut ticks = HashMap::new();
+    for (bin_id, bin) in pool.bins.iter_mut() {
+        let bin_id = u32::try_from(*bin_id)?;
+        let state = reader.getBin(bin_id).block(block).call().await?.bin;
+        let tick = match ticks.entry(state.tick) {
+            Entry::Occupied(tick) => tick.into_mut(),
+            Entry::Vacant(tick) => {
+                tick.insert(reader.getTick(state.tick).block(block).call().await?.tickState)
+            }
+        };
+        bin.lower_tick = state.tick;
+        bin.kind = state.kind;
+        (bin.reserve_a, bin.reserve_b) = if tick.totalSupply == 0 {
+            (U256::ZERO, U256::ZERO)
+        } else {
+            let share = |reserve: u128| {
+                U256::from(reserve) * U256::from(state.tickBalance) / U256::from(tick.totalSupply)
+            };
+            (share(tick.reserveA), share(tick.reserveB))
+        };
+    }
+    pool.bins.retain(|_, bin| !bin.reserve_a.is_zero() || !bin.reserve_b.is_zero());
+    Ok(())
+}
+
+/// Copy a snapshot onto the pool. States of the wrong kind for the pool are ignored
+pub(crate) fn apply_pool_state(pool: &mut Pool, state: &PoolState) {
+    match *state {
+        PoolState::V2 {
+            reserve0, reserve1, ..
+        } => {
+            if let Some(pool) = pool.get_v2_mut() {
+                pool.token0_reserves = reserve0;
+                pool.token1_reserves = reserve1;
+            }
+        }
+        PoolState::V3 {
+            sqrt_price,
+            tick,
+            liquidity,
+            ..
+        } => {
+            if let Some(pool) = pool.get_v3_mut() {
+                pool.sqrt_price = sqrt_price;
+                pool.tick = tick;
+                pool.liquidity = liquidity;
+            }
+        }
+        PoolState::Unknown { .. } => {}
+    }
+}
+
+/// Set the pool balances from the vault, which lists the tokens in its own order
+pub(crate) fn apply_vault_balances(
+    pool: &mut BalancerV2Pool,
+    tokens: &[Address],
+    balances: &[U256],
+) {
+    for (token, balance) in tokens.iter().zip(balances) {
+        if let Some(index) = pool.get_token_index(token) {
+            if let Some(slot) = pool.balances.get_mut(index) {
+                *slot = *balance;
+            }
+        }
+    }
+}
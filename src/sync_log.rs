@@ -0,0 +1,150 @@
+//! Structured Sync Logging
+//!
+//! Defines the events emitted while a sync is running and the sink trait used to consume them.
+//! When no sink is configured the events are written as human readable `log` lines instead.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::PoolType;
+
+/// A progress event emitted during synchronization
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEvent {
+    /// A sync has started for the configured pool types
+    SyncStarted {
+        chain: String,
+        pool_types: Vec<PoolType>,
+    },
+    /// A pool type is being synced over a block range
+    RangeStarted {
+        pool_type: PoolType,
+        start_block: u64,
+        end_block: u64,
+    },
+    /// Addresses of new pools were found and their data is about to be fetched
+    AddressesFetched { pool_type: PoolType, count: usize },
+    /// New pools were discovered for a pool type
+    PoolsDiscovered { pool_type: PoolType, count: usize },
+    /// A pool type has been synced up to a block
+    RangeSynced {
+        pool_type: PoolType,
+        block: u64,
+        total_pools: usize,
+    },
+    /// All pool types are synced and the caches have been written
+    SyncCompleted { block: u64, total_pools: usize },
+    /// The live sync applied the logs of a new block to the pools
+    LiveBlockSynced { block: u64, updated_pools: usize },
+}
+
+impl fmt::Display for LogEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogEvent::SyncStarted { chain, pool_types } => {
+                write!(f, "Syncing {} pool types on {}", pool_types.len(), chain)
+            }
+            LogEvent::RangeStarted {
+                pool_type,
+                start_block,
+                end_block,
+            } => write!(
+                f,
+                "Syncing {} from block {} to {}",
+                pool_type, start_block, end_block
+            ),
+            LogEvent::AddressesFetched { pool_type, count } => {
+                write!(f, "Found {} new {} pool addresses", count, pool_type)
+            }
+            LogEvent::PoolsDiscovered { pool_type, count } => {
+                write!(f, "Discovered {} new {} pools", count, pool_type)
+            }
+            LogEvent::RangeSynced {
+                pool_type,
+                block,
+                total_pools,
+            } => write!(
+                f,
+                "Synced {} {} pools to block {}",
+                total_pools, pool_type, block
+            ),
+            LogEvent::SyncCompleted { block, total_pools } => {
+                write!(f, "Sync complete: {} pools at block {}", total_pools, block)
+            }
+            LogEvent::LiveBlockSynced {
+                block,
+                updated_pools,
+            } => write!(f, "Live synced block {}, {} pools updated", block, updated_pools),
+        }
+    }
+}
+
+/// Receives the events emitted during a sync
+pub trait LogSink: Send + Sync {
+    fn emit(&self, event: &LogEvent);
+}
+
+/// Closures can be used as a sink, for example to drive a custom progress display
+impl<F: Fn(&LogEvent) + Send + Sync> LogSink for F {
+    fn emit(&self, event: &LogEvent) {
+        self(event)
+    }
+}
+
+/// Sink passing each event to several sinks in the order they were added
+pub(crate) struct FanOutSink {
+    sinks: Vec<Arc<dyn LogSink>>,
+}
+
+impl FanOutSink {
+    /// A single sink for `sinks`, None when there are none
+    pub(crate) fn combine(mut sinks: Vec<Arc<dyn LogSink>>) -> Option<Arc<dyn LogSink>> {
+        match sinks.len() {
+            0 => None,
+            1 => sinks.pop(),
+            _ => Some(Arc::new(FanOutSink { sinks })),
+        }
+    }
+}
+
+impl LogSink for FanOutSink {
+    fn emit(&self, event: &LogEvent) {
+        self.sinks.iter().for_each(|sink| sink.emit(event));
+    }
+}
+
+/// Sink that writes each event as a single line of JSON
+pub struct JsonLineSink<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLineSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Write a value as a single line of JSON and flush it so readers following the output see
+    /// it right away. Write failures are dropped, they must never abort a sync
+    pub fn write_line<T: Serialize>(&self, value: &T) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Ok(line) = serde_json::to_string(value) {
+            let _ = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+        }
+    }
+
+    /// Consume the sink and return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send> LogSink for JsonLineSink<W> {
+    fn emit(&self, event: &LogEvent) {
+        self.write_line(event);
+    }
+}
@@ -63,4 +63,53 @@ sol!{
     contract V2State {
         function getReserves() external view returns (uint256 reserve0, uint256 reserve1, uint256 blockTimestampLast);
     }
-}
\ No newline at end of file
+}
+
+sol!{
+    #[sol(rpc)]
+    contract ThreePool {
+        function balances(uint256 i) external view returns (uint256);
+        function A_precise() external view returns (uint256);
+        function fee() external view returns (uint256);
+        function get_dy(int128 i, int128 j, uint256 dx) external view returns (uint256);
+    }
+}
+
+sol!{
+    #[sol(rpc)]
+    contract TwoCryptoView {
+        function pool_count() external view returns (uint256);
+        function pool_list(uint256 i) external view returns (address);
+        function coins(uint256 i) external view returns (address);
+        function get_dy(uint256 i, uint256 j, uint256 dx) external view returns (uint256);
+    }
+}
+
+sol!{
+    #[sol(rpc)]
+    contract BalancerVaultQuery {
+        struct BatchSwapStep {
+            bytes32 poolId;
+            uint256 assetInIndex;
+            uint256 assetOutIndex;
+            uint256 amount;
+            bytes userData;
+        }
+        struct FundManagement {
+            address sender;
+            bool fromInternalBalance;
+            address recipient;
+            bool toInternalBalance;
+        }
+        function getPoolTokens(bytes32 poolId) external view returns (address[] tokens, uint256[] balances, uint256 lastChangeBlock);
+        function queryBatchSwap(uint8 kind, BatchSwapStep[] swaps, address[] assets, FundManagement funds) external returns (int256[] assetDeltas);
+    }
+}
+
+sol!{
+    #[sol(rpc)]
+    contract WeightedPoolState {
+        function getNormalizedWeights() external view returns (uint256[]);
+        function getSwapFeePercentage() external view returns (uint256);
+    }
+}
@@ -0,0 +1,533 @@
+#[cfg(test)]
+mod builder_test {
+    use alloy::primitives::{address, Address, U256};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::cache::PoolCache;
+    use crate::errors::PoolSyncError;
+    use crate::pool_filter::apply_filters;
+    use crate::pools::CURVE_POOL_REGISTRY;
+    use crate::{
+        BalancerV2Pool, Chain, JsonLineSink, LogEvent, MinReserveFilter, Pool, PoolInfo, PoolSync,
+        PoolType, RetryConfig, Rpc, TokenAllowlistFilter, UniswapV2Pool, UniswapV3Pool,
+    };
+
+    #[test]
+    fn test_unsupported_pool_errors_by_default() {
+        let result = PoolSync::builder()
+            .add_pools(&[PoolType::UniswapV2, PoolType::Aerodrome])
+            .chain(Chain::Ethereum)
+            .build();
+        assert!(matches!(result, Err(PoolSyncError::UnsupportedPoolType)));
+    }
+
+    #[test]
+    fn test_unsupported_chain_errors() {
+        // a custom chain without factories has nothing to sync, even with skip_unsupported
+        let devnet = Chain::Custom {
+            chain_id: 1337,
+            name: "Devnet".into(),
+        };
+        let result = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .chain(devnet.clone())
+            .skip_unsupported()
+            .build();
+        assert!(matches!(result, Err(PoolSyncError::UnsupportedChain(chain)) if chain == devnet));
+
+        assert!(Chain::Ethereum.is_supported());
+        assert!(!devnet.is_supported());
+    }
+
+    #[test]
+    fn test_skip_unsupported_drops_pool_types() {
+        let pool_sync = PoolSync::builder()
+            .add_pools(&[PoolType::UniswapV2, PoolType::Aerodrome])
+            .chain(Chain::Ethereum)
+            .skip_unsupported()
+            .build()
+            .unwrap();
+        assert!(pool_sync.fetchers.contains_key(&PoolType::UniswapV2));
+        assert!(!pool_sync.fetchers.contains_key(&PoolType::Aerodrome));
+    }
+
+    #[test]
+    fn test_custom_chain_uses_supplied_factory() {
+        let factory = address!("1111111111111111111111111111111111111111");
+        let chain = Chain::Custom {
+            chain_id: 1337,
+            name: "Devnet".into(),
+        };
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .custom_chain(1337, "Devnet", HashMap::from([(PoolType::UniswapV2, factory)]))
+            .build()
+            .unwrap();
+        let fetcher = &pool_sync.fetchers[&PoolType::UniswapV2];
+        assert_eq!(fetcher.factory_address(chain.clone()), Some(factory));
+        assert_eq!(fetcher.pool_type(), PoolType::UniswapV2);
+        assert_eq!(chain.to_string(), "Devnet");
+
+        // Pool types without a factory can not be synced on the custom chain
+        let result = PoolSync::builder()
+            .add_pool(PoolType::UniswapV3)
+            .custom_chain(1337, "Devnet", HashMap::from([(PoolType::UniswapV2, factory)]))
+            .build();
+        assert!(result.is_err());
+
+        // the name can come from runtime configuration
+        let name = format!("Devnet-{}", 1337);
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .custom_chain(1337, name, HashMap::from([(PoolType::UniswapV2, factory)]))
+            .build()
+            .unwrap();
+        assert_eq!(pool_sync.chain.to_string(), "Devnet-1337");
+        assert_eq!(pool_sync.chain.chain_id(), 1337);
+    }
+
+    #[test]
+    fn test_add_all_concentrated() {
+        let pool_sync = PoolSync::builder()
+            .chain(Chain::Ethereum)
+            .add_all_concentrated()
+            .build()
+            .unwrap();
+        assert!(pool_sync.fetchers.keys().all(|pool_type| pool_type.is_concentrated()));
+        assert!(pool_sync.fetchers.contains_key(&PoolType::UniswapV3));
+        assert!(pool_sync.fetchers.contains_key(&PoolType::MaverickV2));
+        assert!(!pool_sync.fetchers.contains_key(&PoolType::Slipstream));
+    }
+
+    #[test]
+    fn test_pool_type_all() {
+        let all = PoolType::all();
+        assert_eq!(all.len(), 25);
+        assert_eq!(all.iter().collect::<std::collections::HashSet<_>>().len(), all.len());
+
+        // every pool type has a fetcher, the custom chain accepts all of them
+        let factories = all
+            .iter()
+            .map(|pool_type| (*pool_type, Address::repeat_byte(1)))
+            .collect();
+        let pool_sync = PoolSync::builder()
+            .add_pools(all)
+            .custom_chain(1337, "Devnet", factories)
+            .build()
+            .unwrap();
+        for pool_type in all {
+            assert_eq!(pool_sync.fetchers[pool_type].pool_type(), *pool_type);
+        }
+    }
+
+    #[test]
+    fn test_supported_pools() {
+        assert!(Chain::Base.supported_pools().contains(&PoolType::Aerodrome));
+        assert!(!Chain::Ethereum.supported_pools().contains(&PoolType::Aerodrome));
+        assert_eq!(
+            Chain::Optimism.supported_pools(),
+            vec![PoolType::UniswapV3, PoolType::Velodrome]
+        );
+        let devnet = Chain::Custom {
+            chain_id: 1337,
+            name: "Devnet".into(),
+        };
+        assert!(devnet.supported_pools().is_empty());
+
+        let pool_sync = PoolSync::builder().add_all_supported(Chain::Base).build().unwrap();
+        assert_eq!(pool_sync.chain, Chain::Base);
+        assert_eq!(pool_sync.fetchers.len(), Chain::Base.supported_pools().len());
+        assert!(pool_sync.fetchers.contains_key(&PoolType::Slipstream));
+        // fetchers are visited in pool type order on every sync
+        assert!(pool_sync.fetchers.keys().is_sorted());
+        assert_eq!(pool_sync.fetchers.keys().next(), Some(&PoolType::UniswapV2));
+    }
+
+    #[test]
+    fn test_registry_discovery() {
+        let factory = address!("1111111111111111111111111111111111111111");
+        let pool_sync = PoolSync::builder()
+            .add_pools(&[PoolType::UniswapV2, PoolType::CurveTwoCrypto])
+            .custom_chain(
+                1337,
+                "Devnet",
+                HashMap::from([
+                    (PoolType::UniswapV2, factory),
+                    (PoolType::CurveTwoCrypto, factory),
+                ]),
+            )
+            .build()
+            .unwrap();
+
+        // curve factories list their pools, the wrapper keeps the protocol's strategy
+        assert_eq!(
+            pool_sync.fetchers[&PoolType::CurveTwoCrypto].pool_registry(),
+            Some(CURVE_POOL_REGISTRY)
+        );
+        assert!(pool_sync.fetchers[&PoolType::UniswapV2].pool_registry().is_none());
+    }
+
+    #[test]
+    fn test_metadata_concurrency() {
+        let builder = || {
+            PoolSync::builder()
+                .add_pool(PoolType::UniswapV2)
+                .chain(Chain::Ethereum)
+                .rate_limit(20)
+        };
+        assert_eq!(builder().build().unwrap().metadata_concurrency, 20);
+        let pool_sync = builder().metadata_concurrency(4).build().unwrap();
+        assert_eq!(pool_sync.metadata_concurrency, 4);
+        assert_eq!(pool_sync.rate_limit, 20);
+    }
+
+    #[test]
+    fn test_prune_stale_rejects_metadata_only() {
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .chain(Chain::Ethereum)
+            .metadata_only()
+            .build()
+            .unwrap();
+        assert!(matches!(
+            pool_sync.prune_stale(1_000),
+            Err(PoolSyncError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_start_block_override() {
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .add_pool(PoolType::UniswapV3)
+            .chain(Chain::Ethereum)
+            .start_block(PoolType::UniswapV2, 12_000_000)
+            .build()
+            .unwrap();
+        let mut caches = vec![
+            PoolCache::new(PoolType::UniswapV2, Chain::Ethereum),
+            PoolCache::new(PoolType::UniswapV3, Chain::Ethereum),
+        ];
+        pool_sync.apply_start_blocks(&mut caches);
+        assert_eq!(caches[0].last_synced_block, 11_999_999);
+        // without an override the factory deployment block is used
+        assert_eq!(caches[1].last_synced_block, 12_369_620);
+
+        // caches with progress are not moved
+        let mut cache = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        cache.is_initial_sync = false;
+        cache.last_synced_block = 11_000_000;
+        pool_sync.apply_start_blocks(std::slice::from_mut(&mut cache));
+        assert_eq!(cache.last_synced_block, 11_000_000);
+    }
+
+    #[test]
+    fn test_info_batch_and_liquidity_step() {
+        let builder = || PoolSync::builder().add_pool(PoolType::UniswapV3).chain(Chain::Ethereum);
+        let pool_sync = builder().build().unwrap();
+        assert_eq!(pool_sync.info_batch_size, None);
+        assert_eq!(pool_sync.liquidity_step, None);
+
+        let pool_sync = builder().info_batch_size(20).liquidity_step(500).build().unwrap();
+        assert_eq!(pool_sync.info_batch_size, Some(20));
+        assert_eq!(pool_sync.liquidity_step, Some(500));
+    }
+
+    #[test]
+    fn test_http_connection_settings() {
+        let builder = || PoolSync::builder().add_pool(PoolType::UniswapV2).chain(Chain::Ethereum);
+        assert!(matches!(
+            builder().http_pool_size(0).build(),
+            Err(PoolSyncError::InvalidConfig(_))
+        ));
+
+        let pool_sync = builder()
+            .http_pool_size(8)
+            .http_keepalive(Duration::from_secs(30))
+            .http_idle_timeout(Duration::from_secs(90))
+            .build()
+            .unwrap();
+        assert_eq!(pool_sync.http_pool_size, Some(8));
+        assert!(pool_sync.http_permits.is_empty());
+    }
+
+    #[test]
+    fn test_address_batch_size() {
+        let builder = || PoolSync::builder().add_pool(PoolType::UniswapV2).chain(Chain::Ethereum);
+        assert!(matches!(
+            builder().address_batch_size(0).build(),
+            Err(PoolSyncError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            builder().info_batch_size(0).build(),
+            Err(PoolSyncError::InvalidConfig(_))
+        ));
+
+        let pool_sync = builder().address_batch_size(2500).build().unwrap();
+        assert_eq!(pool_sync.address_batch_size, Some(2500));
+        let ranges = Rpc::get_block_range(pool_sync.address_batch_size.unwrap(), 1000, 8000);
+        assert_eq!(ranges, vec![(1000, 3499), (3500, 5999), (6000, 8000)]);
+    }
+
+    #[test]
+    fn test_on_progress() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .chain(Chain::Ethereum)
+            .on_progress(move |event| recorded.lock().unwrap().push(event.clone()))
+            .build()
+            .unwrap();
+
+        let sink = pool_sync.log_sink.as_ref().unwrap();
+        let fetched = LogEvent::AddressesFetched {
+            pool_type: PoolType::UniswapV2,
+            count: 3,
+        };
+        let discovered = LogEvent::PoolsDiscovered {
+            pool_type: PoolType::UniswapV2,
+            count: 2,
+        };
+        sink.emit(&fetched);
+        sink.emit(&discovered);
+        assert_eq!(*events.lock().unwrap(), vec![fetched, discovered]);
+    }
+
+    #[test]
+    fn test_on_progress_with_log_sink() {
+        // the callback and the sink both see every event, whichever was added first
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let json = Arc::new(JsonLineSink::new(Vec::new()));
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .chain(Chain::Ethereum)
+            .log_sink(json.clone())
+            .on_progress(move |event| recorded.lock().unwrap().push(event.clone()))
+            .build()
+            .unwrap();
+
+        // the events of a sync over one range of a single pool type
+        let sync = vec![
+            LogEvent::SyncStarted {
+                chain: Chain::Ethereum.to_string(),
+                pool_types: vec![PoolType::UniswapV2],
+            },
+            LogEvent::RangeStarted {
+                pool_type: PoolType::UniswapV2,
+                start_block: 100,
+                end_block: 200,
+            },
+            LogEvent::AddressesFetched {
+                pool_type: PoolType::UniswapV2,
+                count: 3,
+            },
+            LogEvent::PoolsDiscovered {
+                pool_type: PoolType::UniswapV2,
+                count: 3,
+            },
+            LogEvent::RangeSynced {
+                pool_type: PoolType::UniswapV2,
+                block: 200,
+                total_pools: 3,
+            },
+            LogEvent::SyncCompleted {
+                block: 200,
+                total_pools: 3,
+            },
+        ];
+        sync.iter().for_each(|event| pool_sync.emit(event.clone()));
+        assert_eq!(*events.lock().unwrap(), sync);
+
+        drop(pool_sync);
+        let output = String::from_utf8(Arc::into_inner(json).unwrap().into_inner()).unwrap();
+        let parsed: Vec<LogEvent> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed, sync);
+    }
+
+    #[test]
+    fn test_pool_filters() {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let v2 = |address: Address, reserve: u64| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                token0: usdc,
+                token1: weth,
+                token0_reserves: U256::from(reserve),
+                token1_reserves: U256::from(reserve),
+                ..Default::default()
+            })
+        };
+        let deep = v2(Address::repeat_byte(1), 1_000_000);
+        let shallow = v2(Address::repeat_byte(2), 10);
+        // reserves are not checked for v3 pools
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            address: Address::repeat_byte(3),
+            token0: usdc,
+            token1: weth,
+            ..Default::default()
+        });
+        let balancer = Pool::BalancerV2(BalancerV2Pool {
+            address: Address::repeat_byte(4),
+            token0: weth,
+            token1: usdc,
+            additional_tokens: vec![dai],
+            ..Default::default()
+        });
+
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .chain(Chain::Ethereum)
+            .with_filter(Box::new(MinReserveFilter::new(U256::from(1000))))
+            .with_filter(Box::new(TokenAllowlistFilter::new([weth, usdc])))
+            .build()
+            .unwrap();
+        let mut pools = vec![deep.clone(), shallow, v3.clone(), balancer];
+        apply_filters(&mut pools, &pool_sync.filters);
+        let kept: Vec<Address> = pools.iter().map(|pool| pool.address()).collect();
+        assert_eq!(kept, vec![deep.address(), v3.address()]);
+    }
+
+    #[test]
+    fn test_retry_config() {
+        let builder = || PoolSync::builder().add_pool(PoolType::UniswapV2).chain(Chain::Ethereum);
+        let pool_sync = builder().build().unwrap();
+        assert_eq!(pool_sync.retry.retry_limit, 5);
+        assert_eq!(pool_sync.retry.initial_backoff_ms, 1000);
+
+        let pool_sync = builder().retry_limit(10).initial_backoff_ms(250).build().unwrap();
+        assert_eq!(
+            pool_sync.retry,
+            RetryConfig {
+                retry_limit: 10,
+                initial_backoff_ms: 250,
+            }
+        );
+    }
+
+    #[test]
+    fn test_arbitrum_factories() {
+        let chain = Chain::Arbitrum;
+        assert!(chain.supported(&PoolType::UniswapV3));
+        assert!(!chain.supported(&PoolType::Aerodrome));
+
+        let pool_sync = PoolSync::builder()
+            .add_pools(&[PoolType::UniswapV3, PoolType::SushiSwapV2, PoolType::BalancerV2])
+            .chain(chain.clone())
+            .build()
+            .unwrap();
+        let factory = |pool_type| pool_sync.fetchers[&pool_type].factory_address(chain.clone());
+        assert_eq!(
+            factory(PoolType::UniswapV3),
+            Some(address!("1F98431c8aD98523631AE4a59f267346ea31F984"))
+        );
+        assert_eq!(
+            factory(PoolType::SushiSwapV2),
+            Some(address!("c35DADB65012eC5796536bD9864eD8773aBc74C4"))
+        );
+        assert_eq!(
+            factory(PoolType::BalancerV2),
+            Some(address!("c7E5ED1054A24Ef31D827E6F86caA58B3Bc168d7"))
+        );
+
+        // protocols that are not deployed on the chain have no factory instead of panicking
+        let pool_sync = PoolSync::builder()
+            .add_pool(PoolType::Aerodrome)
+            .chain(Chain::Base)
+            .build()
+            .unwrap();
+        assert_eq!(pool_sync.fetchers[&PoolType::Aerodrome].factory_address(chain.clone()), None);
+    }
+
+    #[test]
+    fn test_optimism_velodrome() {
+        let chain = Chain::Optimism;
+        assert!(chain.supported(&PoolType::Velodrome));
+        assert!(!Chain::Base.supported(&PoolType::Velodrome));
+
+        let pool_sync = PoolSync::builder()
+            .add_pools(&[PoolType::Velodrome, PoolType::UniswapV3])
+            .chain(chain.clone())
+            .build()
+            .unwrap();
+        let fetcher = &pool_sync.fetchers[&PoolType::Velodrome];
+        assert_eq!(fetcher.pool_type(), PoolType::Velodrome);
+        assert_eq!(
+            fetcher.factory_address(chain.clone()),
+            Some(address!("F1046053aa5682b4F9a81b5481394DA16BE5FF5a"))
+        );
+
+        // velodrome pools go through the v2 structure with the solidly stable flag
+        let pool = Pool::new_v2(
+            PoolType::Velodrome,
+            UniswapV2Pool {
+                stable: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(pool.is_v2());
+        assert!(pool.stable());
+        assert_eq!(pool.pool_type(), PoolType::Velodrome);
+    }
+
+    #[test]
+    fn test_chain_id_round_trip() {
+        for chain in [Chain::Ethereum, Chain::Base, Chain::Arbitrum, Chain::Optimism, Chain::BSC] {
+            assert_eq!(Chain::try_from(chain.chain_id()).unwrap(), chain);
+        }
+        let custom = Chain::Custom { chain_id: 1337, name: "Devnet".into() };
+        assert_eq!(custom.chain_id(), 1337);
+        assert!(matches!(
+            Chain::try_from(137),
+            Err(PoolSyncError::UnsupportedChainId(137))
+        ));
+    }
+
+    #[test]
+    fn test_bsc_factories() {
+        let chain = Chain::BSC;
+        let bsc_pools = [
+            PoolType::UniswapV3,
+            PoolType::SushiSwapV2,
+            PoolType::SushiSwapV3,
+            PoolType::PancakeSwapV2,
+            PoolType::PancakeSwapV3,
+            PoolType::CurveTwoCrypto,
+        ];
+        let pool_sync = PoolSync::builder()
+            .add_pools(&bsc_pools)
+            .chain(chain.clone())
+            .build()
+            .unwrap();
+        let factory = |pool_type| pool_sync.fetchers[&pool_type].factory_address(chain.clone());
+        let expected = [
+            address!("dB1d10011AD0Ff90774D0C6Bb92e5C5c8b4461F7"),
+            address!("c35DADB65012eC5796536bD9864eD8773aBc74C4"),
+            address!("126555dd55a39328F69400d6aE4F782Bd4C34ABb"),
+            address!("cA143Ce32Fe78f1f7019d7d551a6402fC5350c73"),
+            address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865"),
+            address!("98EE851a00abeE0d95D08cF4CA2BdCE32aeaAF7F"),
+        ];
+        for (pool_type, address) in bsc_pools.into_iter().zip(expected) {
+            assert!(chain.supported(&pool_type));
+            assert_eq!(factory(pool_type), Some(address), "{}", pool_type);
+        }
+
+        // protocols without a bsc deployment are rejected instead of using another chain's factory
+        assert!(!chain.supported(&PoolType::UniswapV2));
+        assert!(PoolSync::builder()
+            .add_pool(PoolType::UniswapV2)
+            .chain(chain.clone())
+            .build()
+            .is_err());
+    }
+}
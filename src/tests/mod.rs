@@ -1,2 +1,6 @@
 mod abi_gen;
-mod data_tests;
\ No newline at end of file
+mod builder_tests;
+mod data_tests;
+mod pricing_tests;
+mod sync_tests;
+mod v3_tests;
@@ -0,0 +1,797 @@
+#[cfg(test)]
+mod pricing_test {
+    use alloy::primitives::{address, Address, U256};
+    use std::collections::HashMap;
+
+    use crate::pool_sync::{
+        aggregate_pair_depth, filter_pair_pools, index_pools_by_token, rank_pools_by_liquidity,
+        robust_pair_price, select_reference_pools,
+    };
+    use crate::{
+        group_equivalent_pools, AmmSimulate, BalancerV2Pool, Chain, CurveStableSwapPool,
+        CurveTwoCryptoPool, Pool, PoolInfo, PoolType, Router, UniswapV2Pool, UniswapV3Pool,
+    };
+
+    const WETH: Address = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+    const USDC: Address = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+
+    // 1000 USDC / 1 WETH pool with the standard 0.3% fee
+    fn usdc_weth_v2() -> Pool {
+        Pool::UniswapV2(UniswapV2Pool {
+            token0: USDC,
+            token1: WETH,
+            token0_decimals: 6,
+            token1_decimals: 18,
+            token0_reserves: U256::from(1_000_000_000_000u64),
+            token1_reserves: U256::from(1_000_000_000_000_000_000_000u128),
+            ..Default::default()
+        })
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() / b.abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_v2_price() {
+        let pool = usdc_weth_v2();
+        assert_close(pool.price(WETH).unwrap(), 1000.0);
+        assert_close(pool.price(USDC).unwrap(), 0.001);
+        assert!(pool.price(Address::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_v2_bid_ask() {
+        let pool = usdc_weth_v2();
+        assert_close(pool.ask_price(WETH).unwrap(), 1000.0 / 0.997);
+        assert_close(pool.bid_price(WETH).unwrap(), 1000.0 * 0.997);
+    }
+
+    #[test]
+    fn test_v3_price() {
+        // sqrt price of 1 with equal decimals is a price of exactly 1
+        let pool = Pool::UniswapV3(UniswapV3Pool {
+            token0: USDC,
+            token1: WETH,
+            token0_decimals: 18,
+            token1_decimals: 18,
+            sqrt_price: U256::from(1) << 96,
+            fee: 500,
+            ..Default::default()
+        });
+        assert_close(pool.price(USDC).unwrap(), 1.0);
+        assert_close(pool.fee_fraction().unwrap(), 0.0005);
+        assert_close(pool.bid_price(WETH).unwrap(), 0.9995);
+    }
+
+    #[test]
+    fn test_fee_bps() {
+        assert_eq!(usdc_weth_v2().fee_bps(), 30);
+        let pancake = Pool::new_v2(PoolType::PancakeSwapV2, UniswapV2Pool::default()).unwrap();
+        assert_eq!(pancake.fee_bps(), 25);
+        assert_eq!(PoolType::Aerodrome.v2_fee_bps(), None);
+        assert_eq!(PoolType::UniswapV3.v2_fee_bps(), None);
+
+        let stable = Pool::Aerodrome(UniswapV2Pool {
+            stable: Some(true),
+            fee: Some(U256::from(5)),
+            ..Default::default()
+        });
+        assert_eq!(stable.fee_bps(), 5);
+        assert!(stable.stable());
+
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            fee: 3_000,
+            ..Default::default()
+        });
+        assert_eq!(v3.fee_bps(), 30);
+    }
+
+    #[test]
+    fn test_price_of_mainnet_pools() {
+        // USDC/WETH 0.05% pool at a sqrt price of 1771595571142957166518320255467520
+        let v3 = Pool::new_v3(
+            PoolType::UniswapV3,
+            UniswapV3Pool {
+                address: address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"),
+                token0: USDC,
+                token1: WETH,
+                token0_decimals: 6,
+                token1_decimals: 18,
+                sqrt_price: U256::from(1_771_595_571_142_957_166_518_320_255_467_520u128),
+                fee: 500,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_close(v3.price_of(WETH).unwrap(), 2000.0);
+        assert_close(v3.price_of(USDC).unwrap(), 0.0005);
+
+        // USDC/WETH uniswap v2 pair holding 30M USDC against 10k WETH
+        let v2 = Pool::new_v2(
+            PoolType::UniswapV2,
+            UniswapV2Pool {
+                address: address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"),
+                token0: USDC,
+                token1: WETH,
+                token0_decimals: 6,
+                token1_decimals: 18,
+                token0_reserves: U256::from(30_000_000_000_000u64),
+                token1_reserves: U256::from(10_000_000_000_000_000_000_000u128),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_close(v2.price_of(WETH).unwrap(), 3000.0);
+        assert_eq!(v2.price_of(USDC), v2.price(USDC));
+        assert!(v2.price_of(Address::ZERO).is_none());
+
+        let maverick = Pool::new_maverick(PoolType::MaverickV1, Default::default()).unwrap();
+        assert!(maverick.price_of(WETH).is_none());
+    }
+
+    #[test]
+    fn test_v2_apply_swap() {
+        let mut pool = Pool::UniswapV2(UniswapV2Pool {
+            token0: USDC,
+            token1: WETH,
+            token0_reserves: U256::from(1_000_000),
+            token1_reserves: U256::from(1_000_000),
+            ..Default::default()
+        });
+        let snapshot = pool.snapshot_for_sim();
+
+        // 10000 * 997 * 1e6 / (1e6 * 1000 + 10000 * 997)
+        let amount_out = pool.apply_swap(U256::from(10_000), USDC).unwrap();
+        assert_eq!(amount_out, U256::from(9871));
+        let pool = pool.get_v2().unwrap();
+        assert_eq!(pool.token0_reserves, U256::from(1_010_000));
+        assert_eq!(pool.token1_reserves, U256::from(1_000_000 - 9871));
+
+        // the snapshot is independent of the pool
+        assert_eq!(snapshot.get_v2().unwrap().token1_reserves, U256::from(1_000_000));
+    }
+
+    #[test]
+    fn test_v2_get_amount_out() {
+        let pool = usdc_weth_v2();
+        let pool = pool.get_v2().unwrap();
+        // router getAmountOut: 1e18 * 997 * 1e12 / (1e21 * 1000 + 1e18 * 997)
+        let amount_out = pool.get_amount_out(U256::from(10u64.pow(18)), WETH);
+        assert_eq!(amount_out, Some(U256::from(996_006_981)));
+        assert!(pool.get_amount_out(U256::from(1), Address::ZERO).is_none());
+
+        // pancake charges 25 bps: 1e18 * 9975 * 1e12 / (1e21 * 10000 + 1e18 * 9975)
+        let pancake = Pool::new_v2(PoolType::PancakeSwapV2, pool.clone()).unwrap();
+        let pancake = pancake.get_v2().unwrap();
+        let amount_out = pancake.get_amount_out(U256::from(10u64.pow(18)), WETH);
+        assert_eq!(amount_out, Some(U256::from(996_505_985)));
+        // router getAmountIn: 1e21 * 997e6 * 10000 / ((1e12 - 997e6) * 9975) + 1
+        let amount_in = pancake.get_amount_in(U256::from(997_000_000), USDC);
+        assert_eq!(amount_in, Some(U256::from(1_000_496_241_620_063_123u128)));
+
+        // stable pool with a 5 bps fee, the curve is flat around the balanced point
+        let stable = UniswapV2Pool {
+            token0_reserves: U256::from(1_000_000_000_000u64),
+            token1_reserves: U256::from(1_000_000_000_000_000_000_000_000u128),
+            stable: Some(true),
+            fee: Some(U256::from(5)),
+            ..pool.clone()
+        };
+        assert_eq!(
+            stable.get_amount_out(U256::from(1_000_000_000), USDC),
+            Some(U256::from(999_499_999_500_999_250_748u128))
+        );
+        assert_eq!(
+            stable.get_amount_out(U256::from(1_000_000_000_000_000_000_000u128), WETH),
+            Some(U256::from(999_499_999))
+        );
+    }
+
+    #[test]
+    fn test_amm_simulate_v2() {
+        let pool = usdc_weth_v2();
+        let e18 = U256::from(10u64.pow(18));
+        assert_eq!(pool.amount_out(WETH, e18), Some(U256::from(996_006_981)));
+
+        // the quoted input is the smallest that fills the output
+        let usdc_out = U256::from(996_006_981);
+        let amount_in = pool.amount_in(USDC, usdc_out).unwrap();
+        assert!(pool.amount_out(WETH, amount_in).unwrap() >= usdc_out);
+        assert!(pool.amount_out(WETH, amount_in - U256::from(1)).unwrap() < usdc_out);
+        let amount_in = pool.amount_in(WETH, U256::from(1_000_000_000)).unwrap();
+        assert!(pool.amount_out(USDC, amount_in).unwrap() >= U256::from(1_000_000_000));
+
+        // a 30 bps aerodrome fee is grossed up on the input
+        let mut aerodrome = usdc_weth_v2();
+        aerodrome.get_v2_mut().unwrap().fee = Some(U256::from(30));
+        let amount_in = aerodrome.amount_in(WETH, e18).unwrap();
+        assert!(aerodrome.amount_out(USDC, amount_in).unwrap() >= e18);
+
+        // draining the reserve or an unknown token has no quote
+        assert!(pool.amount_in(WETH, U256::from(1000) * e18).is_none());
+        assert!(pool.amount_out(Address::ZERO, e18).is_none());
+    }
+
+    #[test]
+    fn test_v2_impermanent_loss() {
+        let pool = usdc_weth_v2();
+        let pool = pool.get_v2().unwrap();
+        let entry = (pool.token0_reserves, pool.token1_reserves);
+        assert_eq!(pool.impermanent_loss(entry), 0.0);
+
+        // WETH doubles in price: 1 - 2 * sqrt(2) / 3
+        let doubled = (pool.token0_reserves / U256::from(2), pool.token1_reserves);
+        assert_close(pool.impermanent_loss(doubled), 0.057190958417936644);
+        // the loss is symmetric in the direction of the move
+        let halved = (pool.token0_reserves * U256::from(2), pool.token1_reserves);
+        assert_close(pool.impermanent_loss(halved), 0.057190958417936644);
+        assert_eq!(pool.impermanent_loss((U256::ZERO, U256::ZERO)), 0.0);
+    }
+
+    #[test]
+    fn test_verify_decimals() {
+        let mut pool = usdc_weth_v2();
+        assert_eq!(pool.decimals_verified(), None);
+
+        // only tokens in the map are compared
+        assert_eq!(pool.verify_decimals(&HashMap::from([(USDC, 6)])), Some(true));
+        assert_eq!(pool.decimals_verified(), Some(true));
+        assert_eq!(pool.verify_decimals(&HashMap::from([(USDC, 6), (WETH, 8)])), Some(false));
+        assert_eq!(pool.decimals_verified(), Some(false));
+        assert_eq!(pool.verify_decimals(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_reference_pools_pick_deepest() {
+        let shallow = usdc_weth_v2();
+        let deep = Pool::SushiSwapV2(UniswapV2Pool {
+            address: address!("397FF1542f962076d0BFE58eA045FfA2d347ACa0"),
+            token0: USDC,
+            token1: WETH,
+            token0_reserves: U256::from(5_000_000_000_000u64),
+            token1_reserves: U256::from(5_000_000_000_000_000_000_000u128),
+            ..Default::default()
+        });
+        // holds far more USDC but less WETH, ranking is on the WETH side
+        let lopsided = Pool::PancakeSwapV2(UniswapV2Pool {
+            address: address!("1111111111111111111111111111111111111111"),
+            token0: WETH,
+            token1: USDC,
+            token0_reserves: U256::from(2_000_000_000_000_000_000_000u128),
+            token1_reserves: U256::from(u64::MAX),
+            ..Default::default()
+        });
+
+        let pairs = Chain::Ethereum.reference_pairs();
+        let best = select_reference_pools(&pairs, vec![shallow, deep.clone(), lopsided]);
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[&(WETH, USDC)].address(), deep.address());
+    }
+
+    #[test]
+    fn test_balancer_amount_out_multi() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let e18 = U256::from(1_000_000_000_000_000_000u128);
+        // 20/40/40 WETH/USDC/DAI weighted pool with a 0.3% fee, all balances in 18 decimals
+        let pool = BalancerV2Pool {
+            token0: WETH,
+            token1: USDC,
+            token0_decimals: 18,
+            token1_decimals: 18,
+            additional_tokens: vec![dai],
+            additional_token_decimals: vec![18],
+            balances: vec![U256::from(1000) * e18, U256::from(4000) * e18, U256::from(4000) * e18],
+            weights: vec![
+                U256::from(200_000_000_000_000_000u128),
+                U256::from(400_000_000_000_000_000u128),
+                U256::from(400_000_000_000_000_000u128),
+            ],
+            swap_fee: U256::from(3_000_000_000_000_000u128),
+            ..Default::default()
+        };
+
+        // 1000 * (1 - (4000 / (4000 + 9.97)) ^ 2) computed at high precision
+        let amount_out = pool.get_amount_out_multi(U256::from(10) * e18, USDC, WETH);
+        let expected = 4_966_424_078_032_064_032f64;
+        assert!((f64::from(amount_out) - expected).abs() / expected < 1e-12);
+
+        // an exponent of 0.5 goes through the logarithm based power, which the pool rounds up by
+        // its 1e-14 error bound. 4000 * (1 - (1000 / (1000 + 9.97)) ^ 0.5) at high precision
+        let amount_out = pool.get_amount_out_multi(U256::from(10) * e18, WETH, dai);
+        let expected = U256::from(19_792_126_722_968_481_361u128);
+        let bound = U256::from(4000) * e18 / U256::from(100_000_000_000_000u64);
+        assert!(amount_out < expected && expected - amount_out < bound);
+
+        // equal weights reduce to the constant product formula and route through the third
+        // token, the pool rounds the base up so the output is at most a few wei below it
+        let amount_out = pool.get_amount_out_multi(U256::from(10) * e18, USDC, dai);
+        let amount_in = U256::from(10) * e18 * U256::from(997) / U256::from(1000);
+        let expected = U256::from(4000) * e18 * amount_in / (U256::from(4000) * e18 + amount_in);
+        assert!(amount_out <= expected && expected - amount_out < U256::from(10_000));
+
+        // tokens with fewer decimals are scaled to 18 and the output is scaled back down
+        let scaled = BalancerV2Pool {
+            token1_decimals: 6,
+            balances: vec![
+                U256::from(1000) * e18,
+                U256::from(4_000_000_000u64),
+                U256::from(4000) * e18,
+            ],
+            ..pool.clone()
+        };
+        let amount_out = scaled.get_amount_out_multi(U256::from(10) * e18, dai, USDC);
+        let unscaled = pool.get_amount_out_multi(U256::from(10) * e18, dai, USDC);
+        assert_eq!(amount_out, unscaled / U256::from(1_000_000_000_000u64));
+
+        // above the max in ratio and unknown tokens quote nothing
+        assert_eq!(pool.get_amount_out_multi(U256::from(1300) * e18, USDC, WETH), U256::ZERO);
+        assert_eq!(pool.get_amount_out_multi(e18, Address::ZERO, WETH), U256::ZERO);
+    }
+
+    #[test]
+    fn test_amm_simulate_balancer() {
+        const ONE: u128 = 1_000_000_000_000_000_000;
+        let e18 = U256::from(ONE);
+        // WETH/USDC pool with a 0.3% fee
+        let balancer = |weight0: u128| {
+            Pool::BalancerV2(BalancerV2Pool {
+                token0: WETH,
+                token1: USDC,
+                token0_decimals: 18,
+                token1_decimals: 18,
+                balances: vec![U256::from(1000) * e18, U256::from(4000) * e18],
+                weights: vec![U256::from(weight0), U256::from(ONE - weight0)],
+                swap_fee: U256::from(3_000_000_000_000_000u128),
+                ..Default::default()
+            })
+        };
+
+        // 80/20 weights
+        let pool = balancer(800_000_000_000_000_000);
+        let amount_out = pool.amount_out(USDC, U256::from(10) * e18).unwrap();
+        let expected = pool
+            .get_balancer()
+            .unwrap()
+            .get_amount_out_multi(U256::from(10) * e18, USDC, WETH);
+        assert_eq!(amount_out, expected);
+
+        // the exact output quote round trips within the error bound of the fixed point power
+        let amount_in = pool.amount_in(WETH, amount_out).unwrap();
+        let diff = f64::from(amount_in) / 1e19 - 1.0;
+        assert!(diff.abs() < 1e-10);
+
+        // with equal weights the input buys at least the requested output
+        let pool = balancer(ONE / 2);
+        let amount_in = pool.amount_in(USDC, e18).unwrap();
+        assert!(pool.amount_out(WETH, amount_in).unwrap() >= e18);
+        // and stays close to the constant product input 1000 * 1 / 3999 / 0.997
+        let expected = 1e21 / 3999.0 / 0.997;
+        assert!((f64::from(amount_in) - expected).abs() / expected < 1e-12);
+
+        // above the max out ratio there is no quote
+        assert!(pool.amount_in(WETH, U256::from(400) * e18).is_none());
+    }
+
+    #[test]
+    fn test_balancer_spot_price_multi() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let unknown = address!("1111111111111111111111111111111111111111");
+        let e18 = U256::from(1_000_000_000_000_000_000u128);
+        // 50/25/25 WETH/USDC/DAI pool holding 100 WETH, 100k USDC and 100k DAI. The prices of a
+        // real three token pool are checked against the vault in the data tests
+        let pool = BalancerV2Pool {
+            token0: WETH,
+            token1: USDC,
+            token0_decimals: 18,
+            token1_decimals: 6,
+            additional_tokens: vec![dai],
+            additional_token_decimals: vec![18],
+            balances: vec![
+                U256::from(100) * e18,
+                U256::from(100_000_000_000u64),
+                U256::from(100_000) * e18,
+            ],
+            weights: vec![
+                U256::from(500_000_000_000_000_000u128),
+                U256::from(250_000_000_000_000_000u128),
+                U256::from(250_000_000_000_000_000u128),
+            ],
+            ..Default::default()
+        };
+
+        // (100000 / 0.25) / (100 / 0.5) = 2000 USDC or DAI per WETH
+        let usdc_per_weth = pool.spot_price(USDC, WETH).unwrap();
+        assert!((usdc_per_weth - 2000.0).abs() < 1e-9);
+        let dai_per_weth = pool.spot_price(dai, WETH).unwrap();
+        assert!((dai_per_weth - 2000.0).abs() < 1e-9);
+        assert!((pool.spot_price(dai, USDC).unwrap() - 1.0).abs() < 1e-9);
+        assert!((pool.spot_price(WETH, dai).unwrap() - 0.0005).abs() < 1e-12);
+        assert_eq!(pool.price(WETH), pool.spot_price(USDC, WETH));
+        assert_eq!(pool.spot_price(unknown, WETH), None);
+
+        // every token of the pool is visible through PoolInfo
+        let pool = Pool::BalancerV2(pool);
+        assert_eq!(pool.all_tokens(), vec![WETH, USDC, dai]);
+        assert!(pool.contains_token(dai));
+    }
+
+    #[test]
+    fn test_group_equivalent_pools() {
+        let pools = vec![
+            // token order is flipped between forks but the pair is the same
+            usdc_weth_v2(),
+            Pool::SushiSwapV2(UniswapV2Pool {
+                token0: WETH,
+                token1: USDC,
+                ..Default::default()
+            }),
+            // same pair in a 0.3% v3 pool matches the v2 fee tier
+            Pool::UniswapV3(UniswapV3Pool {
+                token0: USDC,
+                token1: WETH,
+                fee: 3000,
+                ..Default::default()
+            }),
+            Pool::UniswapV3(UniswapV3Pool {
+                token0: USDC,
+                token1: WETH,
+                fee: 500,
+                ..Default::default()
+            }),
+            // no known fee
+            Pool::MaverickV1(Default::default()),
+        ];
+
+        let groups = group_equivalent_pools(&pools);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[&(USDC, WETH, 3000)].len(), 3);
+        assert_eq!(groups[&(USDC, WETH, 500)].len(), 1);
+    }
+
+    #[test]
+    fn test_pools_by_token() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let v2 = usdc_weth_v2();
+        let balancer = Pool::BalancerV2(BalancerV2Pool {
+            address: address!("1111111111111111111111111111111111111111"),
+            token0: WETH,
+            token1: USDC,
+            additional_tokens: vec![dai],
+            ..Default::default()
+        });
+
+        let index = index_pools_by_token(&[v2.clone(), balancer.clone()]);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index[&WETH], vec![v2.address(), balancer.address()]);
+        assert_eq!(index[&USDC], vec![v2.address(), balancer.address()]);
+        assert_eq!(index[&dai], vec![balancer.address()]);
+    }
+
+    #[test]
+    fn test_pool_info_tokens() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let v2 = usdc_weth_v2();
+        assert_eq!(v2.tokens(), (USDC, WETH));
+        assert_eq!(v2.decimals(), (6, 18));
+        assert_eq!(v2.all_tokens(), vec![USDC, WETH]);
+        assert!(v2.contains_token(WETH));
+        assert!(!v2.contains_token(dai));
+
+        let balancer = Pool::BalancerV2(BalancerV2Pool {
+            token0: WETH,
+            token1: USDC,
+            token0_decimals: 18,
+            token1_decimals: 6,
+            additional_tokens: vec![dai],
+            additional_token_decimals: vec![18],
+            ..Default::default()
+        });
+        assert_eq!(balancer.tokens(), (WETH, USDC));
+        assert_eq!(balancer.decimals(), (18, 6));
+        assert_eq!(balancer.all_tokens(), vec![WETH, USDC, dai]);
+        assert!(balancer.contains_token(dai));
+        assert!(!balancer.contains_token(Address::ZERO));
+    }
+
+    #[test]
+    fn test_tvl_usd() {
+        let prices = |token: Address| match token {
+            USDC => Some(1.0),
+            WETH => Some(1000.0),
+            _ => None,
+        };
+        // 1M USDC and 1000 WETH at $1000
+        let tvl = usdc_weth_v2().tvl_usd(prices).unwrap();
+        assert!((tvl - 2_000_000.0).abs() < 1e-6);
+
+        // a missing price leaves the value unknown
+        assert_eq!(usdc_weth_v2().tvl_usd(|token| (token == USDC).then_some(1.0)), None);
+
+        let balancer = Pool::BalancerV2(BalancerV2Pool {
+            token0: USDC,
+            token1: WETH,
+            token0_decimals: 6,
+            token1_decimals: 18,
+            balances: vec![
+                U256::from(500_000_000u64),
+                U256::from(2_000_000_000_000_000_000u128),
+            ],
+            ..Default::default()
+        });
+        let tvl = balancer.tvl_usd(prices).unwrap();
+        assert!((tvl - 2500.0).abs() < 1e-6);
+
+        // at a price of one both virtual reserves equal the active liquidity
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            token0: USDC,
+            token1: WETH,
+            token0_decimals: 18,
+            token1_decimals: 18,
+            liquidity: 1_000_000_000_000_000_000,
+            sqrt_price: U256::from(1) << 96,
+            ..Default::default()
+        });
+        let tvl = v3.tvl_usd(prices).unwrap();
+        assert!((tvl - 1001.0).abs() < 1e-6);
+
+        // pool types without tracked balances can not be valued
+        let curve = Pool::CurveTwoCrypto(CurveTwoCryptoPool {
+            token0: USDC,
+            token1: WETH,
+            ..Default::default()
+        });
+        assert_eq!(curve.tvl_usd(prices), None);
+    }
+
+    #[test]
+    fn test_filter_pair_pools() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let v2 = usdc_weth_v2();
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            address: address!("2222222222222222222222222222222222222222"),
+            token0: dai,
+            token1: WETH,
+            ..Default::default()
+        });
+        let balancer = Pool::BalancerV2(BalancerV2Pool {
+            address: address!("1111111111111111111111111111111111111111"),
+            token0: dai,
+            token1: WETH,
+            additional_tokens: vec![USDC],
+            ..Default::default()
+        });
+        let pools = vec![v2.clone(), v3.clone(), balancer.clone()];
+
+        let addresses = |pools: Vec<Pool>| -> Vec<Address> {
+            pools.iter().map(|pool| pool.address()).collect()
+        };
+        assert_eq!(
+            addresses(filter_pair_pools(pools.clone(), WETH, USDC)),
+            vec![v2.address(), balancer.address()]
+        );
+        assert_eq!(
+            addresses(filter_pair_pools(pools.clone(), dai, WETH)),
+            vec![v3.address(), balancer.address()]
+        );
+        assert!(filter_pair_pools(pools, dai, Address::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_robust_price() {
+        let e18 = 1_000_000_000_000_000_000u128;
+        let v2 = |address: Address, weth: u128, usdc: u128| {
+            Pool::SushiSwapV2(UniswapV2Pool {
+                address,
+                token0: WETH,
+                token1: USDC,
+                token0_decimals: 18,
+                token1_decimals: 6,
+                token0_reserves: U256::from(weth),
+                token1_reserves: U256::from(usdc),
+                ..Default::default()
+            })
+        };
+        let pools = vec![
+            usdc_weth_v2(),
+            v2(Address::repeat_byte(1), 1000 * e18, 1_010_000_000_000),
+            // shallow pool pushed far from the rest is ignored
+            v2(Address::repeat_byte(2), 250 * e18, 500_000_000_000),
+        ];
+
+        let price = robust_pair_price(&pools, WETH, USDC, 0.05).unwrap();
+        let expected = ((1000f64.ln() * 1.0 + 1010f64.ln() * 1.01) / 2.01).exp();
+        assert_close(price, expected);
+        // with no deviation limit the outlier pulls the price up
+        assert!(robust_pair_price(&pools, WETH, USDC, f64::MAX).unwrap() > expected);
+        assert!(robust_pair_price(&pools, WETH, Address::ZERO, 0.05).is_none());
+    }
+
+    #[test]
+    fn test_aggregate_depth() {
+        let e18 = 1_000_000_000_000_000_000u128;
+        // price of 1 with 2e18 liquidity, virtual reserves of 2e18 on both sides
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            token0: USDC,
+            token1: WETH,
+            liquidity: 2 * e18,
+            sqrt_price: U256::from(1) << 96,
+            ..Default::default()
+        });
+        let pools = vec![usdc_weth_v2(), v3, Pool::CurveTwoCrypto(Default::default())];
+
+        let depth = aggregate_pair_depth(&pools, WETH, USDC);
+        assert_eq!(depth.pool_count, 2);
+        assert_eq!(depth.total_reserve_a, U256::from(1000 * e18 + 2 * e18));
+        assert_eq!(depth.total_reserve_b, U256::from(1_000_000_000_000u128 + 2 * e18));
+        assert_eq!(aggregate_pair_depth(&pools, WETH, Address::ZERO).pool_count, 0);
+    }
+
+    #[test]
+    fn test_rank_pools_by_liquidity() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let v2 = |address, token0, token1, reserve0: u128, reserve1: u128, decimals0| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                token0,
+                token1,
+                token0_decimals: decimals0,
+                token1_decimals: 18,
+                token0_reserves: U256::from(reserve0),
+                token1_reserves: U256::from(reserve1),
+                ..Default::default()
+            })
+        };
+        // worth 2000 WETH
+        let usdc_weth = usdc_weth_v2();
+        // 10 WETH and 10k DAI, worth 20 WETH
+        let weth_dai = v2(Address::repeat_byte(1), WETH, dai, 10u128.pow(19), 10u128.pow(22), 18);
+        // 5M USDC and 5M DAI, no WETH but priced through the other pools at 10k WETH
+        let (usdc_reserve, dai_reserve) = (5 * 10u128.pow(12), 5 * 10u128.pow(24));
+        let usdc_dai = v2(Address::repeat_byte(2), USDC, dai, usdc_reserve, dai_reserve, 6);
+        let pools = vec![weth_dai, usdc_weth.clone(), usdc_dai];
+
+        let top = rank_pools_by_liquidity(pools.clone(), Some(WETH), 0.05, 2);
+        let addresses: Vec<Address> = top.iter().map(|pool| pool.address()).collect();
+        assert_eq!(addresses, vec![Address::repeat_byte(2), usdc_weth.address()]);
+
+        // without a native token the balances are only normalized by decimals
+        let top = rank_pools_by_liquidity(pools, None, 0.05, 10);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[2].address(), Address::repeat_byte(1));
+    }
+
+    #[test]
+    fn test_curve_two_crypto_get_dy() {
+        // balanced USDC/WETH pool at a price scale of 2500 with the usual factory parameters
+        let pool = CurveTwoCryptoPool {
+            token0: USDC,
+            token1: WETH,
+            token0_decimals: 6,
+            token1_decimals: 18,
+            balances: [
+                U256::from(2_000_000_000_000u64),
+                U256::from(800_000_000_000_000_000_000u128),
+            ],
+            price_scale: U256::from(2_500_000_000_000_000_000_000u128),
+            d: U256::from(4_000_000_000_000_000_000_000_000u128),
+            a: U256::from(400_000),
+            gamma: U256::from(145_000_000_000_000u64),
+            mid_fee: U256::from(26_000_000),
+            out_fee: U256::from(45_000_000),
+            fee_gamma: U256::from(230_000_000_000_000u64),
+            ..Default::default()
+        };
+
+        // quotes against a real pool live in the data tests. 1000 USDC is just under 0.4 WETH
+        // less the 0.26% mid fee
+        let e18 = U256::from(10u64.pow(18));
+        let after_fee =
+            U256::from(4) * e18 / U256::from(10) * U256::from(9974) / U256::from(10_000);
+        let dy = pool.get_dy(0, 1, U256::from(1_000_000_000)).unwrap();
+        assert!(dy < after_fee && dy > after_fee * U256::from(9999) / U256::from(10_000));
+        let dy = pool.get_dy(1, 0, e18).unwrap();
+        assert!(dy < U256::from(2_493_500_000u64) && dy > U256::from(2_490_000_000u64));
+        // a large trade moves along the curve and pays the imbalanced fee
+        let dy = pool.get_dy(0, 1, U256::from(500_000_000_000u64)).unwrap();
+        assert!(dy < U256::from(190) * e18);
+
+        // swaps the math overflows on have no quote instead of panicking
+        assert!(pool.get_dy(0, 1, U256::MAX).is_none());
+        let huge = CurveTwoCryptoPool {
+            d: U256::MAX / U256::from(2),
+            ..pool.clone()
+        };
+        assert!(huge.get_dy(0, 1, U256::from(1_000_000_000)).is_none());
+        assert!(pool.get_dy(0, 0, U256::from(1)).is_none());
+        assert!(pool.get_dy(0, 2, U256::from(1)).is_none());
+        assert!(CurveTwoCryptoPool::default().get_dy(0, 1, U256::from(1)).is_none());
+    }
+
+    #[test]
+    fn test_curve_stable_swap_get_dy() {
+        let e18 = U256::from(10u64.pow(18));
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        // balanced two coin pool at A = 100 and a 0.04% fee, quotes against 3pool
+        // on chain live in the data tests
+        let pool = CurveStableSwapPool::from_state(
+            Address::repeat_byte(2),
+            &[dai, USDC],
+            &[18, 18],
+            vec![U256::from(1000) * e18, U256::from(1000) * e18],
+            vec![e18, e18],
+            U256::from(10_000),
+            U256::from(4_000_000),
+        )
+        .unwrap();
+        // a balanced swap pays the fee and a sliver of slippage, never more than 1:1
+        let dy = pool.get_dy(0, 1, e18).unwrap();
+        let after_fee = e18 - e18 * U256::from(4) / U256::from(10_000);
+        assert!(dy < after_fee);
+        assert!(dy > after_fee - e18 / U256::from(100_000));
+        // larger trades get worse rates
+        let big = pool.get_dy(0, 1, U256::from(100) * e18).unwrap();
+        assert!(big < dy * U256::from(100));
+        assert!(pool.get_dy(0, 2, e18).is_none());
+        assert!(pool.get_dy(1, 1, e18).is_none());
+        assert!(pool.get_dy(0, 1, U256::ZERO).is_none());
+
+        // exact output quotes find the smallest input whose get_dy reaches the output
+        let curve = Pool::CurveStableSwap(pool.clone());
+        let amount_in = curve.amount_in(USDC, e18).unwrap();
+        assert!(pool.get_dy(0, 1, amount_in).unwrap() >= e18);
+        assert!(pool.get_dy(0, 1, amount_in - U256::from(1)).unwrap() < e18);
+        // the pool can not pay out more than it holds
+        assert!(curve.amount_in(USDC, U256::from(1000) * e18).is_none());
+
+        // plain pools hold two to four coins
+        let coins = [Address::repeat_byte(3); 5];
+        let state = |n: usize| vec![e18; n];
+        let build = |n: usize| {
+            CurveStableSwapPool::from_state(
+                Address::ZERO, &coins[..n], &vec![18; n], state(n), state(n), e18, e18,
+            )
+        };
+        assert!(build(1).is_none());
+        assert!(build(4).is_some());
+        assert!(build(5).is_none());
+    }
+
+    #[test]
+    fn test_router_triangle() {
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let e18 = 10u128.pow(18);
+        let v2 = |address: Address, token0, token1, reserve0: u128, reserve1: u128, decimals| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                token0,
+                token1,
+                token0_decimals: decimals,
+                token1_decimals: 18,
+                token0_reserves: U256::from(reserve0),
+                token1_reserves: U256::from(reserve1),
+                ..Default::default()
+            })
+        };
+        // WETH trades at 1000 USDC, but the direct WETH/DAI pool is shallow and prices WETH at
+        // 900 DAI, so the route through USDC pays more
+        let usdc_weth = usdc_weth_v2();
+        let usdc_dai = v2(Address::repeat_byte(1), USDC, dai, 10u128.pow(13), 10_000_000 * e18, 6);
+        let weth_dai = v2(Address::repeat_byte(2), WETH, dai, 10 * e18, 9000 * e18, 18);
+        let router = Router::new(vec![usdc_weth.clone(), usdc_dai, weth_dai]);
+        assert_eq!(router.pools().len(), 3);
+
+        let (path, amount_out) = router.best_amount_out(WETH, dai, U256::from(e18), 2).unwrap();
+        assert_eq!(path, vec![usdc_weth.address(), Address::repeat_byte(1)]);
+        let usdc = usdc_weth.amount_out(WETH, U256::from(e18)).unwrap();
+        assert_eq!(amount_out, router.pools()[1].amount_out(USDC, usdc).unwrap());
+
+        // a single hop only has the direct pool
+        let (path, _) = router.best_amount_out(WETH, dai, U256::from(e18), 1).unwrap();
+        assert_eq!(path, vec![Address::repeat_byte(2)]);
+
+        assert!(router.best_amount_out(WETH, dai, U256::from(e18), 0).is_none());
+        assert!(router.best_amount_out(WETH, WETH, U256::from(e18), 2).is_none());
+        assert!(router.best_amount_out(WETH, Address::ZERO, U256::from(e18), 2).is_none());
+    }
+}
@@ -0,0 +1,1657 @@
+#[cfg(test)]
+mod sync_test {
+    use alloy::primitives::{
+        address, hex,
+        aliases::{I24, U160, U24},
+        Address, FixedBytes, Log as PrimitiveLog, B256, I256, U256,
+    };
+    use alloy::rpc::types::{Filter, Log};
+    use alloy::sol_types::{SolEvent, SolValue};
+    use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+    use alloy::transports::{TransportError, TransportFut};
+    use futures::StreamExt;
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::{Duration, SystemTime};
+    use tokio::sync::Semaphore;
+    use tower::Service;
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use tokio_util::sync::CancellationToken;
+    use uniswap_v3_math::tick_math::{MAX_TICK, MIN_TICK};
+
+    use crate::cache::{cache_error, decode_cache, encode_cache, PoolCache, CACHE_VERSION};
+    use crate::events::{
+        AlgebraEvents, BalancerV2Event, DataEvents, MaverickV1Events, MaverickV2Events,
+        UniswapV4Events,
+    };
+    use crate::export::{write_pools_csv, write_pools_json};
+    use crate::errors::PoolSyncError;
+    use crate::pool_sync::{
+        apply_live_log, first_block_at, live_filters, page_pools, publish_updated_pools, PageIndex,
+        rewind_on_reorg, stream_synced_pools, synced_progress, unwind_live_reorg,
+        until_cancelled, LiveRange,
+    };
+    use crate::pools::gen::PoolStateReader;
+    use crate::pools::CURVE_POOL_REGISTRY;
+    use crate::pools::pool_builder::{is_revert, skip_reverted};
+    use crate::pools::pool_structures::maverick_structure::process_bin_data;
+    use crate::pools::pool_structures::v3_structure::{
+        position_near_price, process_tick_data, TickInfo,
+    };
+    use crate::pools::pool_structures::v4_structure::DYNAMIC_FEE_FLAG;
+    use crate::pools::token_metadata::{decode_symbol, split_cached};
+    use crate::rpc::{registry_call, LogScope};
+    use crate::snapshot::{apply_pool_state, apply_vault_balances, decode_leading};
+    use crate::transport::LimitedTransport;
+    use crate::{
+        BalancerV2Pool, Bin, JsonLineSink, LogCache, LogEvent, LogSink, MaverickPool, Pool,
+        PoolInfo,
+        Chain, CurveStableSwapPool, CurveTriCryptoPool, CurveTwoCryptoPool, PoolState, PoolType,
+        Rpc, UniswapV2Pool, UniswapV3Pool, UniswapV4Pool, POOL_FORMAT_VERSION,
+    };
+
+    const POOL: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+
+    fn to_rpc_log(event: &impl SolEvent, block_number: u64, log_index: u64) -> Log {
+        Log {
+            inner: PrimitiveLog {
+                address: POOL,
+                data: event.encode_log_data(),
+            },
+            block_number: Some(block_number),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    fn mint(amount: u128) -> DataEvents::Mint {
+        DataEvents::Mint {
+            sender: Address::ZERO,
+            owner: Address::ZERO,
+            tickLower: I24::try_from(-60).unwrap(),
+            tickUpper: I24::try_from(60).unwrap(),
+            amount,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+        }
+    }
+
+    fn burn(amount: u128) -> DataEvents::Burn {
+        DataEvents::Burn {
+            owner: Address::ZERO,
+            tickLower: I24::try_from(-60).unwrap(),
+            tickUpper: I24::try_from(60).unwrap(),
+            amount,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_intra_block_logs_applied_in_log_index_order() {
+        // A mint followed by a burn in the same block, fetched in the opposite order
+        let logs = vec![
+            to_rpc_log(&burn(400), 100, 7),
+            to_rpc_log(&mint(1_000), 99, 1),
+            to_rpc_log(&mint(500), 100, 3),
+        ];
+
+        let ordered = Rpc::order_logs(logs);
+        let order: Vec<(u64, u64)> = ordered
+            .values()
+            .flatten()
+            .map(|log| (log.block_number.unwrap(), log.log_index.unwrap()))
+            .collect();
+        assert_eq!(order, vec![(99, 1), (100, 3), (100, 7)]);
+
+        // Applying the ordered logs must not underflow on the burn
+        let mut pool = UniswapV3Pool {
+            address: POOL,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        for log in ordered.into_values().flatten() {
+            process_tick_data(&mut pool, log, PoolType::UniswapV3, true);
+        }
+        assert_eq!(pool.ticks[&-60].liquidity_gross, 1_100);
+        assert_eq!(pool.ticks[&-60].liquidity_net, 1_100);
+        assert_eq!(pool.ticks[&60].liquidity_net, -1_100);
+    }
+
+    #[test]
+    fn test_mint_at_tick_bounds() {
+        let mint_at = |tick_lower: i32, tick_upper: i32| {
+            let mint = DataEvents::Mint {
+                tickLower: I24::try_from(tick_lower).unwrap(),
+                tickUpper: I24::try_from(tick_upper).unwrap(),
+                ..mint(1_000)
+            };
+            to_rpc_log(&mint, 1, 0)
+        };
+        let mut pool = UniswapV3Pool {
+            tick_spacing: 1,
+            ..Default::default()
+        };
+
+        process_tick_data(&mut pool, mint_at(MIN_TICK, MAX_TICK), PoolType::UniswapV3, false);
+        assert_eq!(pool.ticks[&MAX_TICK].liquidity_net, -1_000);
+        assert_eq!(pool.ticks[&MIN_TICK].liquidity_net, 1_000);
+        assert!(pool.validate_bitmap().is_empty());
+
+        // a tick past the edge of the tick space is skipped along with the rest of the position
+        process_tick_data(&mut pool, mint_at(0, MAX_TICK + 1), PoolType::UniswapV3, false);
+        assert_eq!(pool.ticks.len(), 2);
+        assert_eq!(pool.liquidity, 1_000);
+    }
+
+    #[test]
+    fn test_algebra_swap_and_fee_events() {
+        let swap = |override_fee: u32| AlgebraEvents::Swap {
+            sender: Address::repeat_byte(1),
+            recipient: Address::repeat_byte(2),
+            amount0: I256::try_from(-1_000_000_000i64).unwrap(),
+            amount1: I256::try_from(400_000_000_000_000_000i64).unwrap(),
+            price: U160::from(3_961_408_125_713_216_879_677_197u128),
+            liquidity: 12_345_678_901_234,
+            tick: I24::try_from(-197_080).unwrap(),
+            overrideFee: U24::from(override_fee),
+            pluginFee: U24::ZERO,
+        };
+        let mut pool = UniswapV3Pool {
+            address: POOL,
+            fee: 500,
+            tick_spacing: 60,
+            algebra: true,
+            ..Default::default()
+        };
+
+        // the raw log decodes into the price, tick and liquidity after the swap
+        let log = to_rpc_log(&swap(0), 1, 0);
+        assert_eq!(log.topics().len(), 3);
+        process_tick_data(&mut pool, log, PoolType::Camelot, false);
+        assert_eq!(pool.sqrt_price, U256::from(3_961_408_125_713_216_879_677_197u128));
+        assert_eq!(pool.tick, -197_080);
+        assert_eq!(pool.liquidity, 12_345_678_901_234);
+        assert_eq!(pool.fee, 500);
+
+        // an overridden fee is what the swap was charged
+        process_tick_data(&mut pool, to_rpc_log(&swap(3_000), 2, 0), PoolType::Camelot, false);
+        assert_eq!(pool.fee, 3_000);
+
+        // older algebra pools announce fee changes on their own
+        let fee = to_rpc_log(&AlgebraEvents::Fee { fee: 150 }, 3, 0);
+        process_tick_data(&mut pool, fee.clone(), PoolType::Camelot, false);
+        assert_eq!(pool.fee, 150);
+        let mut v3 = UniswapV3Pool {
+            fee: 500,
+            ..Default::default()
+        };
+        process_tick_data(&mut v3, fee, PoolType::UniswapV3, false);
+        assert_eq!(v3.fee, 500);
+    }
+
+    #[test]
+    fn test_camelot_log_fixture() {
+        // raw Swap and Fee logs in the layout Camelot's Algebra 1.9 pools emit, a Swap with the
+        // V3 fields and no fee followed by the new dynamic fee
+        let raw_log = |topics: &[&str], data: &str| Log {
+            inner: PrimitiveLog::new_unchecked(
+                POOL,
+                topics.iter().map(|topic| topic.parse().unwrap()).collect(),
+                hex::decode(data).unwrap().into(),
+            ),
+            block_number: Some(1),
+            ..Default::default()
+        };
+        let swap = raw_log(
+            &[
+                "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67",
+                "0x0000000000000000000000001f721e2e82f6676fce4ea07a5958cf098d339e18",
+                "0x0000000000000000000000009ab2d7be3a3aa8e8f4b6a2e6da6a1e16b3f6d7f2",
+            ],
+            concat!(
+                "fffffffffffffffffffffffffffffffffffffffffffffffff90fa4a62c4e0000",
+                "00000000000000000000000000000000000000000000000000000000598a8410",
+                "0000000000000000000000000000000000000000000396ed0c13c44a40000000",
+                "000000000000000000000000000000000000000000000000112210f47de98115",
+                "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffd015f",
+            ),
+        );
+        let fee = raw_log(
+            &["0x598b9f043c813aa6be3426ca60d1c65d17256312890be5118dab55b0775ebe2a"],
+            "00000000000000000000000000000000000000000000000000000000000001c2",
+        );
+        let mut pools = HashMap::from([(
+            POOL,
+            Pool::Camelot(UniswapV3Pool {
+                address: POOL,
+                fee: 500,
+                tick_spacing: 60,
+                algebra: true,
+                ..Default::default()
+            }),
+        )]);
+
+        assert!(apply_live_log(&mut pools, swap));
+        assert!(apply_live_log(&mut pools, fee));
+        let pool = pools[&POOL].get_v3().unwrap();
+        assert_eq!(pool.sqrt_price, U256::from(4_339_505_179_874_779_662_909_440u128));
+        assert_eq!(pool.tick, -196_257);
+        assert_eq!(pool.liquidity, 1_234_567_890_123_456_789);
+        assert_eq!(pool.fee, 450);
+        // the dynamic fee is what PoolInfo reports for the pool
+        assert_eq!(pools[&POOL].fee(), 450);
+    }
+
+    #[test]
+    fn test_json_line_sink() {
+        let sink = JsonLineSink::new(Vec::new());
+        let events = vec![
+            LogEvent::RangeStarted {
+                pool_type: PoolType::UniswapV3,
+                start_block: 100,
+                end_block: 200,
+            },
+            LogEvent::SyncCompleted {
+                block: 200,
+                total_pools: 3,
+            },
+        ];
+        events.iter().for_each(|event| sink.emit(event));
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"event":"range_started","pool_type":"UniswapV3","start_block":100,"end_block":200}"#
+        );
+        let parsed: Vec<LogEvent> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed, events);
+    }
+
+    #[test]
+    fn test_log_cache() {
+        let dir = std::env::temp_dir().join(format!("pool_sync_log_cache_{}", std::process::id()));
+        let cache = LogCache::new(&dir).unwrap();
+        let logs = vec![to_rpc_log(&mint(100), 10, 0)];
+        let filter = Filter::new()
+            .events([DataEvents::Mint::SIGNATURE, DataEvents::Burn::SIGNATURE])
+            .from_block(0)
+            .to_block(1000);
+
+        // too close to the head to be final
+        cache.set_head(1010);
+        cache.insert(&filter, &logs).unwrap();
+        assert!(cache.get(&filter).is_none());
+
+        // the event order of the filter does not change the key
+        cache.set_head(2000);
+        cache.insert(&filter, &logs).unwrap();
+        let reordered = Filter::new()
+            .events([DataEvents::Burn::SIGNATURE, DataEvents::Mint::SIGNATURE])
+            .from_block(0)
+            .to_block(1000);
+        assert_eq!(cache.get(&reordered), Some(logs));
+        assert!(cache.get(&filter.clone().to_block(999)).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_revert() {
+        use alloy::contract::Error as ContractError;
+        use alloy::transports::{RpcError, TransportErrorKind};
+
+        let error_resp = |message: &str| {
+            let payload = format!(r#"{{"code":3,"message":"{}"}}"#, message);
+            anyhow::Error::from(ContractError::TransportError(RpcError::ErrorResp(
+                serde_json::from_str(&payload).unwrap(),
+            )))
+        };
+        assert!(is_revert(&error_resp("execution reverted")));
+        assert!(!is_revert(&error_resp("header not found")));
+        assert!(!is_revert(&anyhow::Error::from(
+            ContractError::TransportError(TransportErrorKind::backend_gone())
+        )));
+
+        // a reverting pool is skipped, a node failure fails the batch
+        let (a, b) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let read = |failure: &str| vec![(a, Ok(1)), (b, Err(error_resp(failure)))];
+        let pools = skip_reverted(PoolType::CurveStableSwap, read("execution reverted"));
+        assert_eq!(pools.unwrap(), vec![1]);
+        assert!(skip_reverted(PoolType::CurveStableSwap, read("header not found")).is_err());
+    }
+
+    #[test]
+    fn test_prune_inactive() {
+        let pool = |address: Address| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                ..Default::default()
+            })
+        };
+        let (active, stale, unknown) = (POOL, Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut cache = PoolCache {
+            last_synced_block: 0,
+            pool_type: PoolType::UniswapV2,
+            pools: vec![pool(active), pool(stale), pool(unknown)],
+            is_initial_sync: false,
+            discovery_block: 0,
+            pending_addrs: Vec::new(),
+            registry_count: 0,
+            last_activity: Default::default(),
+            version: CACHE_VERSION,
+            last_block_hash: None,
+            pool_format_version: POOL_FORMAT_VERSION,
+        };
+
+        cache.record_activity([(active, 950), (stale, 100)]);
+        // older activity does not move the block back
+        cache.record_activity([(active, 900)]);
+        assert_eq!(cache.prune_inactive(500), 1);
+
+        // a pool without recorded activity is not known to be stale
+        assert_eq!(cache.pools.len(), 2);
+        assert_eq!(cache.pools[0].address(), active);
+        assert_eq!(cache.pools[1].address(), unknown);
+        assert_eq!(cache.last_activity[&active], 950);
+        assert!(!cache.last_activity.contains_key(&stale));
+    }
+
+    #[test]
+    fn test_remove_pools() {
+        let pool = |address: Address| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                ..Default::default()
+            })
+        };
+        let addresses: Vec<Address> = (1..=4).map(Address::repeat_byte).collect();
+        let mut cache = PoolCache {
+            last_synced_block: 1000,
+            pool_type: PoolType::UniswapV2,
+            pools: addresses.iter().map(|address| pool(*address)).collect(),
+            is_initial_sync: false,
+            discovery_block: 0,
+            pending_addrs: vec![addresses[1]],
+            registry_count: 0,
+            last_activity: Default::default(),
+            version: CACHE_VERSION,
+            last_block_hash: None,
+            pool_format_version: POOL_FORMAT_VERSION,
+        };
+        cache.record_activity(addresses.iter().map(|address| (*address, 900)));
+
+        // an address that is not cached is not counted
+        let removed: HashSet<Address> = [addresses[0], addresses[1], POOL].into();
+        assert_eq!(cache.remove_pools(&removed), 2);
+        assert_eq!(cache.pools.len(), 2);
+        assert!(cache.pending_addrs.is_empty());
+        assert!(!cache.last_activity.contains_key(&addresses[0]));
+
+        assert_eq!(cache.remove_pools(&removed), 0);
+        cache.record_activity([(addresses[3], 990)]);
+        assert_eq!(cache.prune_inactive(950), 1);
+        assert_eq!(cache.pools[0].address(), addresses[3]);
+    }
+
+    #[test]
+    fn test_add_pending_skips_known_pools() {
+        let addresses: Vec<Address> = (1..=4).map(Address::repeat_byte).collect();
+        let mut cache = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        cache.pools.push(Pool::UniswapV2(UniswapV2Pool {
+            address: addresses[0],
+            ..Default::default()
+        }));
+        cache.pending_addrs.push(addresses[1]);
+
+        // cached, pending and repeated addresses are only queued once
+        let found = [addresses[0], addresses[1], addresses[2], addresses[2], addresses[3]];
+        assert_eq!(cache.add_pending(found), 2);
+        assert_eq!(cache.pending_addrs, addresses[1..].to_vec());
+
+        // once populated, rescanning the same range queues nothing
+        let populated = std::mem::take(&mut cache.pending_addrs);
+        cache.pools.extend(populated.into_iter().map(|address| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                ..Default::default()
+            })
+        }));
+        assert_eq!(cache.add_pending(found), 0);
+        assert!(cache.pending_addrs.is_empty());
+    }
+
+    #[test]
+    fn test_registry_call() {
+        let count = registry_call(CURVE_POOL_REGISTRY.count, None);
+        assert_eq!(count.to_vec(), hex!("956aae3a"));
+        // the index follows the selector as a full word
+        let list = registry_call(CURVE_POOL_REGISTRY.list, Some(7));
+        assert_eq!(list[..4], hex!("3a1d5d8e"));
+        assert_eq!(U256::from_be_slice(&list[4..]), U256::from(7));
+        assert_eq!(list.len(), 36);
+    }
+
+    #[test]
+    fn test_pool_log_scope() {
+        let v2 = Pool::UniswapV2(UniswapV2Pool {
+            address: POOL,
+            ..Default::default()
+        });
+        assert_eq!(Rpc::pool_log_scope(&[v2]), LogScope::Emitters(vec![POOL]));
+
+        // vault swaps all come from the vault, the pool id tells them apart
+        let pool_id = FixedBytes::repeat_byte(7);
+        let balancer = Pool::BalancerV2(BalancerV2Pool {
+            address: POOL,
+            pool_id,
+            ..Default::default()
+        });
+        assert_eq!(Rpc::pool_log_scope(&[balancer]), LogScope::PoolIds(vec![pool_id]));
+    }
+
+    #[test]
+    fn test_merge_pools_dedupes_by_address() {
+        let pool = |address: Address, reserve: u64| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                token0_reserves: U256::from(reserve),
+                ..Default::default()
+            })
+        };
+        let other = Address::repeat_byte(1);
+        let mut cache = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        cache.pools.push(pool(POOL, 1));
+
+        // rescanning a range rediscovers the cached pool
+        cache.merge_pools(vec![pool(POOL, 2), pool(other, 3)]);
+        assert_eq!(cache.pools.len(), 2);
+        assert_eq!(cache.pools.iter().filter(|p| p.address() == POOL).count(), 1);
+        let Pool::UniswapV2(merged) = &cache.pools[0] else {
+            panic!("expected a v2 pool");
+        };
+        assert_eq!(merged.token0_reserves, U256::from(2));
+        assert_eq!(cache.pools[1].address(), other);
+    }
+
+    #[test]
+    fn test_pool_format_round_trip() {
+        let (token0, token1) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let names = || ("USDC".to_string(), "WETH".to_string());
+        let pool_of = |pool_type: PoolType| -> Pool {
+            let (token0_name, token1_name) = names();
+            if pool_type.is_v2() {
+                let pool = UniswapV2Pool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    token0_reserves: U256::from(1_000),
+                    fee: Some(U256::from(5)),
+                    ..Default::default()
+                };
+                Pool::new_v2(pool_type, pool).unwrap()
+            } else if pool_type.is_v3() {
+                let tick = TickInfo {
+                    liquidity_net: -5,
+                    initialized: true,
+                    liquidity_gross: 5,
+                };
+                let pool = UniswapV3Pool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    fee: 500,
+                    ticks: HashMap::from([(-60, tick)]),
+                    ..Default::default()
+                };
+                Pool::new_v3(pool_type, pool).unwrap()
+            } else if pool_type.is_v4() {
+                Pool::UniswapV4(UniswapV4Pool {
+                    address: POOL,
+                    pool_id: FixedBytes::repeat_byte(3),
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    ..Default::default()
+                })
+            } else if pool_type.is_maverick() {
+                let pool = MaverickPool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    ..Default::default()
+                };
+                Pool::new_maverick(pool_type, pool).unwrap()
+            } else if pool_type.is_curve_two() {
+                let pool = CurveTwoCryptoPool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    ..Default::default()
+                };
+                Pool::new_curve_two(pool_type, pool).unwrap()
+            } else if pool_type.is_curve_tri() {
+                let pool = CurveTriCryptoPool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    ..Default::default()
+                };
+                Pool::new_curve_tri(pool_type, pool).unwrap()
+            } else if pool_type.is_curve_stable() {
+                let pool = CurveStableSwapPool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    balances: vec![U256::from(1), U256::from(2)],
+                    ..Default::default()
+                };
+                Pool::new_curve_stable(pool_type, pool).unwrap()
+            } else {
+                let pool = BalancerV2Pool {
+                    address: POOL,
+                    token0,
+                    token1,
+                    token0_name,
+                    token1_name,
+                    ..Default::default()
+                };
+                Pool::new_balancer(pool_type, pool).unwrap()
+            }
+        };
+
+        // every variant reads back as itself with the same state
+        for pool_type in PoolType::all() {
+            let pool = pool_of(*pool_type);
+            let bytes = pool.to_serialized().unwrap();
+            let decoded = Pool::from_serialized(&bytes).unwrap();
+            assert_eq!(decoded.pool_type(), *pool_type);
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&pool).unwrap(),
+                "{}",
+                pool_type
+            );
+        }
+
+        // the envelope and variant tagging are part of the format
+        let envelope: serde_json::Value =
+            serde_json::from_slice(&pool_of(PoolType::Aerodrome).to_serialized().unwrap())
+                .unwrap();
+        assert_eq!(envelope["format_version"], POOL_FORMAT_VERSION);
+        assert_eq!(envelope["pool"]["Aerodrome"]["token0_name"], "USDC");
+
+        // other versions and unversioned data are rejected before decoding
+        let pool = serde_json::to_value(pool_of(PoolType::UniswapV2)).unwrap();
+        let future = serde_json::json!({ "format_version": POOL_FORMAT_VERSION + 1, "pool": pool });
+        assert!(matches!(
+            Pool::from_serialized(future.to_string().as_bytes()),
+            Err(PoolSyncError::IncompatibleFormat { found, expected })
+                if found == POOL_FORMAT_VERSION + 1 && expected == POOL_FORMAT_VERSION
+        ));
+        assert!(matches!(
+            Pool::from_serialized(pool.to_string().as_bytes()),
+            Err(PoolSyncError::IncompatibleFormat { found: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_compressed_cache_round_trip() {
+        let ticks = (-2_000..2_000)
+            .map(|i| {
+                let info = TickInfo {
+                    liquidity_net: i as i128 * 1_000_000,
+                    initialized: true,
+                    liquidity_gross: 5_000_000_000,
+                };
+                (i * 60, info)
+            })
+            .collect();
+        let mut cache = PoolCache::new(PoolType::UniswapV3, Chain::Ethereum);
+        cache.pools.push(Pool::UniswapV3(UniswapV3Pool {
+            address: POOL,
+            tick_spacing: 60,
+            ticks,
+            ..Default::default()
+        }));
+
+        let plain = encode_cache(&cache, false).unwrap();
+        let compressed = encode_cache(&cache, true).unwrap();
+        assert!(compressed.len() < plain.len());
+
+        // compressed and plain caches load to the same state
+        let json = |cache: &PoolCache| serde_json::to_value(cache).unwrap();
+        assert_eq!(json(&decode_cache(&compressed).unwrap()), json(&cache));
+        assert_eq!(json(&decode_cache(&plain).unwrap()), json(&cache));
+    }
+
+    #[test]
+    fn test_cache_pool_format_version() {
+        let cache = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        assert_eq!(cache.pool_format_version, POOL_FORMAT_VERSION);
+
+        // caches from before the pool format was recorded hold the first format
+        let mut value = serde_json::to_value(&cache).unwrap();
+        value.as_object_mut().unwrap().remove("pool_format_version");
+        let old = decode_cache(value.to_string().as_bytes()).unwrap();
+        assert_eq!(old.pool_format_version, 1);
+
+        // pools of another format are rejected before they are decoded
+        value["pool_format_version"] = (POOL_FORMAT_VERSION + 1).into();
+        value["pools"] = serde_json::json!([{ "NotAPool": {} }]);
+        let err = decode_cache(value.to_string().as_bytes()).unwrap_err();
+        assert!(matches!(
+            cache_error(err),
+            PoolSyncError::IncompatibleFormat { found, expected }
+                if found == POOL_FORMAT_VERSION + 1 && expected == POOL_FORMAT_VERSION
+        ));
+        assert!(matches!(
+            cache_error(anyhow::anyhow!("unreadable")),
+            PoolSyncError::CacheError(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_first_block_at() {
+        // 12 second blocks from a genesis at 1000
+        let timestamp_of = |number: u64| async move { Ok(1000 + number * 12) };
+        assert_eq!(first_block_at(500, 1000, timestamp_of).await.unwrap(), 0);
+        assert_eq!(first_block_at(500, 1120, timestamp_of).await.unwrap(), 10);
+        // between two blocks rounds up to the later one
+        assert_eq!(first_block_at(500, 1121, timestamp_of).await.unwrap(), 11);
+        assert_eq!(first_block_at(500, 7000, timestamp_of).await.unwrap(), 500);
+        assert!(matches!(
+            first_block_at(500, 7001, timestamp_of).await,
+            Err(PoolSyncError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_cache_start_at() {
+        let mut cache = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        assert!(cache.start_at(15_537_394, Chain::Ethereum));
+        assert_eq!(cache.last_synced_block, 15_537_393);
+
+        // a start before the default block does not sync further back
+        let mut cache = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        assert!(cache.start_at(100, Chain::Ethereum));
+        assert_eq!(cache.last_synced_block, 9_999_999);
+
+        // caches with progress keep syncing from where they are
+        let mut cache = PoolCache::new(PoolType::UniswapV2, Chain::Base);
+        cache.pending_addrs.push(POOL);
+        assert!(!cache.start_at(1_000, Chain::Base));
+        assert_eq!(cache.last_synced_block, 0);
+    }
+
+    #[test]
+    fn test_rewind_on_reorg() {
+        let stored = FixedBytes::repeat_byte(1);
+        let cache = |pool_type: PoolType, pool: Pool| PoolCache {
+            last_synced_block: 20_000_000,
+            pool_type,
+            pools: vec![pool],
+            is_initial_sync: false,
+            discovery_block: 19_000_000,
+            pending_addrs: vec![],
+            registry_count: 0,
+            last_activity: Default::default(),
+            version: CACHE_VERSION,
+            last_block_hash: Some(stored),
+            pool_format_version: POOL_FORMAT_VERSION,
+        };
+        let v2 = Pool::UniswapV2(UniswapV2Pool {
+            address: POOL,
+            ..Default::default()
+        });
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            address: POOL,
+            ..Default::default()
+        });
+
+        // the block is still canonical
+        let mut v2_cache = cache(PoolType::UniswapV2, v2);
+        assert_eq!(rewind_on_reorg(&mut v2_cache, Some(stored), 64, Chain::Ethereum), None);
+        assert_eq!(v2_cache.last_synced_block, 20_000_000);
+
+        // a node that does not have the block yet is not a reorg
+        assert_eq!(rewind_on_reorg(&mut v2_cache, None, 64, Chain::Ethereum), None);
+        assert_eq!(v2_cache.last_block_hash, Some(stored));
+
+        // v2 reserves are absolute so only the reorged range is replayed
+        let reorged = Some(FixedBytes::repeat_byte(2));
+        let from_block = rewind_on_reorg(&mut v2_cache, reorged, 64, Chain::Ethereum);
+        assert_eq!(from_block, Some(20_000_000 - 63));
+        assert_eq!(v2_cache.last_synced_block, 20_000_000 - 64);
+        assert_eq!(v2_cache.pools.len(), 1);
+        assert_eq!(v2_cache.last_block_hash, None);
+        // nothing to compare against until the next sync stores a hash
+        assert_eq!(rewind_on_reorg(&mut v2_cache, reorged, 64, Chain::Ethereum), None);
+
+        // v3 pools would double count replayed liquidity, so they keep their progress and the
+        // pools active in the reorged range are read again
+        let mut v3_cache = cache(PoolType::UniswapV3, v3);
+        let quiet = Address::repeat_byte(1);
+        v3_cache.record_activity([(POOL, 19_999_990), (quiet, 19_000_000)]);
+        let from_block = rewind_on_reorg(&mut v3_cache, reorged, 64, Chain::Ethereum).unwrap();
+        assert_eq!(v3_cache.pools.len(), 1);
+        assert!(v3_cache.pending_addrs.is_empty());
+        assert_eq!(v3_cache.last_synced_block, 20_000_000);
+        assert!(!v3_cache.is_initial_sync);
+        assert_eq!(v3_cache.reorged_pools(from_block), HashSet::from([POOL]));
+    }
+
+    #[test]
+    fn test_rewind_restarts_discovery() {
+        let orphaned = Address::repeat_byte(3);
+        for pool_type in [PoolType::UniswapV2, PoolType::UniswapV3] {
+            // discovery ran ahead of the liquidity sync and queued a pool from the orphaned blocks
+            let mut cache = PoolCache {
+                last_synced_block: 20_000_000,
+                discovery_block: 20_000_010,
+                pending_addrs: vec![orphaned],
+                is_initial_sync: false,
+                last_block_hash: Some(FixedBytes::repeat_byte(1)),
+                ..PoolCache::new(pool_type, Chain::Ethereum)
+            };
+            assert_eq!(cache.discovery_start(), 20_000_011);
+
+            let reorged = Some(FixedBytes::repeat_byte(2));
+            let from_block = rewind_on_reorg(&mut cache, reorged, 64, Chain::Ethereum).unwrap();
+            assert_eq!(from_block, 20_000_000 - 63);
+            assert_eq!(cache.discovery_start(), from_block, "{}", pool_type);
+            assert!(cache.pending_addrs.is_empty());
+        }
+
+        // registry listed pools are not rediscovered by the rescan and stay queued
+        let mut cache = PoolCache {
+            last_synced_block: 20_000_000,
+            discovery_block: 20_000_010,
+            pending_addrs: vec![orphaned],
+            registry_count: 10,
+            last_block_hash: Some(FixedBytes::repeat_byte(1)),
+            ..PoolCache::new(PoolType::CurveStableSwap, Chain::Ethereum)
+        };
+        let reorged = Some(FixedBytes::repeat_byte(2));
+        let from_block = rewind_on_reorg(&mut cache, reorged, 64, Chain::Ethereum).unwrap();
+        assert_eq!(cache.discovery_start(), from_block);
+        assert_eq!(cache.pending_addrs, vec![orphaned]);
+
+        // a fresh cache has no cursor and starts after its last synced block
+        let fresh = PoolCache::new(PoolType::UniswapV2, Chain::Ethereum);
+        assert_eq!(fresh.discovery_start(), 10_000_000);
+    }
+
+    #[test]
+    fn test_export_pools() {
+        let v2 = Pool::UniswapV2(UniswapV2Pool {
+            address: POOL,
+            token0_name: "USDC".to_string(),
+            token1_name: "Wrapped, Ether".to_string(),
+            token0_decimals: 6,
+            token1_decimals: 18,
+            ..Default::default()
+        });
+        let v3 = Pool::UniswapV3(UniswapV3Pool {
+            address: Address::repeat_byte(1),
+            fee: 500,
+            ..Default::default()
+        });
+        let caches = || vec![Ok(vec![v2.clone()]), Ok(vec![]), Ok(vec![v3.clone()])];
+        let dir = std::env::temp_dir();
+
+        let json_path = dir.join(format!("pool_sync_export_{}.json", std::process::id()));
+        let file = std::fs::File::create(&json_path).unwrap();
+        write_pools_json(file, caches()).unwrap();
+        let pools: Vec<Pool> =
+            serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+        std::fs::remove_file(&json_path).unwrap();
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[1].address(), v3.address());
+        assert_eq!(pools[1].fee(), 500);
+
+        let csv_path = dir.join(format!("pool_sync_export_{}.csv", std::process::id()));
+        let file = std::fs::File::create(&csv_path).unwrap();
+        write_pools_csv(file, caches()).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        std::fs::remove_file(&csv_path).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with("address,pool_type,token0"));
+        assert_eq!(
+            rows[1],
+            format!(
+                "{},UniswapV2,{},{},USDC,\"Wrapped, Ether\",6,18,{}",
+                POOL,
+                Address::ZERO,
+                Address::ZERO,
+                v2.fee()
+            )
+        );
+
+        let failed = vec![Ok(vec![v2.clone()]), Err(PoolSyncError::UnsupportedPoolType)];
+        assert!(write_pools_csv(Vec::new(), failed).is_err());
+    }
+
+    #[test]
+    fn test_cache_migrations() {
+        // a cache written before versioning and activity tracking
+        let json = r#"{"last_synced_block":1000,"pool_type":"PancakeSwapV2","pools":[],
+            "is_initial_sync":false}"#;
+        let mut cache: PoolCache = serde_json::from_str(json).unwrap();
+        assert_eq!(cache.version, 0);
+        cache.pools.push(Pool::PancakeSwapV2(UniswapV2Pool {
+            address: POOL,
+            ..Default::default()
+        }));
+
+        assert!(cache.run_migrations());
+        assert_eq!(cache.version, CACHE_VERSION);
+        assert_eq!(cache.last_activity[&POOL], 1000);
+        assert_eq!(cache.pools[0].fee_bps(), 25);
+        // a current cache is left alone
+        assert!(!cache.run_migrations());
+        assert_eq!(cache.prune_inactive(900), 0);
+    }
+
+    #[tokio::test]
+    async fn test_until_cancelled() {
+        let token = CancellationToken::new();
+        assert_eq!(until_cancelled(Some(&token), async { 1 }).await, Some(1));
+
+        // a cancelled token stops a future that would never finish
+        token.cancel();
+        let pending = std::future::pending::<u64>();
+        assert_eq!(until_cancelled(Some(&token), pending).await, None);
+        assert_eq!(until_cancelled(None, async { 2 }).await, Some(2));
+    }
+
+    #[test]
+    fn test_synced_progress() {
+        let cache = |pool_type: PoolType, last_synced_block: u64| PoolCache {
+            last_synced_block,
+            pools: vec![Pool::UniswapV2(UniswapV2Pool {
+                address: Address::repeat_byte(last_synced_block as u8),
+                ..Default::default()
+            })],
+            is_initial_sync: false,
+            ..PoolCache::new(pool_type, Chain::Ethereum)
+        };
+
+        // a shut down sync is only as far along as its slowest pool type
+        let (pools, block) = synced_progress(vec![
+            cache(PoolType::UniswapV2, 12),
+            cache(PoolType::SushiSwapV2, 10),
+        ]);
+        assert_eq!(pools.len(), 2);
+        assert_eq!(block, 10);
+        assert_eq!(synced_progress(vec![]).1, 0);
+    }
+
+    #[test]
+    fn test_snapshot_decodes_leading_words() {
+        // full uniswap v3 slot0 return, the reader only declares the first two fields
+        let words: [U256; 7] = [
+            U256::from(1) << 96,
+            U256::MAX - U256::from(9), // tick of -10
+            U256::from(1),
+            U256::from(2),
+            U256::from(2),
+            U256::ZERO,
+            U256::from(1),
+        ];
+        let data: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes::<32>()).collect();
+        let slot0 = decode_leading::<PoolStateReader::slot0Call>(Ok(data.into()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(U256::from(slot0.sqrtPriceX96), U256::from(1) << 96);
+        assert_eq!(slot0.tick.as_i32(), -10);
+
+        // an address without code returns no data
+        let empty = decode_leading::<PoolStateReader::getReservesCall>(Ok(Default::default()));
+        assert!(empty.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_publish_updated_pools() {
+        let pool = |address: Address| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                ..Default::default()
+            })
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let ndjson = JsonLineSink::new(Vec::new());
+        let mut sent = HashMap::new();
+        let mut last_activity = HashMap::from([(POOL, 100)]);
+
+        publish_updated_pools(&[pool(POOL)], &last_activity, &mut sent, Some(&tx), Some(&ndjson))
+            .await
+            .unwrap();
+        // a later range without activity for the sent pool only publishes the new ones
+        let pools = [pool(POOL), pool(Address::ZERO)];
+        publish_updated_pools(&pools, &last_activity, &mut sent, Some(&tx), Some(&ndjson))
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().address(), POOL);
+        assert_eq!(rx.recv().await.unwrap().address(), Address::ZERO);
+        assert!(rx.try_recv().is_err());
+
+        // a range that updates a sent pool sends it again
+        last_activity.insert(POOL, 200);
+        publish_updated_pools(&pools, &last_activity, &mut sent, Some(&tx), Some(&ndjson))
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().address(), POOL);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(sent[&POOL], 200);
+
+        let output = String::from_utf8(ndjson.into_inner()).unwrap();
+        let lines: Vec<Pool> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].address(), Address::ZERO);
+        assert_eq!(lines[2].address(), POOL);
+
+        drop(rx);
+        let closed = publish_updated_pools(
+            &[pool(Address::repeat_byte(1))],
+            &last_activity,
+            &mut sent,
+            Some(&tx),
+            None::<&JsonLineSink<Vec<u8>>>,
+        )
+        .await;
+        assert!(matches!(closed, Err(PoolSyncError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_synced_pools() {
+        let pool = |address: Address| {
+            Pool::UniswapV2(UniswapV2Pool {
+                address,
+                ..Default::default()
+            })
+        };
+        // a single slot channel makes the sync wait on the consumer between pools
+        let sync = |fail: bool| {
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            let sync = async move {
+                for address in [POOL, Address::ZERO] {
+                    tx.send(pool(address)).await.unwrap();
+                }
+                if fail {
+                    Err(PoolSyncError::Cancelled)
+                } else {
+                    Ok(100)
+                }
+            };
+            stream_synced_pools(sync, rx)
+        };
+
+        let items: Vec<_> = sync(false).collect().await;
+        let addresses: Vec<Address> =
+            items.into_iter().map(|item| item.unwrap().address()).collect();
+        assert_eq!(addresses, vec![POOL, Address::ZERO]);
+
+        // pools sent before the failure come first
+        let items: Vec<_> = sync(true).collect().await;
+        assert_eq!(items.len(), 3);
+        assert!(items[..2].iter().all(|item| item.is_ok()));
+        assert!(matches!(items[2], Err(PoolSyncError::Cancelled)));
+    }
+
+    #[test]
+    fn test_position_near_price() {
+        let pool = |tick: i32| UniswapV3Pool {
+            tick,
+            ..Default::default()
+        };
+        let log = to_rpc_log(&mint(1_000), 1, 0);
+
+        // the position spans [-60, 60]
+        assert!(position_near_price(&pool(0), &log, 0));
+        assert!(position_near_price(&pool(160), &log, 100));
+        assert!(!position_near_price(&pool(161), &log, 100));
+        assert!(!position_near_price(&pool(-161), &to_rpc_log(&burn(1), 1, 0), 100));
+    }
+
+    #[test]
+    fn test_apply_spot_state() {
+        let mut v2 = Pool::UniswapV2(UniswapV2Pool::default());
+        let state = PoolState::V2 {
+            address: POOL,
+            reserve0: U256::from(10),
+            reserve1: U256::from(20),
+        };
+        apply_pool_state(&mut v2, &state);
+        assert_eq!(v2.get_v2().unwrap().token1_reserves, U256::from(20));
+
+        // a v3 state does not apply to a v2 pool
+        let v3_state = PoolState::V3 {
+            address: POOL,
+            sqrt_price: U256::from(1) << 96,
+            tick: 5,
+            liquidity: 7,
+        };
+        apply_pool_state(&mut v2, &v3_state);
+        assert_eq!(v2.get_v2().unwrap().token0_reserves, U256::from(10));
+        let mut v3 = Pool::UniswapV3(UniswapV3Pool::default());
+        apply_pool_state(&mut v3, &v3_state);
+        assert_eq!((v3.get_v3().unwrap().tick, v3.get_v3().unwrap().liquidity), (5, 7));
+
+        // the vault lists tokens in its own order
+        let (token0, token1) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut balancer = BalancerV2Pool {
+            token0,
+            token1,
+            balances: vec![U256::ZERO, U256::ZERO],
+            ..Default::default()
+        };
+        apply_vault_balances(&mut balancer, &[token1, token0], &[U256::from(2), U256::from(1)]);
+        assert_eq!(balancer.balances, vec![U256::from(1), U256::from(2)]);
+    }
+
+    #[test]
+    fn test_pool_completeness() {
+        let v2 = UniswapV2Pool {
+            address: POOL,
+            token0_name: "USDC".to_string(),
+            token1_name: "WETH".to_string(),
+            token0_decimals: 6,
+            token1_decimals: 18,
+            ..Default::default()
+        };
+        assert!(Pool::new_v2(PoolType::UniswapV2, v2.clone()).unwrap().is_complete(true));
+
+        let unnamed = UniswapV2Pool { token1_name: String::new(), ..v2.clone() };
+        assert!(!Pool::new_v2(PoolType::UniswapV2, unnamed).unwrap().is_complete(true));
+        let no_decimals = UniswapV2Pool { token0_decimals: 0, ..v2 };
+        assert!(!Pool::new_v2(PoolType::UniswapV2, no_decimals).unwrap().is_complete(true));
+
+        // V3 pools without ticks only count as incomplete when liquidity is synced
+        let v3 = Pool::new_v3(
+            PoolType::UniswapV3,
+            UniswapV3Pool {
+                token0_name: "USDC".to_string(),
+                token1_name: "WETH".to_string(),
+                token0_decimals: 6,
+                token1_decimals: 18,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!v3.is_complete(true));
+        assert!(v3.is_complete(false));
+    }
+
+    #[test]
+    fn test_page_pools() {
+        let cache = |start: u8, len: u8| -> Vec<Pool> {
+            (start..start + len)
+                .map(|i| {
+                    Pool::new_v2(
+                        PoolType::UniswapV2,
+                        UniswapV2Pool {
+                            address: Address::repeat_byte(i),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap()
+                })
+                .collect()
+        };
+        let stamp = Some((SystemTime::UNIX_EPOCH, 1));
+        let caches = [
+            (PoolType::UniswapV2, stamp),
+            (PoolType::SushiSwapV2, stamp),
+            (PoolType::UniswapV3, stamp),
+        ];
+        let reads = RefCell::new(Vec::new());
+        let load = |pool_type: PoolType| {
+            reads.borrow_mut().push(pool_type);
+            Ok(match pool_type {
+                PoolType::UniswapV2 => cache(1, 7),
+                PoolType::SushiSwapV2 => cache(8, 0),
+                _ => cache(8, 5),
+            })
+        };
+
+        // pages of 5 cover all 12 pools exactly once, reading each cache once
+        let mut index = PageIndex::default();
+        let mut seen = HashSet::new();
+        for offset in (0..15).step_by(5) {
+            for pool in page_pools(&mut index, caches, load, 5, offset).unwrap() {
+                assert!(seen.insert(pool.address()));
+            }
+        }
+        assert_eq!(seen.len(), 12);
+        assert_eq!(reads.borrow().len(), 3);
+
+        // only the caches the page overlaps are read, the empty one is skipped by its count
+        reads.borrow_mut().clear();
+        let page = page_pools(&mut index, caches, load, 3, 6).unwrap();
+        let addresses: Vec<Address> = page.iter().map(|pool| pool.address()).collect();
+        assert_eq!(addresses, (7..10).map(Address::repeat_byte).collect::<Vec<_>>());
+        assert_eq!(*reads.borrow(), vec![PoolType::UniswapV2, PoolType::UniswapV3]);
+        // the pools of the last cache read are kept
+        reads.borrow_mut().clear();
+        assert_eq!(page_pools(&mut index, caches, load, 5, 10).unwrap().len(), 2);
+        assert!(reads.borrow().is_empty());
+
+        // a cache written since is read again
+        reads.borrow_mut().clear();
+        let changed = [(PoolType::UniswapV2, Some((SystemTime::UNIX_EPOCH, 2)))];
+        assert_eq!(page_pools(&mut index, changed, load, 2, 0).unwrap().len(), 2);
+        assert_eq!(*reads.borrow(), vec![PoolType::UniswapV2]);
+
+        // caches past the page are never read
+        let failing = |pool_type: PoolType| match pool_type {
+            PoolType::UniswapV2 => Ok(cache(1, 3)),
+            _ => Err(PoolSyncError::CacheError("unread".to_string())),
+        };
+        let mut index = PageIndex::default();
+        assert_eq!(page_pools(&mut index, caches, failing, 2, 0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_live_filters() {
+        let v2 = Address::repeat_byte(2);
+        let curve = Address::repeat_byte(3);
+        let pool_id = B256::repeat_byte(4);
+        let v2_pool = UniswapV2Pool {
+            address: v2,
+            ..Default::default()
+        };
+        let curve_pool = CurveStableSwapPool {
+            address: curve,
+            ..Default::default()
+        };
+        let balancer_pool = BalancerV2Pool {
+            pool_id,
+            ..Default::default()
+        };
+        let pools = HashMap::from([
+            (v2, Pool::new_v2(PoolType::UniswapV2, v2_pool).unwrap()),
+            (curve, Pool::CurveStableSwap(curve_pool)),
+            (Address::from_slice(&pool_id[..20]), Pool::BalancerV2(balancer_pool)),
+        ]);
+
+        // pools are matched on their address or pool id, pools the live sync does not update
+        // are left out
+        let filters = live_filters(&pools);
+        assert_eq!(filters.len(), 2);
+        assert!(filters[0].address.matches(&v2));
+        assert!(!filters[0].address.matches(&curve));
+        assert!(filters[1].address.is_empty());
+        assert!(filters[1].topics[1].matches(&pool_id));
+        assert!(live_filters(&HashMap::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unwind_live_reorg() {
+        let range = |from_block: u64, to_block: u64, pool: u8| LiveRange {
+            from_block,
+            to_block,
+            hash: B256::repeat_byte(to_block as u8),
+            updated: HashSet::from([Address::repeat_byte(pool)]),
+        };
+        let ranges = || VecDeque::from([range(1, 10, 1), range(11, 11, 2), range(12, 12, 3)]);
+        // block 11 was replaced on the canonical chain, 10 is still canonical
+        let hash_of = |number: u64| async move {
+            Ok(Some(B256::repeat_byte(if number == 10 { 10 } else { 0xff })))
+        };
+
+        // a new header at an applied height replaces it and everything after
+        let mut recent = ranges();
+        let (from_block, touched) = unwind_live_reorg(&mut recent, 11, hash_of)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(from_block, 11);
+        assert_eq!(touched, HashSet::from([Address::repeat_byte(2), Address::repeat_byte(3)]));
+        assert_eq!(recent.len(), 1);
+
+        // a header that builds on the canonical chain replaces nothing
+        let mut recent = VecDeque::from([range(1, 10, 1)]);
+        assert!(unwind_live_reorg(&mut recent, 13, hash_of).await.unwrap().is_none());
+        assert_eq!(recent.len(), 1);
+
+        // a node without the block proves nothing
+        let mut recent = ranges();
+        let unknown = |_| async { Ok(None) };
+        assert!(unwind_live_reorg(&mut recent, 13, unknown).await.unwrap().is_none());
+        assert_eq!(recent.len(), 3);
+    }
+
+    #[test]
+    fn test_apply_live_log() {
+        let mut pools = HashMap::from([(
+            POOL,
+            Pool::new_v2(
+                PoolType::UniswapV2,
+                UniswapV2Pool {
+                    address: POOL,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        )]);
+        let sync = DataEvents::Sync {
+            reserve0: 1_000u128.try_into().unwrap(),
+            reserve1: 2_000u128.try_into().unwrap(),
+        };
+        assert!(apply_live_log(&mut pools, to_rpc_log(&sync, 1, 0)));
+        let pool = pools[&POOL].get_v2().unwrap();
+        assert_eq!(pool.token0_reserves, U256::from(1_000));
+        assert_eq!(pool.token1_reserves, U256::from(2_000));
+
+        // mints of a v2 pool address are not reserve updates
+        assert!(!apply_live_log(&mut pools, to_rpc_log(&mint(1), 1, 1)));
+
+        // liquidity changes are applied to v3 pools
+        let v3 = UniswapV3Pool {
+            address: POOL,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        pools.insert(POOL, Pool::new_v3(PoolType::UniswapV3, v3).unwrap());
+        assert!(apply_live_log(&mut pools, to_rpc_log(&mint(500), 2, 0)));
+        assert_eq!(pools[&POOL].get_v3().unwrap().liquidity, 500);
+
+        // logs of pools that are not tracked are ignored
+        let mut untracked = to_rpc_log(&sync, 3, 0);
+        untracked.inner.address = Address::ZERO;
+        assert!(!apply_live_log(&mut pools, untracked));
+    }
+
+    #[test]
+    fn test_mismatched_pool_type_errors() {
+        let v2 = Pool::new_v2(PoolType::UniswapV3, UniswapV2Pool::default());
+        assert!(matches!(v2, Err(PoolSyncError::UnsupportedPoolType)));
+        let v3 = Pool::new_v3(PoolType::BalancerV2, UniswapV3Pool::default());
+        assert!(matches!(v3, Err(PoolSyncError::UnsupportedPoolType)));
+        let balancer = Pool::new_balancer(PoolType::UniswapV2, BalancerV2Pool::default());
+        assert!(matches!(balancer, Err(PoolSyncError::UnsupportedPoolType)));
+    }
+
+    #[test]
+    fn test_v3_pool_types_keep_their_variant() {
+        let v3_types = [
+            PoolType::UniswapV3,
+            PoolType::SushiSwapV3,
+            PoolType::PancakeSwapV3,
+            PoolType::Slipstream,
+            PoolType::BaseSwapV3,
+            PoolType::SwapBasedV3,
+            PoolType::DackieSwapV3,
+            PoolType::AlienBaseV3,
+        ];
+        for pool_type in v3_types {
+            assert!(pool_type.is_v3());
+            let pool = Pool::new_v3(pool_type, UniswapV3Pool::default()).unwrap();
+            assert_eq!(pool.pool_type(), pool_type);
+        }
+    }
+
+    #[test]
+    fn test_maverick_bin_updates() {
+        let delta = |bin_id: u128, delta_a: u128, delta_b: u128| MaverickV1Events::BinDelta {
+            deltaA: delta_a,
+            deltaB: delta_b,
+            deltaLpBalance: U256::ZERO,
+            binId: bin_id,
+            kind: 0,
+            lowerTick: bin_id as i32,
+            isActive: true,
+        };
+        let mut pool = MaverickPool::default();
+
+        let add = MaverickV1Events::AddLiquidity {
+            sender: Address::ZERO,
+            tokenId: U256::from(1),
+            binDeltas: vec![delta(1, 100, 0), delta(2, 50, 70)],
+        };
+        process_bin_data(&mut pool, to_rpc_log(&add, 1, 0));
+        assert_eq!(pool.bins.len(), 2);
+        assert_eq!(pool.bins[&2].reserve_b, U256::from(70));
+
+        // a bin emptied by a removal is dropped, partial removals keep the rest
+        let remove = MaverickV1Events::RemoveLiquidity {
+            sender: Address::ZERO,
+            recipient: Address::ZERO,
+            tokenId: U256::from(1),
+            binDeltas: vec![delta(1, 100, 0), delta(2, 20, 70)],
+        };
+        process_bin_data(&mut pool, to_rpc_log(&remove, 2, 0));
+        assert!(!pool.bins.contains_key(&1));
+        assert_eq!(pool.bins[&2].reserve_a, U256::from(30));
+        assert_eq!(pool.bins[&2].reserve_b, U256::ZERO);
+
+        let swap = MaverickV1Events::Swap {
+            sender: Address::ZERO,
+            recipient: Address::ZERO,
+            tokenAIn: true,
+            exactOutput: false,
+            amountIn: U256::from(10),
+            amountOut: U256::from(9),
+            activeTick: -3,
+        };
+        process_bin_data(&mut pool, to_rpc_log(&swap, 3, 0));
+        assert_eq!(pool.active_tick, -3);
+    }
+
+    #[test]
+    fn test_maverick_swap_moves_bins() {
+        let bin = |lower_tick: i32, reserve_a: u64, reserve_b: u64| Bin {
+            reserve_a: U256::from(reserve_a),
+            reserve_b: U256::from(reserve_b),
+            lower_tick,
+            kind: 0,
+        };
+        let mut pool = MaverickPool {
+            bins: HashMap::from([
+                (1, bin(-2, 0, 50)),
+                (2, bin(-1, 0, 50)),
+                (3, bin(0, 100, 100)),
+                (4, bin(1, 80, 0)),
+            ]),
+            ..Default::default()
+        };
+        let swap = |token_a_in: bool, amount_in: u64, amount_out: u64, active_tick: i32| {
+            MaverickV1Events::Swap {
+                sender: Address::ZERO,
+                recipient: Address::ZERO,
+                tokenAIn: token_a_in,
+                exactOutput: false,
+                amountIn: U256::from(amount_in),
+                amountOut: U256::from(amount_out),
+                activeTick: active_tick,
+            }
+        };
+
+        // token a in takes token b from the active bin down to the new active tick and
+        // credits the input to the bins that paid out
+        process_bin_data(&mut pool, to_rpc_log(&swap(true, 130, 120, -1), 1, 0));
+        assert_eq!(pool.active_tick, -1);
+        assert_eq!(pool.bins[&3], bin(0, 208, 0));
+        assert_eq!(pool.bins[&2], bin(-1, 22, 30));
+        assert_eq!(pool.bins[&1], bin(-2, 0, 50));
+        assert_eq!(pool.bins[&4], bin(1, 80, 0));
+
+        // token b in moves back up, taking token a from the bins on the way
+        process_bin_data(&mut pool, to_rpc_log(&swap(false, 30, 25, 0), 2, 0));
+        assert_eq!(pool.active_tick, 0);
+        assert_eq!(pool.bins[&2], bin(-1, 0, 56));
+        assert_eq!(pool.bins[&3], bin(0, 205, 4));
+        assert_eq!(pool.bins[&4], bin(1, 80, 0));
+
+        // the totals move by exactly the swapped amounts
+        let total_b = pool.bins.values().fold(U256::ZERO, |total, bin| total + bin.reserve_b);
+        assert_eq!(total_b, U256::from(200 - 120 + 30));
+    }
+
+    #[test]
+    fn test_maverick_v2_add_liquidity_bins() {
+        let mut pool = MaverickPool::default();
+        let add = MaverickV2Events::PoolAddLiquidity {
+            sender: Address::ZERO,
+            recipient: Address::ZERO,
+            subaccount: U256::ZERO,
+            params: MaverickV2Events::AddLiquidityParams {
+                kind: 1,
+                ticks: vec![-2, 3],
+                amounts: vec![10, 20],
+            },
+            tokenAAmount: U256::from(5),
+            tokenBAmount: U256::from(7),
+            binIds: vec![7, 8],
+        };
+        process_bin_data(&mut pool, to_rpc_log(&add, 1, 0));
+        // the reserves of the bins are read from the pool afterwards
+        assert_eq!(pool.bins.len(), 2);
+        assert_eq!(pool.bins[&7].lower_tick, -2);
+        assert_eq!(pool.bins[&8].lower_tick, 3);
+        assert_eq!(pool.bins[&8].kind, 1);
+        assert!(pool.bins[&8].reserve_a.is_zero());
+    }
+
+    #[test]
+    fn test_balancer_vault_swap_updates_pool() {
+        let (token0, token1) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let pool_address = Address::repeat_byte(0xba);
+        let mut pool_id = [0u8; 32];
+        pool_id[..20].copy_from_slice(pool_address.as_slice());
+        let mut pools = HashMap::from([(
+            pool_address,
+            Pool::new_balancer(
+                PoolType::BalancerV2,
+                BalancerV2Pool {
+                    address: pool_address,
+                    pool_id: pool_id.into(),
+                    token0,
+                    token1,
+                    balances: vec![U256::from(1_000), U256::from(1_000)],
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        )]);
+
+        // the swap is emitted by the vault and routed to the pool through the pool id
+        let swap = BalancerV2Event::Swap {
+            poolId: pool_id.into(),
+            tokenIn: token1,
+            tokenOut: token0,
+            amountIn: U256::from(100),
+            amountOut: U256::from(90),
+        };
+        let log = to_rpc_log(&swap, 1, 0);
+        assert_eq!(Rpc::log_pool_address(&log), pool_address);
+        assert!(apply_live_log(&mut pools, log));
+        let balances = &pools[&pool_address].get_balancer().unwrap().balances;
+        assert_eq!(balances, &vec![U256::from(910), U256::from(1_100)]);
+    }
+
+    #[test]
+    fn test_v4_pool_from_events() {
+        let pool_id = FixedBytes::<32>::repeat_byte(0x44);
+        let pool_address = Address::repeat_byte(0x44);
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let initialize = UniswapV4Events::Initialize {
+            id: pool_id,
+            currency0: Address::ZERO,
+            currency1: usdc,
+            fee: U24::from(DYNAMIC_FEE_FLAG),
+            tickSpacing: I24::try_from(60).unwrap(),
+            hooks: Address::repeat_byte(0xaa),
+            sqrtPriceX96: U160::from(1u128 << 96),
+            tick: I24::ZERO,
+        };
+        let pool = UniswapV4Pool::from_initialize(&to_rpc_log(&initialize, 1, 0)).unwrap();
+        assert_eq!(pool.address, pool_address);
+        assert_eq!(pool.pool_id, pool_id);
+        assert_eq!((pool.token0, pool.token1), (Address::ZERO, usdc));
+        assert!(pool.dynamic_fee);
+        assert_eq!(pool.fee, 0);
+        // a native currency0 is still a valid pool
+        assert!(Pool::UniswapV4(pool.clone()).is_valid());
+        let mut pools = HashMap::from([(pool_address, Pool::UniswapV4(pool))]);
+
+        // the PoolManager emits the events, they are routed through the pool id
+        let modify = |ticks: (i32, i32), liquidity_delta: i64| UniswapV4Events::ModifyLiquidity {
+            id: pool_id,
+            sender: Address::ZERO,
+            tickLower: I24::try_from(ticks.0).unwrap(),
+            tickUpper: I24::try_from(ticks.1).unwrap(),
+            liquidityDelta: I256::try_from(liquidity_delta).unwrap(),
+            salt: FixedBytes::ZERO,
+        };
+        let log = to_rpc_log(&modify((-60, 60), 1_000), 2, 0);
+        assert_eq!(Rpc::log_pool_address(&log), pool_address);
+        assert!(apply_live_log(&mut pools, log));
+        // a position above the price only adds ticks
+        assert!(apply_live_log(&mut pools, to_rpc_log(&modify((60, 120), 500), 2, 1)));
+        let pool = pools[&pool_address].get_v4().unwrap();
+        assert_eq!(pool.liquidity, 1_000);
+        assert_eq!(pool.ticks[&60].liquidity_gross, 1_500);
+        assert_eq!(pool.ticks[&60].liquidity_net, -500);
+
+        // removing the whole position clears its lower tick
+        assert!(apply_live_log(&mut pools, to_rpc_log(&modify((-60, 60), -1_000), 3, 0)));
+        let pool = pools[&pool_address].get_v4().unwrap();
+        assert_eq!(pool.liquidity, 0);
+        assert!(!pool.ticks.contains_key(&-60));
+
+        let swap = UniswapV4Events::Swap {
+            id: pool_id,
+            sender: Address::ZERO,
+            amount0: -10,
+            amount1: 10,
+            sqrtPriceX96: U160::from(2u128 << 96),
+            liquidity: 500,
+            tick: I24::try_from(13_863).unwrap(),
+            fee: U24::from(3_000),
+        };
+        assert!(apply_live_log(&mut pools, to_rpc_log(&swap, 4, 0)));
+        let pool = &pools[&pool_address];
+        assert_eq!(pool.get_v4().unwrap().tick, 13_863);
+        assert_eq!(pool.fee(), 3_000);
+        assert_eq!(pool.price(Address::ZERO), Some(4.0));
+    }
+
+    #[test]
+    fn test_decode_symbol() {
+        assert_eq!(decode_symbol(&"WETH".to_string().abi_encode()), Some("WETH".to_string()));
+        // MKR style bytes32 symbols are zero padded
+        let bytes32 = FixedBytes::<32>::right_padding_from(b"MKR");
+        assert_eq!(decode_symbol(bytes32.as_slice()), Some("MKR".to_string()));
+        assert_eq!(decode_symbol(&String::new().abi_encode()), None);
+        assert_eq!(decode_symbol(&[0u8; 32]), None);
+        assert_eq!(decode_symbol(&[]), None);
+    }
+
+    #[test]
+    fn test_build_pool_decode_errors() {
+        use alloy::dyn_abi::DynSolValue;
+
+        let uint = |value: u64| DynSolValue::Uint(U256::from(value), 256);
+        let v2_data = vec![
+            DynSolValue::Address(POOL),
+            DynSolValue::Address(Address::repeat_byte(1)),
+            DynSolValue::Address(Address::repeat_byte(2)),
+            uint(6),
+            uint(18),
+            uint(1_000),
+            uint(2_000),
+        ];
+        let pool = PoolType::UniswapV2.build_pool(&v2_data).unwrap();
+        assert_eq!(pool.token0_decimals(), 6);
+        assert_eq!(pool.get_v2().unwrap().token1_reserves, U256::from(2_000));
+
+        // malformed data from the data sync contract is an error instead of a panic
+        assert!(matches!(
+            PoolType::UniswapV2.build_pool(&v2_data[..4]),
+            Err(PoolSyncError::DecodeError(_))
+        ));
+        let mut wrong_type = v2_data.clone();
+        wrong_type[1] = uint(1);
+        assert!(matches!(
+            PoolType::UniswapV2.build_pool(&wrong_type),
+            Err(PoolSyncError::DecodeError(_))
+        ));
+        let mut bad_decimals = v2_data.clone();
+        bad_decimals[3] = uint(256);
+        assert!(matches!(
+            PoolType::UniswapV2.build_pool(&bad_decimals),
+            Err(PoolSyncError::DecodeError(_))
+        ));
+        assert!(matches!(
+            PoolType::BalancerV2.build_pool(&v2_data),
+            Err(PoolSyncError::DecodeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_split_cached_tokens() {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let cache = dashmap::DashMap::new();
+
+        // nothing is cached for the first pool, both tokens are read
+        let (cached, missing) = split_cached(&[usdc, weth], &cache);
+        assert!(cached.is_empty());
+        assert_eq!(missing, vec![usdc, weth]);
+        cache.insert(usdc, ("USDC".to_string(), 6));
+        cache.insert(weth, ("WETH".to_string(), 18));
+
+        // a second pool sharing weth only reads its other token
+        let (cached, missing) = split_cached(&[dai, weth], &cache);
+        assert_eq!(missing, vec![dai]);
+        assert_eq!(cached[&weth], ("WETH".to_string(), 18));
+    }
+
+    #[tokio::test]
+    async fn test_limited_transport() {
+        // counts the requests in flight and answers each after a short wait
+        #[derive(Clone, Default)]
+        struct Counting {
+            open: Arc<AtomicUsize>,
+            max_open: Arc<AtomicUsize>,
+        }
+        impl Service<RequestPacket> for Counting {
+            type Response = ResponsePacket;
+            type Error = TransportError;
+            type Future = TransportFut<'static>;
+
+            fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), TransportError>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, _: RequestPacket) -> Self::Future {
+                let counting = self.clone();
+                Box::pin(async move {
+                    let open = counting.open.fetch_add(1, Ordering::SeqCst) + 1;
+                    counting.max_open.fetch_max(open, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    counting.open.fetch_sub(1, Ordering::SeqCst);
+                    Ok(ResponsePacket::Batch(Vec::new()))
+                })
+            }
+        }
+
+        let max_open = |permits: Option<Arc<Semaphore>>| async move {
+            let counting = Counting::default();
+            let transport = LimitedTransport::new(counting.clone(), permits);
+            let requests = (0..8).map(|_| transport.clone().call(RequestPacket::Batch(Vec::new())));
+            for response in futures::future::join_all(requests).await {
+                response.unwrap();
+            }
+            counting.max_open.load(Ordering::SeqCst)
+        };
+        assert_eq!(max_open(Some(Arc::new(Semaphore::new(3)))).await, 3);
+        assert_eq!(max_open(None).await, 8);
+    }
+}
@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod v3_test {
+    use alloy::primitives::{address, Address, U256};
+
+    use crate::errors::PoolSyncError;
+    use crate::pools::pool_structures::v3_structure::{flip_tick, modify_position};
+    use crate::UniswapV3Pool;
+
+    const TOKEN0: Address = address!("0000000000000000000000000000000000000001");
+    const TOKEN1: Address = address!("0000000000000000000000000000000000000002");
+    const E18: u128 = 1_000_000_000_000_000_000;
+
+    // Pool at price 1 with a narrow position nested inside a wide one
+    fn nested_positions_pool() -> UniswapV3Pool {
+        let mut pool = UniswapV3Pool {
+            token0: TOKEN0,
+            token1: TOKEN1,
+            tick_spacing: 10,
+            fee: 3000,
+            sqrt_price: U256::from(1) << 96,
+            ..Default::default()
+        };
+        modify_position(&mut pool, -100, 100, E18 as i128, false);
+        modify_position(&mut pool, -1000, 1000, 2 * E18 as i128, false);
+        pool
+    }
+
+    // Pool at tick 0 with three positions, two of which are in range
+    fn pool_with_positions() -> UniswapV3Pool {
+        let mut pool = UniswapV3Pool {
+            tick_spacing: 10,
+            ..Default::default()
+        };
+        modify_position(&mut pool, -100, 100, 1_000, false);
+        modify_position(&mut pool, -20, 50, 250, false);
+        modify_position(&mut pool, 200, 300, 5_000, false);
+        pool
+    }
+
+    #[test]
+    fn test_liquidity_from_ticks() {
+        let mut pool = pool_with_positions();
+        assert_eq!(pool.liquidity, 1_250);
+        assert_eq!(pool.liquidity_from_ticks(), 1_250);
+
+        // Above every position but the last one
+        pool.tick = 250;
+        assert_eq!(pool.liquidity_from_ticks(), 5_000);
+
+        // Below every position
+        pool.tick = -500;
+        assert_eq!(pool.liquidity_from_ticks(), 0);
+    }
+
+    #[test]
+    fn test_apply_swap_within_range() {
+        let mut pool = nested_positions_pool();
+        let amount_in = 1_000_000_000_000_000u128;
+        let amount_out = pool.apply_swap(U256::from(amount_in), TOKEN0).unwrap();
+
+        // Closed form for a swap that stays within one range at price 1
+        let liquidity = 3.0 * E18 as f64;
+        let amount_less_fee = amount_in as f64 * 0.997;
+        let expected = liquidity * amount_less_fee / (liquidity + amount_less_fee);
+        let relative_error = (u128::try_from(amount_out).unwrap() as f64 - expected).abs() / expected;
+        assert!(relative_error < 1e-9, "{} vs {}", amount_out, expected);
+        assert!(pool.sqrt_price < U256::from(1) << 96);
+        assert_eq!(pool.liquidity, 3 * E18);
+    }
+
+    #[test]
+    fn test_apply_swap_crosses_ticks() {
+        // zero for one crosses the lower tick of the narrow position
+        let mut pool = nested_positions_pool();
+        let amount_out = pool.apply_swap(U256::from(30_000_000_000_000_000u128), TOKEN0).unwrap();
+        assert!(amount_out > U256::ZERO);
+        assert_eq!(pool.liquidity, 2 * E18);
+        assert!(pool.tick < -100 && pool.tick > -1000, "tick {}", pool.tick);
+        assert_eq!(pool.liquidity_from_ticks(), pool.liquidity);
+
+        // one for zero crosses the upper tick of the narrow position
+        let mut pool = nested_positions_pool();
+        let amount_out = pool.apply_swap(U256::from(30_000_000_000_000_000u128), TOKEN1).unwrap();
+        assert!(amount_out > U256::ZERO);
+        assert_eq!(pool.liquidity, 2 * E18);
+        assert!(pool.tick >= 100 && pool.tick < 1000, "tick {}", pool.tick);
+    }
+
+    #[test]
+    fn test_simulate_swap() {
+        let pool = nested_positions_pool();
+        let amount_in = U256::from(30_000_000_000_000_000u128);
+
+        // matches applying the swap in both directions and leaves the pool as is
+        for token_in in [TOKEN0, TOKEN1] {
+            let amount_out = pool.simulate_swap(token_in, amount_in).unwrap();
+            assert_eq!(amount_out, pool.clone().apply_swap(amount_in, token_in).unwrap());
+        }
+        assert_eq!(pool.sqrt_price, U256::from(1) << 96);
+
+        // the output is limited by the liquidity of the wide position
+        let exhausted = pool.simulate_swap(TOKEN0, U256::from(1_000 * E18));
+        assert!(matches!(exhausted, Err(PoolSyncError::InsufficientLiquidity)));
+        let unknown = pool.simulate_swap(Address::ZERO, amount_in);
+        assert!(matches!(unknown, Err(PoolSyncError::SwapError(_))));
+    }
+
+    #[test]
+    fn test_apply_swap_unfillable() {
+        let mut pool = nested_positions_pool();
+        let unknown = pool.apply_swap(U256::from(1000), Address::ZERO);
+        assert!(matches!(unknown, Err(PoolSyncError::SwapError(_))));
+        assert_eq!(pool.sqrt_price, U256::from(1) << 96);
+
+        // running out of liquidity is not a partial fill, the pool is left as is
+        let exhausted = pool.apply_swap(U256::from(1_000 * E18), TOKEN0);
+        assert!(matches!(exhausted, Err(PoolSyncError::InsufficientLiquidity)));
+        assert_eq!(pool.sqrt_price, U256::from(1) << 96);
+        assert_eq!(pool.tick, 0);
+        assert_eq!(pool.liquidity, 3 * E18);
+    }
+
+    #[test]
+    fn test_truncate_ticks() {
+        let mut pool = nested_positions_pool();
+        modify_position(&mut pool, -5000, 5000, E18 as i128, false);
+        let full = pool.clone();
+
+        pool.truncate_ticks(4);
+        assert!(pool.ticks_truncated);
+        let mut kept: Vec<i32> = pool.ticks.keys().copied().collect();
+        kept.sort();
+        assert_eq!(kept, vec![-1000, -100, 100, 1000]);
+
+        // a swap inside the window quotes the same as with the full tick map
+        let amount_in = U256::from(30_000_000_000_000_000u128);
+        let mut full_swap = full.clone();
+        assert_eq!(
+            pool.clone().apply_swap(amount_in, TOKEN0).unwrap(),
+            full_swap.apply_swap(amount_in, TOKEN0).unwrap()
+        );
+
+        // burning the evicted position does not underflow
+        modify_position(&mut pool, -5000, 5000, -(E18 as i128), false);
+        assert_eq!(pool.liquidity, 3 * E18);
+    }
+
+    #[test]
+    fn test_validate_and_repair_bitmap() {
+        let mut pool = nested_positions_pool();
+        assert!(pool.validate_bitmap().is_empty());
+
+        // drop a flag for a live tick and flag a tick with no liquidity
+        flip_tick(&mut pool, -100, 10);
+        flip_tick(&mut pool, 2560, 10);
+        assert_eq!(pool.validate_bitmap(), vec![-100, 2560]);
+
+        let amount_in = U256::from(30_000_000_000_000_000u128);
+        pool.repair_bitmap();
+        assert!(pool.validate_bitmap().is_empty());
+        assert_eq!(
+            pool.apply_swap(amount_in, TOKEN0).unwrap(),
+            nested_positions_pool().apply_swap(amount_in, TOKEN0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_price_history() {
+        let mut pool = nested_positions_pool();
+        pool.set_price_history_depth(2);
+        let amount_in = U256::from(1_000_000_000_000_000u128);
+
+        let mut prices = Vec::new();
+        for _ in 0..3 {
+            pool.apply_swap(amount_in, TOKEN0).unwrap();
+            prices.push(pool.price(TOKEN0).unwrap());
+            pool.record_price();
+        }
+        assert_eq!(pool.recent_prices().iter().copied().collect::<Vec<_>>(), &prices[1..]);
+        assert!(prices[2] < prices[1]);
+
+        pool.set_price_history_depth(1);
+        assert_eq!(pool.recent_prices().iter().copied().collect::<Vec<_>>(), &prices[2..]);
+    }
+}
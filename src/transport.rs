@@ -0,0 +1,65 @@
+//! Connection Limited Transport
+//!
+//! reqwest has no cap on the connections it opens to a host, every concurrent request without
+//! an idle connection opens a new one. The transport here holds a permit of a semaphore shared by
+//! every provider of an endpoint while a request is in flight, which bounds the open connections.
+
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{TransportError, TransportErrorKind, TransportFut};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::Semaphore;
+use tower::Service;
+
+/// Transport that waits for a permit of `permits` before handing a request to `inner`. Without
+/// permits requests go straight through
+#[derive(Debug, Clone)]
+pub(crate) struct LimitedTransport<T> {
+    inner: T,
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl<T> LimitedTransport<T> {
+    pub(crate) fn new(inner: T, permits: Option<Arc<Semaphore>>) -> Self {
+        Self { inner, permits }
+    }
+}
+
+impl<T> Service<RequestPacket> for LimitedTransport<T>
+where
+    T: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let permits = self.permits.clone();
+        Box::pin(async move {
+            // the permit is released once the response is read and the connection is idle again
+            let _permit = match permits {
+                Some(permits) => Some(
+                    permits
+                        .acquire_owned()
+                        .await
+                        .map_err(|_| TransportErrorKind::custom_str("connection limit closed"))?,
+                ),
+                None => None,
+            };
+            inner.call(request).await
+        })
+    }
+}